@@ -1,5 +1,15 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
 use remotefs::file_system_service_client::FileSystemServiceClient;
+use sha2::{Digest, Sha256};
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use unicode_normalization::UnicodeNormalization;
 use remotefs::{
+    AuthenticateArg,
+    AuthenticateResult,
+    LogoutArg,
     MakeDirectoryArg,
     UploadArg,
     DownloadArg,
@@ -8,15 +18,130 @@ use remotefs::{
     DeleteArg,
     MoveArg,
     CopyArg,
+    GetAttributesArg,
 };
 
 pub mod remotefs {
     tonic::include_proto!("remotefs");
 }
 
+fn hmac_sha256 (key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Authenticates using SCRAM-SHA-256 (IETF RFC 7677), so that the password
+/// is never sent to the server.
+async fn scram_sha_256 (
+    client: &mut FileSystemServiceClient<Channel>,
+    username: &str,
+    password: &str,
+) -> Result<tonic::Response<AuthenticateResult>, Box<dyn std::error::Error>> {
+    let mut nonce_bytes = [0u8; 18];
+    rand::rngs::OsRng.fill_bytes(&mut nonce_bytes);
+    let client_nonce = BASE64.encode(nonce_bytes);
+    let username = username.replace('=', "=3D").replace(',', "=2C");
+    let client_first_bare = format!("n={},r={}", username, client_nonce);
+    let response = client.authenticate(tonic::Request::new(AuthenticateArg {
+        mechanism: String::from("SCRAM-SHA-256"),
+        assertion: format!("n,,{}", client_first_bare).into_bytes(),
+        ..Default::default()
+    })).await?;
+    if response.get_ref().decision.is_some() {
+        return Ok(response);
+    }
+    let server_first = String::from_utf8(response.get_ref().challenge.clone())?;
+    let mut nonce = "";
+    let mut salt = vec![];
+    let mut iterations = 0;
+    for attr in server_first.split(',') {
+        match attr.split_once('=') {
+            Some(("r", v)) => nonce = v,
+            Some(("s", v)) => salt = BASE64.decode(v)?,
+            Some(("i", v)) => iterations = v.parse()?,
+            _ => {},
+        };
+    }
+    if !nonce.starts_with(&client_nonce) {
+        return Err("server altered the nonce".into());
+    }
+    // This is the same approximation of SASLprep as the server makes.
+    let password = password.nfkc().collect::<String>();
+    let mut salted_password = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut salted_password);
+    let client_key = hmac_sha256(&salted_password, b"Client Key");
+    let stored_key = Sha256::digest(&client_key);
+    let without_proof = format!("c={},r={}", BASE64.encode("n,,"), nonce);
+    let auth_message = format!("{},{},{}", client_first_bare, server_first, without_proof);
+    let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+    let proof: Vec<u8> = client_key.iter().zip(client_signature.iter()).map(|(a, b)| a ^ b).collect();
+    let response = client.authenticate(tonic::Request::new(AuthenticateArg {
+        mechanism: String::from("SCRAM-SHA-256"),
+        assertion: format!("{},p={}", without_proof, BASE64.encode(proof)).into_bytes(),
+        continuation: response.get_ref().continuation.clone(),
+    })).await?;
+    if response.get_ref().decision == Some(true) {
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+        let server_signature = hmac_sha256(&server_key, auth_message.as_bytes());
+        let expected = format!("v={}", BASE64.encode(server_signature));
+        if response.get_ref().challenge != expected.as_bytes() {
+            return Err("server could not prove its identity".into());
+        }
+    }
+    Ok(response)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = FileSystemServiceClient::connect("http://127.0.0.1:50051").await?
+    // If the server uses TLS, the CA that issued its certificate must be
+    // supplied. A client certificate may also be supplied for SASL EXTERNAL.
+    let mut endpoint = Channel::from_static("http://127.0.0.1:50051");
+    let mut has_client_cert = false;
+    if let Ok(ca_path) = std::env::var("YEETBOX_TLS_CA") {
+        let ca = tokio::fs::read(ca_path).await?;
+        let mut tls_config = ClientTlsConfig::new()
+            .domain_name("localhost")
+            .ca_certificate(Certificate::from_pem(ca));
+        if let (Ok(cert_path), Ok(key_path)) = (std::env::var("YEETBOX_TLS_CLIENT_CERT"), std::env::var("YEETBOX_TLS_CLIENT_KEY")) {
+            let cert = tokio::fs::read(cert_path).await?;
+            let key = tokio::fs::read(key_path).await?;
+            tls_config = tls_config.identity(Identity::from_pem(cert, key));
+            has_client_cert = true;
+        }
+        endpoint = Channel::from_static("https://127.0.0.1:50051").tls_config(tls_config)?;
+    }
+    let channel = endpoint.connect().await?;
+    let mut authn_client = FileSystemServiceClient::new(channel.clone());
+
+    // Every operation requires a session, so we have to authenticate first.
+    let response = match (std::env::var("YEETBOX_USERNAME"), std::env::var("YEETBOX_PASSWORD")) {
+        (Ok(username), Ok(password)) => scram_sha_256(&mut authn_client, &username, &password).await?,
+        _ if std::env::var("YEETBOX_OAUTH_TOKEN").is_ok() => authn_client.authenticate(tonic::Request::new(AuthenticateArg {
+            mechanism: String::from("OAUTHBEARER"),
+            assertion: format!("n,,\x01auth=Bearer {}\x01\x01", std::env::var("YEETBOX_OAUTH_TOKEN")?).into_bytes(),
+            ..Default::default()
+        })).await?,
+        _ if has_client_cert => authn_client.authenticate(tonic::Request::new(AuthenticateArg {
+            mechanism: String::from("EXTERNAL"),
+            ..Default::default()
+        })).await?,
+        _ => authn_client.authenticate(tonic::Request::new(AuthenticateArg {
+            mechanism: String::from("ANONYMOUS"),
+            ..Default::default()
+        })).await?,
+    };
+    println!("RESPONSE={:?}", response);
+    if response.get_ref().decision != Some(true) {
+        return Err("authentication failed".into());
+    }
+
+    // The session token has to be sent with every subsequent request.
+    let token: MetadataValue<_> = format!("Bearer {}", response.get_ref().session_token).parse()?;
+    let mut client = FileSystemServiceClient::with_interceptor(channel, move |mut req: tonic::Request<()>| {
+        req.metadata_mut().insert("authorization", token.clone());
+        Ok(req)
+    })
         .max_decoding_message_size(8 * 1024 * 1024);
 
     let request = tonic::Request::new(MakeDirectoryArg {
@@ -170,5 +295,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let response = client.list(request4).await?;
     println!("RESPONSE={:?}", response);
 
+    let request9 = tonic::Request::new(GetAttributesArg {
+        target: Some(remotefs::FileId {
+            path: vec![
+                String::from("foo"),
+                String::from("quux.txt"),
+            ],
+            version: None,
+        }),
+    });
+
+    let response = client.get_attributes(request9).await?;
+    println!("RESPONSE={:?}", response);
+
+    let response = client.logout(tonic::Request::new(LogoutArg {})).await?;
+    println!("RESPONSE={:?}", response);
+
     Ok(())
 }
@@ -0,0 +1,769 @@
+use std::{collections::HashMap, net::IpAddr};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::prelude::*;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tonic::{metadata::MetadataMap, service::Interceptor, Request, Status};
+use ulid::Ulid;
+use unicode_normalization::UnicodeNormalization;
+use crate::config::ExternalAuthConfig;
+use crate::grpc::remotefs::{AuthenticateArg, AuthenticateResult, Mechanism};
+use crate::x509::{dn_satisfies, subject_clearances, subject_dn_attributes, Clearance};
+use std::sync::{Arc, Mutex};
+
+pub mod oauthbearer;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub type UserId = String;
+
+/// The length, in bytes, of the random part of a session token.
+pub const SESSION_TOKEN_LENGTH: usize = 32;
+
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+pub const SCRAM_SHA_256_PLUS: &str = "SCRAM-SHA-256-PLUS";
+
+/// IETF RFC 7677 requires at least 4096 iterations for SCRAM-SHA-256.
+pub const DEFAULT_SCRAM_ITERATIONS: u32 = 4096;
+
+/// Multi-step SASL exchanges that are not completed within this many seconds
+/// are abandoned.
+pub const SASL_EXCHANGE_TIMEOUT_SECS: i64 = 60;
+
+/// At most this many multi-step SASL exchanges may be in progress at once, so
+/// that unauthenticated clients cannot make the server hold on to an unbounded
+/// number of them. New exchanges are refused while this many are in progress.
+pub const MAX_SASL_EXCHANGES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Session {
+    pub user_id: Option<UserId>,
+    /// The groups of which the user is a member, as asserted by the
+    /// authenticator. Most authenticators have no concept of groups.
+    pub groups: Vec<String>,
+    pub auth_mech: String,
+    pub start_time: DateTime<Utc>,
+    /// The address of the peer that authenticated. Later requests using the
+    /// same session may come from elsewhere.
+    pub peer_ip: IpAddr,
+    pub peer_port: u16,
+    /// Security clearances, from the client certificate or configuration.
+    pub clearances: Vec<Clearance>,
+    // TODO: Permissions?
+}
+
+/// The outcome of a single authentication attempt.
+pub struct AuthnOutcome {
+    pub result: AuthenticateResult,
+
+    /// The new session, present only if the attempt succeeded.
+    pub session: Option<Session>,
+}
+
+impl AuthnOutcome {
+
+    pub fn reject () -> Self {
+        AuthnOutcome {
+            result: AuthenticateResult::reject(),
+            session: None,
+        }
+    }
+
+    pub fn accept (session: Session) -> Self {
+        AuthnOutcome {
+            result: AuthenticateResult::accept(),
+            session: Some(session),
+        }
+    }
+
+}
+
+#[tonic::async_trait]
+pub trait Authenticator {
+    async fn get_auth_mechs (&self) -> Vec<Mechanism>;
+
+    /// Run the SASL exchange for the peer. On success, the returned outcome
+    /// contains the `Session`, but issuing a token for it is up to the caller.
+    async fn attempt_authn (
+        &mut self,
+        request: &Request<AuthenticateArg>,
+    ) -> anyhow::Result<AuthnOutcome>;
+}
+
+/// An authenticator that delegates each attempt to the first of several
+/// authenticators that offers the requested mechanism.
+pub struct MultiAuth {
+    pub authenticators: Vec<Box<dyn Authenticator + Send + Sync + 'static>>,
+}
+
+impl MultiAuth {
+
+    pub fn new () -> Self {
+        MultiAuth { authenticators: vec![] }
+    }
+
+    pub fn add<A: Authenticator + Send + Sync + 'static> (&mut self, authenticator: A) {
+        self.authenticators.push(Box::new(authenticator));
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authenticator for MultiAuth {
+
+    async fn get_auth_mechs (&self) -> Vec<Mechanism> {
+        let mut mechs: Vec<Mechanism> = vec![];
+        for authenticator in self.authenticators.iter() {
+            for mech in authenticator.get_auth_mechs().await {
+                if !mechs.iter().any(|m| m.name == mech.name) {
+                    mechs.push(mech);
+                }
+            }
+        }
+        mechs
+    }
+
+    async fn attempt_authn (
+        &mut self,
+        request: &Request<AuthenticateArg>,
+    ) -> anyhow::Result<AuthnOutcome> {
+        let mechanism = &request.get_ref().mechanism;
+        for authenticator in self.authenticators.iter_mut() {
+            let offered = authenticator.get_auth_mechs().await
+                .iter()
+                .any(|m| &m.name == mechanism);
+            if offered {
+                return authenticator.attempt_authn(request).await;
+            }
+        }
+        Ok(AuthnOutcome::reject())
+    }
+
+}
+
+impl AuthenticateResult {
+
+    pub fn reject () -> Self {
+        AuthenticateResult{
+            decision: Some(false),
+            ..Default::default()
+        }
+    }
+
+    pub fn accept () -> Self {
+        AuthenticateResult{
+            decision: Some(true),
+            ..Default::default()
+        }
+    }
+
+}
+
+struct SessionEntry {
+    session: Session,
+    last_used: DateTime<Utc>,
+    expires: DateTime<Utc>,
+}
+
+/// Sessions, keyed by the bearer tokens that were issued for them.
+///
+/// A session ends when it has not been used for `idle_timeout`, when
+/// `absolute_timeout` has elapsed since it was created, or when the client
+/// logs out, whichever comes first.
+pub struct SessionStore {
+    sessions: HashMap<String, SessionEntry>,
+    pub idle_timeout: chrono::Duration,
+    pub absolute_timeout: chrono::Duration,
+}
+
+impl SessionStore {
+
+    pub fn new (idle_timeout: chrono::Duration, absolute_timeout: chrono::Duration) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            idle_timeout,
+            absolute_timeout,
+        }
+    }
+
+    /// Issues a new token for the session. Returns the token and the time at
+    /// which the session will expire, regardless of whether it is used.
+    pub fn create (&mut self, session: Session) -> (String, DateTime<Utc>) {
+        let now = Utc::now();
+        self.purge_expired(now);
+        let mut token_bytes = [0u8; SESSION_TOKEN_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut token_bytes);
+        let token = hex::encode(token_bytes);
+        let expires = now + self.absolute_timeout;
+        self.sessions.insert(token.clone(), SessionEntry {
+            session,
+            last_used: now,
+            expires,
+        });
+        (token, expires)
+    }
+
+    /// Returns the session for the token, if it exists and has not expired,
+    /// and resets its idle timer.
+    pub fn resolve (&mut self, token: &str) -> Option<Session> {
+        let now = Utc::now();
+        let entry = self.sessions.get_mut(token)?;
+        if now >= entry.expires || now >= entry.last_used + self.idle_timeout {
+            self.sessions.remove(token);
+            return None;
+        }
+        entry.last_used = now;
+        Some(entry.session.clone())
+    }
+
+    /// Ends the session. Returns `false` if there was no such session.
+    pub fn remove (&mut self, token: &str) -> bool {
+        self.sessions.remove(token).is_some()
+    }
+
+    fn purge_expired (&mut self, now: DateTime<Utc>) {
+        let idle_timeout = self.idle_timeout;
+        self.sessions.retain(|_, e| now < e.expires && now < e.last_used + idle_timeout);
+    }
+
+}
+
+/// Returns the session token from the `authorization` header, if the client
+/// supplied one using the `Bearer` scheme.
+pub fn get_bearer_token (metadata: &MetadataMap) -> Option<&str> {
+    metadata.get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|t| t.trim())
+}
+
+/// Inserted into the request extensions instead of a `Session` if the bearer
+/// token of the request was invalid or expired.
+#[derive(Clone, Copy, Debug)]
+pub struct InvalidSession;
+
+/// Resolves the bearer token of each request to a `Session`, which is inserted
+/// into the request extensions. Requests without a token are passed through
+/// without a session, because they are needed to authenticate in the first
+/// place. Requests with an invalid or expired token are passed through with an
+/// `InvalidSession` instead, so that they can be rejected by every RPC that
+/// requires a session, but not by `Logout`, which only needs the token.
+#[derive(Clone)]
+pub struct SessionInterceptor {
+    // This uses a synchronous mutex, because interceptors are not async.
+    pub sessions: Arc<Mutex<SessionStore>>,
+}
+
+impl Interceptor for SessionInterceptor {
+
+    fn call (&mut self, mut req: Request<()>) -> Result<Request<()>, Status> {
+        let maybe_session = match get_bearer_token(req.metadata()) {
+            Some(token) => {
+                let mut sessions = self.sessions.lock()
+                    .map_err(|_| Status::internal("session store poisoned"))?;
+                Some(sessions.resolve(token))
+            },
+            None => None,
+        };
+        match maybe_session {
+            Some(Some(session)) => { req.extensions_mut().insert(session); },
+            Some(None) => { req.extensions_mut().insert(InvalidSession); },
+            None => {},
+        };
+        Ok(req)
+    }
+
+}
+
+fn hmac_sha256 (key: &[u8], data: &[u8]) -> [u8; 32] {
+    // HMAC accepts keys of any length, so this cannot fail.
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// A salted and iterated password verifier, as defined in IETF RFC 5802. The
+/// password itself cannot be recovered from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScramCredential {
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+    pub stored_key: [u8; 32],
+    pub server_key: [u8; 32],
+}
+
+impl ScramCredential {
+
+    pub fn from_password (password: &str, iterations: u32) -> Self {
+        let mut salt = vec![0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        Self::derive(password, salt, iterations)
+    }
+
+    fn derive (password: &str, salt: Vec<u8>, iterations: u32) -> Self {
+        // This is an approximation of SASLprep, which is not implemented.
+        let password = password.nfkc().collect::<String>();
+        let mut salted_password = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut salted_password);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        ScramCredential {
+            salt,
+            iterations,
+            stored_key: Sha256::digest(client_key).into(),
+            server_key: hmac_sha256(&salted_password, b"Server Key"),
+        }
+    }
+
+    /// Checks a cleartext password, such as one supplied via PLAIN.
+    pub fn verify_password (&self, password: &str) -> bool {
+        let other = Self::derive(password, self.salt.clone(), self.iterations);
+        other.stored_key.ct_eq(&self.stored_key).into()
+    }
+
+    /// Parses the format defined in IETF RFC 5803, which is
+    /// `SCRAM-SHA-256$<iterations>:<salt>$<stored key>:<server key>`, where
+    /// the last three are base64-encoded.
+    pub fn parse (s: &str) -> Option<Self> {
+        let rest = s.strip_prefix("SCRAM-SHA-256$")?;
+        let (iter_salt, keys) = rest.split_once('$')?;
+        let (iterations, salt) = iter_salt.split_once(':')?;
+        let (stored_key, server_key) = keys.split_once(':')?;
+        Some(ScramCredential {
+            salt: BASE64.decode(salt).ok()?,
+            iterations: iterations.parse().ok()?,
+            stored_key: BASE64.decode(stored_key).ok()?.try_into().ok()?,
+            server_key: BASE64.decode(server_key).ok()?.try_into().ok()?,
+        })
+    }
+
+}
+
+impl std::fmt::Display for ScramCredential {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SCRAM-SHA-256${}:{}${}:{}",
+            self.iterations,
+            BASE64.encode(&self.salt),
+            BASE64.encode(self.stored_key),
+            BASE64.encode(self.server_key),
+        )
+    }
+
+}
+
+/// The state of a SCRAM exchange between the server-first-message and the
+/// client-final-message.
+struct ScramExchange {
+    start_time: DateTime<Utc>,
+    mechanism: String,
+    username: String,
+    gs2_header: String,
+    client_first_bare: String,
+    server_first: String,
+    nonce: String,
+}
+
+/// Decodes a SCRAM `saslname`, in which `,` and `=` are escaped.
+fn decode_saslname (name: &str) -> Option<String> {
+    let mut ret = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(i) = rest.find('=') {
+        ret.push_str(&rest[..i]);
+        match rest.get(i..i + 3) {
+            Some("=2C") => ret.push(','),
+            Some("=3D") => ret.push('='),
+            _ => return None,
+        };
+        rest = &rest[i + 3..];
+    }
+    ret.push_str(rest);
+    Some(ret)
+}
+
+pub struct SimpleAuth {
+    pub db: HashMap<String, ScramCredential>, // Username:Credential
+
+    /// If false, PLAIN, which transfers passwords in cleartext, is refused.
+    pub allow_plain: bool,
+
+    /// The `tls-server-end-point` channel binding data from IETF RFC 5929,
+    /// which is only present if TLS is in use. SCRAM-SHA-256-PLUS is only
+    /// offered if this is present.
+    pub tls_server_end_point: Option<Vec<u8>>,
+
+    /// If present, EXTERNAL is offered, which authenticates peers by the TLS
+    /// client certificates they presented.
+    pub external: Option<ExternalAuthConfig>,
+
+    /// Exchanges awaiting the client-final-message, keyed by continuation.
+    exchanges: HashMap<Ulid, ScramExchange>,
+
+    /// Used to derive consistent salts for users that do not exist, so that
+    /// SCRAM cannot be used to determine whether a user exists.
+    mock_secret: [u8; 32],
+}
+
+impl SimpleAuth {
+
+    pub fn new () -> Self {
+        let mut mock_secret = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut mock_secret);
+        Self {
+            db: HashMap::new(),
+            allow_plain: true,
+            tls_server_end_point: None,
+            external: None,
+            exchanges: HashMap::new(),
+            mock_secret,
+        }
+    }
+
+    /// Adds a user. The password may be given in cleartext, in which case it
+    /// is hashed immediately, or as a stored credential in the format of
+    /// IETF RFC 5803, so that the cleartext need not be configured at all.
+    pub fn add_user (&mut self, username: &str, password: &str) {
+        let cred = ScramCredential::parse(password)
+            .unwrap_or_else(|| ScramCredential::from_password(password, DEFAULT_SCRAM_ITERATIONS));
+        self.db.insert(username.to_owned(), cred);
+    }
+
+    /// Maps the subject DN of the peer's verified client certificate to a
+    /// user ID, and returns it along with any clearances in the certificate.
+    /// The assertion is the authorization identity, which, if present, must
+    /// be the same.
+    fn attempt_external (&self, request: &Request<AuthenticateArg>) -> anyhow::Result<Option<(UserId, Vec<Clearance>)>> {
+        let external = match self.external.as_ref() {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        // The TLS layer has already verified the chain against the client CA.
+        let certs = match request.peer_certs() {
+            Some(certs) => certs,
+            None => return Ok(None),
+        };
+        let end_entity = match certs.first() {
+            Some(cert) => cert,
+            None => return Ok(None),
+        };
+        let attrs = subject_dn_attributes(end_entity.get_ref())?;
+        if !dn_satisfies(&attrs, &external.restrictions) {
+            return Ok(None);
+        }
+        let user_id = match attrs.get(external.user_id_attribute.as_str()) {
+            Some(u) if u.len() > 0 => u.to_owned(),
+            _ => return Ok(None),
+        };
+        let authzid = std::str::from_utf8(&request.get_ref().assertion)?;
+        if authzid.len() > 0 && authzid != user_id {
+            return Ok(None);
+        }
+        let clearances = subject_clearances(end_entity.get_ref())?;
+        Ok(Some((user_id, clearances)))
+    }
+
+    fn scram_reject (&self) -> AuthnOutcome {
+        AuthnOutcome {
+            result: AuthenticateResult {
+                decision: Some(false),
+                challenge: b"e=other-error".to_vec(),
+                ..Default::default()
+            },
+            session: None,
+        }
+    }
+
+    /// Handles the client-first-message, and returns the server-first-message.
+    fn scram_server_first (&mut self, arg: &AuthenticateArg) -> anyhow::Result<AuthnOutcome> {
+        let plus = arg.mechanism == SCRAM_SHA_256_PLUS;
+        let msg = std::str::from_utf8(&arg.assertion)?;
+        let mut parts = msg.splitn(3, ',');
+        let (cbind_flag, authzid, client_first_bare) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(c), Some(a), Some(b)) => (c, a, b),
+            _ => return Ok(self.scram_reject()),
+        };
+        let cbind_ok = match cbind_flag {
+            "n" => !plus,
+            // The client supports channel binding, but thinks we do not. If
+            // we do, this indicates that the mechanism list was tampered with.
+            "y" => !plus && self.tls_server_end_point.is_none(),
+            "p=tls-server-end-point" => plus,
+            _ => false,
+        };
+        if !cbind_ok {
+            return Ok(self.scram_reject());
+        }
+        let mut attrs = client_first_bare.split(',');
+        let maybe_username = attrs.next()
+            .and_then(|a| a.strip_prefix("n="))
+            .and_then(decode_saslname);
+        let maybe_client_nonce = attrs.next().and_then(|a| a.strip_prefix("r="));
+        let (username, client_nonce) = match (maybe_username, maybe_client_nonce) {
+            (Some(u), Some(r)) if u.len() > 0 && r.len() > 0 => (u, r),
+            _ => return Ok(self.scram_reject()),
+        };
+        // Like PLAIN, there is no concept of an authorization identity here.
+        if authzid.len() > 0 && authzid.strip_prefix("a=").and_then(decode_saslname) != Some(username.clone()) {
+            return Ok(self.scram_reject());
+        }
+        let (salt, iterations) = match self.db.get(&username) {
+            Some(cred) => (cred.salt.clone(), cred.iterations),
+            None => (
+                hmac_sha256(&self.mock_secret, username.as_bytes())[0..16].to_vec(),
+                DEFAULT_SCRAM_ITERATIONS,
+            ),
+        };
+        let mut server_nonce = [0u8; 18];
+        rand::rngs::OsRng.fill_bytes(&mut server_nonce);
+        let nonce = format!("{}{}", client_nonce, BASE64.encode(server_nonce));
+        let server_first = format!("r={},s={},i={}", nonce, BASE64.encode(&salt), iterations);
+        let continuation = Ulid::new();
+        self.exchanges.insert(continuation, ScramExchange {
+            start_time: Utc::now(),
+            mechanism: arg.mechanism.clone(),
+            username,
+            gs2_header: format!("{},{},", cbind_flag, authzid),
+            client_first_bare: client_first_bare.to_owned(),
+            server_first: server_first.clone(),
+            nonce,
+        });
+        Ok(AuthnOutcome {
+            result: AuthenticateResult {
+                decision: None,
+                challenge: server_first.into_bytes(),
+                continuation: continuation.to_bytes().to_vec(),
+                ..Default::default()
+            },
+            session: None,
+        })
+    }
+
+    /// Handles the client-final-message, and returns the server-final-message.
+    fn scram_server_final (
+        &mut self,
+        peer: std::net::SocketAddr,
+        exchange: ScramExchange,
+        arg: &AuthenticateArg,
+    ) -> anyhow::Result<AuthnOutcome> {
+        let msg = std::str::from_utf8(&arg.assertion)?;
+        let (without_proof, proof) = match msg.rsplit_once(",p=") {
+            Some(x) => x,
+            None => return Ok(self.scram_reject()),
+        };
+        let mut attrs = without_proof.split(',');
+        let maybe_cbind = attrs.next().and_then(|a| a.strip_prefix("c="));
+        let maybe_nonce = attrs.next().and_then(|a| a.strip_prefix("r="));
+        let (cbind, nonce) = match (maybe_cbind, maybe_nonce) {
+            (Some(c), Some(r)) => (BASE64.decode(c)?, r),
+            _ => return Ok(self.scram_reject()),
+        };
+        if nonce != exchange.nonce {
+            return Ok(self.scram_reject());
+        }
+        let mut expected_cbind = exchange.gs2_header.clone().into_bytes();
+        if exchange.mechanism == SCRAM_SHA_256_PLUS {
+            match self.tls_server_end_point.as_ref() {
+                Some(cb) => expected_cbind.extend_from_slice(cb),
+                None => return Ok(self.scram_reject()),
+            };
+        }
+        if cbind != expected_cbind {
+            return Ok(self.scram_reject());
+        }
+        let cred = match self.db.get(&exchange.username) {
+            Some(c) => c,
+            None => return Ok(self.scram_reject()),
+        };
+        let proof = BASE64.decode(proof)?;
+        if proof.len() != 32 {
+            return Ok(self.scram_reject());
+        }
+        let auth_message = format!("{},{},{}", exchange.client_first_bare, exchange.server_first, without_proof);
+        let client_signature = hmac_sha256(&cred.stored_key, auth_message.as_bytes());
+        let client_key: Vec<u8> = proof.iter().zip(client_signature.iter()).map(|(a, b)| a ^ b).collect();
+        let stored_key: [u8; 32] = Sha256::digest(&client_key).into();
+        if !bool::from(stored_key.ct_eq(&cred.stored_key)) {
+            return Ok(self.scram_reject());
+        }
+        let server_signature = hmac_sha256(&cred.server_key, auth_message.as_bytes());
+        Ok(AuthnOutcome {
+            result: AuthenticateResult {
+                decision: Some(true),
+                challenge: format!("v={}", BASE64.encode(server_signature)).into_bytes(),
+                ..Default::default()
+            },
+            session: Some(Session {
+                user_id: Some(exchange.username),
+                groups: vec![],
+                auth_mech: exchange.mechanism,
+                start_time: Utc::now(),
+                peer_ip: peer.ip(),
+                peer_port: peer.port(),
+                clearances: vec![],
+            }),
+        })
+    }
+
+    fn attempt_scram (
+        &mut self,
+        peer: std::net::SocketAddr,
+        arg: &AuthenticateArg,
+    ) -> anyhow::Result<AuthnOutcome> {
+        if arg.mechanism == SCRAM_SHA_256_PLUS && self.tls_server_end_point.is_none() {
+            return Ok(self.scram_reject());
+        }
+        // Prevents DoS via gigantic auth strings.
+        if arg.assertion.len() > 1000 {
+            return Ok(self.scram_reject());
+        }
+        let now = Utc::now();
+        self.exchanges.retain(|_, e| (now - e.start_time).num_seconds() < SASL_EXCHANGE_TIMEOUT_SECS);
+        if arg.continuation.len() == 0 {
+            if self.exchanges.len() >= MAX_SASL_EXCHANGES {
+                return Ok(self.scram_reject());
+            }
+            return self.scram_server_first(arg);
+        }
+        let continuation: [u8; 16] = match arg.continuation[..].try_into() {
+            Ok(c) => c,
+            Err(_) => return Ok(self.scram_reject()),
+        };
+        // The exchange is removed whether or not it succeeds, so that the
+        // client cannot make multiple guesses with the same nonce.
+        match self.exchanges.remove(&Ulid::from_bytes(continuation)) {
+            Some(exchange) if exchange.mechanism == arg.mechanism => {
+                self.scram_server_final(peer, exchange, arg)
+            },
+            _ => Ok(self.scram_reject()),
+        }
+    }
+
+    /// Returns `None` if the assertion is rejected, or `Some(user_id)` if it
+    /// is accepted, where `user_id` is `None` for anonymous users.
+    fn check_assertion (&self, arg: &AuthenticateArg) -> anyhow::Result<Option<Option<UserId>>> {
+        if arg.mechanism == "ANONYMOUS" {
+            return Ok(Some(None));
+        }
+        if arg.mechanism != "PLAIN" || !self.allow_plain {
+            return Ok(None);
+        }
+        // Prevents DoS via gigantic auth strings.
+        if arg.assertion.len() > 1000 {
+            return Ok(None);
+        }
+        let mut splitter = arg.assertion.split(|c| *c == b'\0');
+        let maybe_authzid = splitter.next();
+        let maybe_authcid = splitter.next();
+        let maybe_passwd = splitter.next();
+        let maybe_whatever = splitter.next();
+
+        // There MUST be two nulls EXACTLY.
+        if
+            maybe_authzid.is_none()
+            || maybe_authcid.is_none()
+            || maybe_passwd.is_none()
+            || maybe_whatever.is_some()
+        {
+            return Ok(None);
+        }
+        let authzid = maybe_authzid.unwrap();
+        let authcid = maybe_authcid.unwrap();
+        let passwd = maybe_passwd.unwrap();
+
+        // The simple authenticator has no concept of authorization identity.
+        // So any assertion of authzid that differs from authcid is rejected.
+        if authzid.len() > 0 && authzid != authcid {
+            return Ok(None);
+        }
+
+        let authcid = std::str::from_utf8(authcid)?;
+        let passwd = std::str::from_utf8(passwd)?;
+        let success = self.db.get(authcid).is_some_and(|cred| cred.verify_password(passwd));
+
+        if success {
+            Ok(Some(Some(authcid.to_string())))
+        } else {
+            Ok(None)
+        }
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authenticator for SimpleAuth {
+
+    async fn get_auth_mechs (&self) -> Vec<Mechanism> {
+        let mut mechs = vec![
+            Mechanism {
+                name: "ANONYMOUS".to_string(),
+                anonymous: true,
+                ..Default::default()
+            },
+        ];
+        if self.allow_plain {
+            mechs.push(Mechanism {
+                name: "PLAIN".to_string(),
+                transfers_plaintext_passwords: true,
+                subject_to_dictionary_attack: true,
+                subject_to_non_dictionary_attack: true,
+                ..Default::default()
+            });
+        }
+        mechs.push(Mechanism {
+            name: SCRAM_SHA_256.to_string(),
+            subject_to_dictionary_attack: true,
+            provides_mutual_authentication: true,
+            ..Default::default()
+        });
+        if self.tls_server_end_point.is_some() {
+            mechs.push(Mechanism {
+                name: SCRAM_SHA_256_PLUS.to_string(),
+                subject_to_dictionary_attack: true,
+                provides_mutual_authentication: true,
+                ..Default::default()
+            });
+        }
+        if self.external.is_some() {
+            mechs.push(Mechanism {
+                name: "EXTERNAL".to_string(),
+                provides_mutual_authentication: true,
+                ..Default::default()
+            });
+        }
+        mechs
+    }
+
+    async fn attempt_authn (
+        &mut self,
+        request: &Request<AuthenticateArg>,
+    ) -> anyhow::Result<AuthnOutcome> {
+        let peer = request.remote_addr()
+            .ok_or_else(|| anyhow::anyhow!("could not determine peer address"))?;
+        let arg = request.get_ref();
+        if arg.mechanism == SCRAM_SHA_256 || arg.mechanism == SCRAM_SHA_256_PLUS {
+            return self.attempt_scram(peer, arg);
+        }
+        let maybe_user_id = if arg.mechanism == "EXTERNAL" {
+            self.attempt_external(request)?.map(|(user_id, clearances)| (Some(user_id), clearances))
+        } else {
+            self.check_assertion(arg)?.map(|user_id| (user_id, vec![]))
+        };
+        let (user_id, clearances) = match maybe_user_id {
+            Some(user_id) => user_id,
+            None => return Ok(AuthnOutcome::reject()),
+        };
+        Ok(AuthnOutcome::accept(Session {
+            user_id,
+            groups: vec![],
+            auth_mech: arg.mechanism.clone(),
+            start_time: Utc::now(),
+            peer_ip: peer.ip(),
+            peer_port: peer.port(),
+            clearances,
+        }))
+    }
+
+}
+
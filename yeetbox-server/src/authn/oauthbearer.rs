@@ -0,0 +1,217 @@
+use std::path::Path;
+use chrono::prelude::*;
+use jsonwebtoken::jwk::{Jwk, JwkSet, PublicKeyUse};
+use jsonwebtoken::{decode, decode_header, DecodingKey, Header, Validation};
+use serde_json::{Map, Value};
+use tonic::Request;
+use crate::authn::{decode_saslname, Authenticator, AuthnOutcome, Session, UserId};
+use crate::config::OAuthBearerConfig;
+use crate::grpc::remotefs::{AuthenticateArg, AuthenticateResult, Mechanism};
+
+pub const OAUTHBEARER: &str = "OAUTHBEARER";
+
+/// Prevents DoS via gigantic tokens.
+const MAX_ASSERTION_LENGTH: usize = 16 * 1024;
+
+/// The JSON Web Key Set is reloaded for tokens signed by an unknown key at
+/// most once per this many seconds, so that such tokens cannot make the server
+/// read and parse it on every request.
+const JWKS_RELOAD_INTERVAL_SECS: i64 = 60;
+
+/// The initial client response of OAUTHBEARER (IETF RFC 7628, Section 3.1).
+struct ClientResponse {
+    authzid: Option<String>,
+    token: String,
+}
+
+impl ClientResponse {
+
+    fn parse (assertion: &[u8]) -> Option<Self> {
+        let assertion = std::str::from_utf8(assertion).ok()?;
+        let (gs2_header, kvpairs) = assertion.split_once('\x01')?;
+        let mut gs2 = gs2_header.splitn(3, ',');
+        let cbind_flag = gs2.next()?;
+        let authzid = gs2.next()?;
+        if gs2.next() != Some("") {
+            return None;
+        }
+        // OAUTHBEARER does not define any channel binding.
+        if cbind_flag != "n" && cbind_flag != "y" {
+            return None;
+        }
+        let authzid = match authzid {
+            "" => None,
+            a => Some(decode_saslname(a.strip_prefix("a=")?)?),
+        };
+        let mut token = None;
+        for kvpair in kvpairs.strip_suffix('\x01')?.split_terminator('\x01') {
+            let (key, value) = kvpair.split_once('=')?;
+            if key != "auth" {
+                continue;
+            }
+            let (scheme, credentials) = value.split_once(' ')?;
+            if !scheme.eq_ignore_ascii_case("Bearer") {
+                return None;
+            }
+            token = Some(credentials.to_string());
+        }
+        Some(ClientResponse { authzid, token: token? })
+    }
+
+}
+
+/// Rejects the attempt with an error in the format of IETF RFC 7628, Section
+/// 3.2.2. Unlike the RFC, the client need not send a dummy response to
+/// receive the final outcome, since it is sent along with the error.
+fn oauthbearer_reject (status: &str) -> AuthnOutcome {
+    let error = serde_json::json!({ "status": status });
+    AuthnOutcome {
+        result: AuthenticateResult {
+            challenge: error.to_string().into_bytes(),
+            ..AuthenticateResult::reject()
+        },
+        session: None,
+    }
+}
+
+fn load_jwks (path: &Path) -> anyhow::Result<JwkSet> {
+    let jwks = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&jwks)?)
+}
+
+/// An authenticator that accepts JWT access tokens via SASL OAUTHBEARER,
+/// verifying their signatures using a local JSON Web Key Set.
+pub struct OAuthBearerAuth {
+    pub config: OAuthBearerConfig,
+    jwks: JwkSet,
+    jwks_loaded: DateTime<Utc>,
+}
+
+impl OAuthBearerAuth {
+
+    pub fn new (config: OAuthBearerConfig) -> anyhow::Result<Self> {
+        let jwks = load_jwks(&config.jwks_path)?;
+        Ok(OAuthBearerAuth { config, jwks, jwks_loaded: Utc::now() })
+    }
+
+    fn select_jwk (&self, header: &Header) -> Option<&Jwk> {
+        let jwk = match header.kid.as_ref() {
+            Some(kid) => self.jwks.find(kid),
+            // Without a key ID, the key is only unambiguous if there is one.
+            None if self.jwks.keys.len() == 1 => self.jwks.keys.first(),
+            None => None,
+        }?;
+        if jwk.common.public_key_use.as_ref().is_some_and(|u| *u != PublicKeyUse::Signature) {
+            return None;
+        }
+        // If the key is restricted to an algorithm, the token must use it.
+        if jwk.common.key_algorithm.is_some_and(|alg| alg.to_string() != format!("{:?}", header.alg)) {
+            return None;
+        }
+        Some(jwk)
+    }
+
+    fn get_decoding_key (&mut self, header: &Header) -> Option<DecodingKey> {
+        let now = Utc::now();
+        if self.select_jwk(header).is_none() && (now - self.jwks_loaded).num_seconds() >= JWKS_RELOAD_INTERVAL_SECS {
+            // The issuer may have rotated its keys since the set was loaded.
+            self.jwks_loaded = now;
+            match load_jwks(&self.config.jwks_path) {
+                Ok(jwks) => self.jwks = jwks,
+                Err(e) => log::error!("Could not reload JWKS: {}", e),
+            };
+        }
+        DecodingKey::from_jwk(self.select_jwk(header)?).ok()
+    }
+
+    /// Returns the claims of the token, or `None` if it is not valid.
+    fn validate_token (&mut self, token: &str) -> Option<Map<String, Value>> {
+        let header = decode_header(token).ok()?;
+        let key = self.get_decoding_key(&header)?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        validation.validate_nbf = true;
+        validation.leeway = self.config.leeway;
+        match decode::<Map<String, Value>>(token, &key, &validation) {
+            Ok(data) => Some(data.claims),
+            Err(e) => {
+                log::debug!("Rejected bearer token: {}", e);
+                None
+            },
+        }
+    }
+
+    fn get_user_id (&self, claims: &Map<String, Value>) -> Option<UserId> {
+        match claims.get(&self.config.user_id_claim) {
+            Some(Value::String(user_id)) if !user_id.is_empty() => Some(user_id.clone()),
+            _ => None,
+        }
+    }
+
+    fn get_groups (&self, claims: &Map<String, Value>) -> Vec<String> {
+        match claims.get(&self.config.groups_claim) {
+            Some(Value::Array(groups)) => groups.iter()
+                .filter_map(|g| g.as_str())
+                .map(|g| g.to_string())
+                .collect(),
+            Some(Value::String(groups)) => groups.split_whitespace()
+                .map(|g| g.to_string())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authenticator for OAuthBearerAuth {
+
+    async fn get_auth_mechs (&self) -> Vec<Mechanism> {
+        vec![
+            Mechanism {
+                name: OAUTHBEARER.to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    async fn attempt_authn (
+        &mut self,
+        request: &Request<AuthenticateArg>,
+    ) -> anyhow::Result<AuthnOutcome> {
+        let peer = request.remote_addr()
+            .ok_or_else(|| anyhow::anyhow!("could not determine peer address"))?;
+        let arg = request.get_ref();
+        if arg.mechanism != OAUTHBEARER || arg.assertion.len() > MAX_ASSERTION_LENGTH {
+            return Ok(oauthbearer_reject("invalid_request"));
+        }
+        let client_response = match ClientResponse::parse(&arg.assertion) {
+            Some(client_response) => client_response,
+            None => return Ok(oauthbearer_reject("invalid_request")),
+        };
+        let claims = match self.validate_token(&client_response.token) {
+            Some(claims) => claims,
+            None => return Ok(oauthbearer_reject("invalid_token")),
+        };
+        let user_id = match self.get_user_id(&claims) {
+            Some(user_id) => user_id,
+            None => return Ok(oauthbearer_reject("invalid_token")),
+        };
+        // There is no concept of acting on behalf of another user.
+        if client_response.authzid.is_some_and(|authzid| authzid != user_id) {
+            return Ok(oauthbearer_reject("invalid_request"));
+        }
+        Ok(AuthnOutcome::accept(Session {
+            user_id: Some(user_id),
+            groups: self.get_groups(&claims),
+            auth_mech: arg.mechanism.clone(),
+            start_time: Utc::now(),
+            peer_ip: peer.ip(),
+            peer_port: peer.port(),
+            clearances: vec![],
+        }))
+    }
+
+}
@@ -0,0 +1,402 @@
+use std::sync::Arc;
+use prost::Message;
+use redb::Database;
+use crate::authz::Authorizer;
+use crate::config::ClearanceAuthzConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, GetAttributesArg, GetAuditTrailArg, GetPresignedDownloadArg,
+    GetPresignedUploadArg, GetServiceInfoArg, ListArg, ListIncompleteUploadsArg,
+    MakeDirectoryArg, MoveArg, PatchArg, SetAttributesArg, StartTransactionArg, UnlinkArg,
+    UploadArg, WatchManyArg, WatchOnceArg, SecurityLabel, SubtreeSpec, SearchArg, QueryArg,
+};
+use crate::authn::Session;
+use crate::storage::database::{get_object_attrs, get_path_permissions};
+use crate::x509::Clearance;
+
+/// The name of the attribute in which the security label of an object is
+/// stored, as an `Any` containing a `SecurityLabel`.
+pub const SECURITY_LABEL_ATTR: &str = "securityLabel";
+pub const SECURITY_LABEL_TYPE_URL: &str = "type.googleapis.com/remotefs.SecurityLabel";
+
+fn decode_label (any: &prost_types::Any) -> Option<SecurityLabel> {
+    if any.type_url != SECURITY_LABEL_TYPE_URL {
+        return None;
+    }
+    SecurityLabel::decode(any.value.as_slice()).ok()
+}
+
+fn get_clearance <'a> (session: &'a Session, label: &SecurityLabel) -> Option<&'a Clearance> {
+    session.clearances.iter().find(|c| c.policy_id == label.policy_id)
+}
+
+/// No read up: the clearance must include the classification of the label,
+/// and every one of its categories.
+fn may_read (session: &Session, label: &SecurityLabel) -> bool {
+    get_clearance(session, label).is_some_and(|c| {
+        c.class_list.contains(&label.classification)
+        && label.categories.iter().all(|cat| c.categories.contains(cat))
+    })
+}
+
+/// No write down: the label must be at least the highest classification of
+/// the clearance, and include every one of its categories.
+fn may_write (session: &Session, label: &SecurityLabel) -> bool {
+    get_clearance(session, label).is_some_and(|c| {
+        c.class_list.iter().max().is_some_and(|max| label.classification >= *max)
+        && c.categories.iter().all(|cat| label.categories.contains(cat))
+    })
+}
+
+fn subtree_path (subtree: Option<&SubtreeSpec>) -> Option<Vec<String>> {
+    subtree
+        .map(|s| s.base.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|pc| String::from_utf8(pc.clone()).ok())
+        .collect()
+}
+
+fn to_io_error (e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+/// Mandatory access control in the manner of Bell-LaPadula: users may only
+/// read objects whose labels are dominated by their clearances, and only
+/// write objects whose labels dominate their clearances. Users with no
+/// clearance for the policy of a label may not access the object at all.
+pub struct ClearanceAuthz {
+    db: Arc<Database>,
+    pub config: ClearanceAuthzConfig,
+}
+
+impl ClearanceAuthz {
+
+    pub fn new (db: Arc<Database>, config: ClearanceAuthzConfig) -> Self {
+        ClearanceAuthz { db, config }
+    }
+
+    /// Returns the label of the object at `path`, which is that of the nearest
+    /// labeled folder containing it, if it has none of its own. If the object
+    /// does not exist, this is the label it would have if it were created.
+    fn get_effective_label (&self, path: &[String]) -> std::io::Result<SecurityLabel> {
+        let lineage = get_path_permissions(&self.db, path).map_err(to_io_error)?;
+        for obj in lineage.iter().rev() {
+            let attrs = get_object_attrs(&self.db, obj.id).map_err(to_io_error)?;
+            if let Some(any) = attrs.get(SECURITY_LABEL_ATTR) {
+                return decode_label(any)
+                    .ok_or_else(|| std::io::Error::other("malformed security label"));
+            }
+        }
+        Ok(self.config.default_label.clone())
+    }
+
+    fn may_read_path (&self, session: &Session, path: &[String]) -> std::io::Result<bool> {
+        Ok(may_read(session, &self.get_effective_label(path)?))
+    }
+
+    fn may_write_path (&self, session: &Session, path: &[String]) -> std::io::Result<bool> {
+        Ok(may_write(session, &self.get_effective_label(path)?))
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for ClearanceAuthz {
+
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.may_read_path(session, &path),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => self.may_read_path(session, &[]),
+        }
+    }
+
+    /// Moving an object reads it from its source and writes it to its
+    /// destination, and, since this alters the source, writes it too. Since
+    /// the label of the destination must dominate the clearance, which must
+    /// dominate the label of the source, objects cannot be downgraded.
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        Ok(
+            self.may_read_path(session, &source)?
+            && self.may_write_path(session, &source)?
+            && self.may_write_path(session, &arg.destination)?
+        )
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        Ok(
+            self.may_read_path(session, &source)?
+            && self.may_write_path(session, &arg.destination)?
+        )
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        self.may_read_path(session, &request.get_ref().subtree)
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    /// Changing the attributes of an object requires reading and writing it.
+    /// A new label must be valid, and must dominate the clearance, so that
+    /// users cannot downgrade objects.
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        if !self.may_read_path(session, &path)? || !self.may_write_path(session, &path)? {
+            return Ok(false);
+        }
+        let new_label = arg.attrs.as_ref().and_then(|a| a.other_metadata.get(SECURITY_LABEL_ATTR));
+        match new_label {
+            // Labels are only enforced on whole objects, so a label on a
+            // single version would be misleading.
+            Some(_) if arg.target.as_ref().is_some_and(|t| t.version.is_some()) => Ok(false),
+            Some(any) => Ok(decode_label(any).is_some_and(|label| may_write(session, &label))),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.may_write_path(session, &path),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.may_read_path(session, &path),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.source.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        let destination = arg.destination.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        Ok(
+            self.may_read_path(session, &source)?
+            && self.may_write_path(session, &destination)?
+        )
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_write_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.may_read_path(session, &path),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.may_read_path(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+}
@@ -1,4 +1,10 @@
+pub mod clearance;
+pub mod rbac;
+pub mod script;
 pub mod simple;
+pub mod unix;
+pub mod xacml;
+pub mod zanzibar;
 use crate::grpc::remotefs::{
     AbortTransactionArg, AbortTransactionResult, AppendArg, AppendResult,
     CommitTransactionArg, CommitTransactionResult, CopyArg, CopyResult, CreateLinkArg,
@@ -11,6 +17,9 @@ use crate::grpc::remotefs::{
     SetAttributesResult, StartTransactionArg, StartTransactionResult, UnlinkArg, UnlinkResult,
     UploadArg, UploadResult, WatchManyArg, WatchOnceArg, WatchOnceResult,
     GetAvailableSaslMechanismsResult, AuthenticateArg, AuthenticateResult,
+    ReadRelationshipsArg, ReadRelationshipsResult, WriteRelationshipsArg,
+    WriteRelationshipsResult, ReadRolesArg, ReadRolesResult, WriteRolesArg, WriteRolesResult,
+    SearchArg, QueryArg,
 };
 use crate::authn::Session;
 
@@ -211,4 +220,62 @@ pub trait Authorizer {
         unimplemented!()
     }
 
+    /// Authorizes searching the subtree. Each result is also filtered out
+    /// unless its attributes may be read.
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        unimplemented!()
+    }
+
+    /// Authorizes querying the contents of a file, which may reveal as much
+    /// as downloading it.
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        unimplemented!()
+    }
+
+    /// Administration of relationship tuples. Authorizers that are not based
+    /// on relationships do not support this. The authorizer decides who may
+    /// perform it.
+    async fn write_relationships(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WriteRelationshipsArg>,
+    ) -> std::result::Result<WriteRelationshipsResult, tonic::Status> {
+        Err(tonic::Status::unimplemented("this authorizer does not use relationships"))
+    }
+
+    async fn read_relationships(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ReadRelationshipsArg>,
+    ) -> std::result::Result<ReadRelationshipsResult, tonic::Status> {
+        Err(tonic::Status::unimplemented("this authorizer does not use relationships"))
+    }
+
+    /// Administration of roles, group memberships, and grants. Authorizers
+    /// that are not based on roles do not support this. The authorizer
+    /// decides who may perform it.
+    async fn write_roles(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WriteRolesArg>,
+    ) -> std::result::Result<WriteRolesResult, tonic::Status> {
+        Err(tonic::Status::unimplemented("this authorizer does not use roles"))
+    }
+
+    async fn read_roles(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ReadRolesArg>,
+    ) -> std::result::Result<ReadRolesResult, tonic::Status> {
+        Err(tonic::Status::unimplemented("this authorizer does not use roles"))
+    }
+
 }
@@ -0,0 +1,693 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use prost::Message;
+use redb::{Database, ReadableTable, TableDefinition};
+use unicode_normalization::UnicodeNormalization;
+use crate::authz::Authorizer;
+use crate::config::RbacConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, GetAttributesArg, GetAuditTrailArg, GetPresignedDownloadArg,
+    GetPresignedUploadArg, GetServiceInfoArg, ListArg, ListIncompleteUploadsArg,
+    MakeDirectoryArg, MoveArg, PatchArg, SetAttributesArg, StartTransactionArg, UnlinkArg,
+    UploadArg, WatchManyArg, WatchOnceArg, FileSystemError, RoleDefinition, GroupMembership,
+    RoleGrant, ReadRolesArg, ReadRolesResult, WriteRolesArg, WriteRolesResult, SearchArg, QueryArg,
+};
+use crate::authn::Session;
+use crate::storage::database::validate_path;
+
+// This is where roles are stored, keyed by name, as encoded `RoleDefinition`s.
+const ROLES_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("rbac_roles");
+
+// This is where group memberships are stored, keyed by user, then group.
+const MEMBERS_TABLE: TableDefinition<(&str, &str), ()> = TableDefinition::new("rbac_members");
+
+// This is where grants are stored, keyed by path, principal, and role, in that
+// order, so that the grants applying to each ancestor can be found quickly.
+const GRANTS_TABLE: TableDefinition<(&str, &str, &str), ()> = TableDefinition::new("rbac_grants");
+
+pub const READ_PERMISSION: &str = "read";
+pub const WRITE_PERMISSION: &str = "write";
+pub const DELETE_PERMISSION: &str = "delete";
+pub const SET_ATTRIBUTES_PERMISSION: &str = "set_attributes";
+pub const AUDIT_PERMISSION: &str = "audit";
+/// Permits administration of roles and grants, if granted on the root.
+pub const ADMIN_PERMISSION: &str = "admin";
+
+pub const PERMISSIONS: [&str; 6] = [
+    READ_PERMISSION,
+    WRITE_PERMISSION,
+    DELETE_PERMISSION,
+    SET_ATTRIBUTES_PERMISSION,
+    AUDIT_PERMISSION,
+    ADMIN_PERMISSION,
+];
+
+fn role (name: &str, permissions: &[&str], inherits: &[&str]) -> RoleDefinition {
+    RoleDefinition {
+        name: name.to_string(),
+        permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        inherits: inherits.iter().map(|r| r.to_string()).collect(),
+    }
+}
+
+/// The roles that are defined when there are none: admins are owners, owners
+/// are editors, and editors are viewers.
+pub fn default_roles () -> Vec<RoleDefinition> {
+    vec![
+        role("viewer", &[READ_PERMISSION], &[]),
+        role("editor", &[WRITE_PERMISSION, DELETE_PERMISSION], &["viewer"]),
+        role("owner", &[SET_ATTRIBUTES_PERMISSION, AUDIT_PERMISSION], &["editor"]),
+        role("admin", &[ADMIN_PERMISSION], &["owner"]),
+    ]
+}
+
+/// Returns the key under which grants on the subtree at `path` are stored.
+fn path_key <S: AsRef<str>> (path: &[S]) -> String {
+    let path: Vec<String> = path.iter().map(|pc| pc.as_ref().nfkd().collect()).collect();
+    format!("/{}", path.join("/"))
+}
+
+fn key_path (key: &str) -> Vec<String> {
+    key.split('/')
+        .filter(|pc| !pc.is_empty())
+        .map(|pc| pc.to_string())
+        .collect()
+}
+
+/// Whether the path with key `key` is within the subtree with key `base`.
+fn is_within (key: &str, base: &str) -> bool {
+    base == "/"
+        || key == base
+        || key.strip_prefix(base).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn is_valid_name (name: &str) -> bool {
+    !name.is_empty() && !name.contains([':', '/'])
+}
+
+fn is_valid_principal (principal: &str) -> bool {
+    if principal == "*" || principal == "user:*" {
+        return true;
+    }
+    match principal.split_once(':') {
+        Some(("user", id)) | Some(("group", id)) => !id.is_empty(),
+        _ => false,
+    }
+}
+
+fn validate_role (role: &RoleDefinition) -> std::result::Result<(), tonic::Status> {
+    if !is_valid_name(&role.name) || !role.inherits.iter().all(|r| is_valid_name(r)) {
+        return Err(tonic::Status::invalid_argument("malformed role name"));
+    }
+    if !role.permissions.iter().all(|p| PERMISSIONS.contains(&p.as_str())) {
+        return Err(tonic::Status::invalid_argument("unrecognized permission"));
+    }
+    Ok(())
+}
+
+fn validate_membership (membership: &GroupMembership) -> std::result::Result<(), tonic::Status> {
+    if !is_valid_name(&membership.group) || membership.user.is_empty() {
+        return Err(tonic::Status::invalid_argument("malformed group membership"));
+    }
+    Ok(())
+}
+
+/// Checks a grant written by an administrator, and returns its key.
+fn validate_grant (grant: &RoleGrant) -> std::result::Result<(String, String, String), tonic::Status> {
+    let path = validate_path(&grant.path)
+        .map_err(|_| tonic::Status::invalid_argument("malformed role grant"))?;
+    if !is_valid_name(&grant.role) || !is_valid_principal(&grant.principal) {
+        return Err(tonic::Status::invalid_argument("malformed role grant"));
+    }
+    Ok((path_key(&path), grant.principal.clone(), grant.role.clone()))
+}
+
+fn subtree_path (base: &[Vec<u8>]) -> Option<Vec<String>> {
+    base.iter()
+        .map(|pc| String::from_utf8(pc.clone()).ok())
+        .collect()
+}
+
+fn to_io_error (e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+/// An authorizer that permits operations according to the roles granted to
+/// users, their groups, or everyone on the subtrees containing their targets.
+/// Roles are only ever added by the grants on descendants, never removed.
+pub struct RbacAuthz {
+    pub db: Arc<Database>,
+    pub config: RbacConfig,
+}
+
+impl RbacAuthz {
+
+    pub fn new (db: Arc<Database>, config: RbacConfig) -> anyhow::Result<Self> {
+        let w = db.begin_write()?;
+        {
+            let mut roles = w.open_table(ROLES_TABLE)?;
+            if roles.is_empty()? {
+                for role in default_roles() {
+                    roles.insert(role.name.as_str(), role.encode_to_vec().as_slice())?;
+                }
+            }
+            w.open_table(MEMBERS_TABLE)?;
+            w.open_table(GRANTS_TABLE)?;
+        }
+        w.commit()?;
+        Ok(RbacAuthz { db, config })
+    }
+
+    /// Returns the principals that the session is: everyone, all users, the
+    /// user, and each group that they are in, whether asserted by the
+    /// authentication mechanism or written by an administrator.
+    fn principals_of <T> (&self, members: &T, session: &Session) -> std::io::Result<Vec<String>>
+        where T: ReadableTable<(&'static str, &'static str), ()> {
+        let mut principals = vec!["*".to_string()];
+        let user_id = match session.user_id.as_ref() {
+            Some(user_id) => user_id,
+            None => return Ok(principals),
+        };
+        principals.push("user:*".to_string());
+        principals.push(format!("user:{}", user_id));
+        principals.extend(session.groups.iter().map(|g| format!("group:{}", g)));
+        for entry in members.range((user_id.as_str(), "")..).map_err(to_io_error)? {
+            let (key, _) = entry.map_err(to_io_error)?;
+            let (user, group) = key.value();
+            if user != user_id {
+                break;
+            }
+            principals.push(format!("group:{}", group));
+        }
+        Ok(principals)
+    }
+
+    /// Whether `role` or any role that it inherits has `permission`. Roles
+    /// that do not exist have no permissions.
+    fn role_has_permission <T> (
+        &self,
+        roles: &T,
+        role: &str,
+        permission: &str,
+        visited: &mut HashSet<String>,
+    ) -> std::io::Result<bool>
+        where T: ReadableTable<&'static str, &'static [u8]> {
+        if !visited.insert(role.to_string()) {
+            return Ok(false);
+        }
+        let definition = match roles.get(role).map_err(to_io_error)? {
+            Some(value) => RoleDefinition::decode(value.value()).map_err(to_io_error)?,
+            None => return Ok(false),
+        };
+        if definition.permissions.iter().any(|p| p == permission) {
+            return Ok(true);
+        }
+        for inherited in definition.inherits.iter() {
+            if self.role_has_permission(roles, inherited, permission, visited)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether the session has been granted a role with `permission` on the
+    /// subtree at `path` or at any of its ancestors.
+    pub fn check_path (&self, session: &Session, path: &[String], permission: &str) -> std::io::Result<bool> {
+        let r = self.db.begin_read().map_err(to_io_error)?;
+        let roles = r.open_table(ROLES_TABLE).map_err(to_io_error)?;
+        let members = r.open_table(MEMBERS_TABLE).map_err(to_io_error)?;
+        let grants = r.open_table(GRANTS_TABLE).map_err(to_io_error)?;
+        let principals = self.principals_of(&members, session)?;
+        let mut visited = HashSet::new();
+        // Paths that could be mistaken for others once joined are never granted.
+        let path = match validate_path(path) {
+            Ok(path) => path,
+            Err(_) => return Ok(false),
+        };
+        for i in 0..=path.len() {
+            let key = path_key(&path[..i]);
+            for principal in principals.iter() {
+                let range = grants.range((key.as_str(), principal.as_str(), "")..)
+                    .map_err(to_io_error)?;
+                for entry in range {
+                    let (grant, _) = entry.map_err(to_io_error)?;
+                    let (p, pr, role) = grant.value();
+                    if p != key || pr != principal {
+                        break;
+                    }
+                    if self.role_has_permission(&roles, role, permission, &mut visited)? {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn is_admin (&self, session: &Session) -> std::io::Result<bool> {
+        if session.user_id.as_ref().is_some_and(|u| self.config.admins.contains(u)) {
+            return Ok(true);
+        }
+        self.check_path(session, &[], ADMIN_PERMISSION)
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for RbacAuthz {
+
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, READ_PERMISSION),
+            Some(None) => Ok(false),
+            None => self.check_path(session, &[], READ_PERMISSION),
+        }
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, WRITE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, WRITE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, WRITE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, WRITE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, DELETE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => self.check_path(session, &[], READ_PERMISSION),
+        }
+    }
+
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match req.target.as_ref() {
+            Some(target) => Ok(self.check_path(session, &target.path, DELETE_PERMISSION)?
+                && self.check_path(session, &req.destination, WRITE_PERMISSION)?),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match req.target.as_ref() {
+            Some(target) => Ok(self.check_path(session, &target.path, READ_PERMISSION)?
+                && self.check_path(session, &req.destination, WRITE_PERMISSION)?),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        self.check_path(session, &request.get_ref().subtree, WRITE_PERMISSION)
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, WRITE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, SET_ATTRIBUTES_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    // Since grants are only ever inherited, those permitted to delete the base
+    // are permitted to delete everything beneath it.
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, DELETE_PERMISSION),
+            Some(None) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, AUDIT_PERMISSION),
+            Some(None) => Ok(false),
+            None => self.is_admin(session),
+        }
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match (req.source.as_ref(), req.destination.as_ref()) {
+            (Some(source), Some(destination)) => Ok(self.check_path(session, &source.path, READ_PERMISSION)?
+                && self.check_path(session, &destination.path, WRITE_PERMISSION)?),
+            _ => Ok(true),
+        }
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, DELETE_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, READ_PERMISSION),
+            Some(None) => Ok(false),
+            None => self.check_path(session, &[], READ_PERMISSION),
+        }
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, READ_PERMISSION),
+            None => Ok(true),
+        }
+    }
+
+    async fn write_roles(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WriteRolesArg>,
+    ) -> std::result::Result<WriteRolesResult, tonic::Status> {
+        if !self.is_admin(session)? {
+            return Ok(WriteRolesResult {
+                err: Some(FileSystemError::not_permitted()),
+            });
+        }
+        let req = request.get_ref();
+        for role in req.define_roles.iter() {
+            validate_role(role)?;
+        }
+        for membership in req.add_members.iter().chain(req.remove_members.iter()) {
+            validate_membership(membership)?;
+        }
+        let revocations = req.revocations.iter()
+            .map(validate_grant)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let grants = req.grants.iter()
+            .map(validate_grant)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        {
+            let mut roles = w.open_table(ROLES_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to roles table"))?;
+            for name in req.delete_roles.iter() {
+                roles.remove(name.as_str())
+                    .map_err(|_| tonic::Status::internal("could not delete role"))?;
+            }
+            for role in req.define_roles.iter() {
+                roles.insert(role.name.as_str(), role.encode_to_vec().as_slice())
+                    .map_err(|_| tonic::Status::internal("could not write role"))?;
+            }
+            for (_, _, role) in grants.iter() {
+                let exists = roles.get(role.as_str())
+                    .map_err(|_| tonic::Status::internal("could not read from roles table"))?
+                    .is_some();
+                if !exists {
+                    return Err(tonic::Status::invalid_argument("no such role"));
+                }
+            }
+            let mut members = w.open_table(MEMBERS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to members table"))?;
+            for membership in req.remove_members.iter() {
+                members.remove((membership.user.as_str(), membership.group.as_str()))
+                    .map_err(|_| tonic::Status::internal("could not delete group membership"))?;
+            }
+            for membership in req.add_members.iter() {
+                members.insert((membership.user.as_str(), membership.group.as_str()), ())
+                    .map_err(|_| tonic::Status::internal("could not write group membership"))?;
+            }
+            let mut grants_table = w.open_table(GRANTS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to grants table"))?;
+            for (path, principal, role) in revocations.iter() {
+                grants_table.remove((path.as_str(), principal.as_str(), role.as_str()))
+                    .map_err(|_| tonic::Status::internal("could not delete grant"))?;
+            }
+            for (path, principal, role) in grants.iter() {
+                grants_table.insert((path.as_str(), principal.as_str(), role.as_str()), ())
+                    .map_err(|_| tonic::Status::internal("could not write grant"))?;
+            }
+        }
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        log::info!(
+            "{:?} defined {} roles, deleted {}, added {} group members, removed {}, granted {} roles, and revoked {}",
+            session.user_id,
+            req.define_roles.len(),
+            req.delete_roles.len(),
+            req.add_members.len(),
+            req.remove_members.len(),
+            grants.len(),
+            revocations.len(),
+        );
+        Ok(WriteRolesResult::default())
+    }
+
+    async fn read_roles(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ReadRolesArg>,
+    ) -> std::result::Result<ReadRolesResult, tonic::Status> {
+        if !self.is_admin(session)? {
+            return Ok(ReadRolesResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            });
+        }
+        let req = request.get_ref();
+        let base = path_key(&req.subtree);
+        let r = self.db.begin_read()
+            .map_err(|_| tonic::Status::internal("could not read from database"))?;
+        let roles_table = r.open_table(ROLES_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from roles table"))?;
+        let members_table = r.open_table(MEMBERS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from members table"))?;
+        let grants_table = r.open_table(GRANTS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from grants table"))?;
+        let mut ret = ReadRolesResult::default();
+        for entry in roles_table.iter().map_err(|_| tonic::Status::internal("could not read from roles table"))? {
+            let (_, value) = entry
+                .map_err(|_| tonic::Status::internal("could not read from roles table"))?;
+            let role = RoleDefinition::decode(value.value())
+                .map_err(|_| tonic::Status::internal("corrupted role"))?;
+            ret.roles.push(role);
+        }
+        for entry in members_table.iter().map_err(|_| tonic::Status::internal("could not read from members table"))? {
+            let (key, _) = entry
+                .map_err(|_| tonic::Status::internal("could not read from members table"))?;
+            let (user, group) = key.value();
+            let matches = match req.principal.split_once(':') {
+                Some(("user", id)) => id == user,
+                Some(("group", id)) => id == group,
+                _ => req.principal.is_empty(),
+            };
+            if matches {
+                ret.members.push(GroupMembership {
+                    group: group.to_string(),
+                    user: user.to_string(),
+                });
+            }
+        }
+        let iter = grants_table.range((base.as_str(), "", "")..)
+            .map_err(|_| tonic::Status::internal("could not read from grants table"))?;
+        for entry in iter {
+            let (key, _) = entry
+                .map_err(|_| tonic::Status::internal("could not read from grants table"))?;
+            let (path, principal, role) = key.value();
+            if !path.starts_with(base.as_str()) {
+                break;
+            }
+            if !is_within(path, &base) || (!req.principal.is_empty() && principal != req.principal) {
+                continue;
+            }
+            ret.grants.push(RoleGrant {
+                path: key_path(path),
+                role: role.to_string(),
+                principal: principal.to_string(),
+            });
+        }
+        Ok(ret)
+    }
+
+}
@@ -0,0 +1,589 @@
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use crate::authz::Authorizer;
+use crate::config::ScriptAuthzConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, FileVersion, GetAttributesArg, GetAuditTrailArg,
+    GetPresignedDownloadArg, GetPresignedUploadArg, GetServiceInfoArg, ListArg,
+    ListIncompleteUploadsArg, MakeDirectoryArg, MoveArg, PatchArg, RequestedFileVersion,
+    SetAttributesArg, StartTransactionArg, SubtreeSpec, UnixPermissions, UnlinkArg, UploadArg,
+    WatchManyArg, WatchOnceArg, SearchArg, QueryArg, query_arg, DocumentFormat,
+};
+use crate::authn::Session;
+use crate::storage::database::normalize_path;
+use crate::utils::unix_perms_to_u16;
+
+/// Called as `authorize(session, op, path, args)` for every operation. It must
+/// return `true` for the operation to be permitted.
+pub const AUTHORIZE_FN: &str = "authorize";
+
+/// If defined, called as `allow_file_name(name)` with the last component of
+/// the path of every object about to be created. It must return `true` for
+/// the object to be created.
+pub const ALLOW_FILE_NAME_FN: &str = "allow_file_name";
+
+struct LoadedScript {
+    /// The modification time of the script file when it was last loaded.
+    modified: Option<SystemTime>,
+    ast: Arc<AST>,
+}
+
+fn new_engine (config: &ScriptAuthzConfig) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(config.max_operations)
+        .set_max_call_levels(config.max_call_levels)
+        .set_max_expr_depths(config.max_expr_depth, config.max_expr_depth)
+        .set_max_string_size(config.max_string_size)
+        .set_max_array_size(config.max_array_size)
+        .set_max_map_size(config.max_map_size)
+        // The script may not load other scripts or code from strings.
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .on_print(|s| log::info!("Authorization script: {}", s))
+        .on_debug(|s, _, pos| log::debug!("Authorization script ({}): {}", pos, s));
+    engine
+}
+
+fn get_modified_time (config: &ScriptAuthzConfig) -> Option<SystemTime> {
+    std::fs::metadata(&config.script_path)
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+fn session_to_map (session: &Session) -> Map {
+    let mut map = Map::new();
+    map.insert("user_id".into(), match session.user_id.as_ref() {
+        Some(user_id) => user_id.clone().into(),
+        None => Dynamic::UNIT,
+    });
+    map.insert("groups".into(), session.groups.iter()
+        .map(|g| Dynamic::from(g.clone()))
+        .collect::<Array>()
+        .into());
+    map.insert("auth_mech".into(), session.auth_mech.clone().into());
+    map.insert("start_time".into(), session.start_time.timestamp().into());
+    map.insert("peer_ip".into(), session.peer_ip.to_string().into());
+    map.insert("peer_port".into(), (session.peer_port as i64).into());
+    map
+}
+
+fn path_to_array (path: &[String]) -> Array {
+    normalize_path(path).into_iter().map(Dynamic::from).collect()
+}
+
+/// Returns `None` if any path component of the subtree is not valid UTF-8.
+fn subtree_to_args (subtree: Option<&SubtreeSpec>) -> Option<(Vec<String>, Map)> {
+    let subtree = match subtree {
+        Some(subtree) => subtree,
+        None => return Some((vec![], Map::new())),
+    };
+    let base = subtree.base.iter()
+        .map(|pc| String::from_utf8(pc.clone()).ok())
+        .collect::<Option<Vec<String>>>()?;
+    Some((base, args([
+        ("minimum", (subtree.minimum as i64).into()),
+        ("maximum", (subtree.maximum as i64).into()),
+    ])))
+}
+
+fn args<const N: usize> (pairs: [(&str, Dynamic); N]) -> Map {
+    pairs.into_iter()
+        .map(|(k, v)| (k.into(), v))
+        .collect()
+}
+
+fn perms_to_dynamic (perms: Option<&UnixPermissions>) -> Dynamic {
+    match perms {
+        Some(perms) => (unix_perms_to_u16(perms) as i64).into(),
+        None => Dynamic::UNIT,
+    }
+}
+
+fn version_to_dynamic (version: Option<&FileVersion>) -> Dynamic {
+    match version {
+        Some(v) => args([
+            ("major", (v.major as i64).into()),
+            ("minor", (v.minor as i64).into()),
+        ]).into(),
+        None => Dynamic::UNIT,
+    }
+}
+
+fn requested_version_to_dynamic (version: Option<&RequestedFileVersion>) -> Dynamic {
+    match version {
+        Some(v) => args([
+            ("major", (v.major as i64).into()),
+            ("minor", v.minor.map(|m| (m as i64).into()).unwrap_or(Dynamic::UNIT)),
+        ]).into(),
+        None => Dynamic::UNIT,
+    }
+}
+
+/// An authorizer that delegates every decision to a Rhai script, which is
+/// reloaded whenever the file changes. Errors in the script, including
+/// exceeding the limits of the sandbox, result in denial.
+pub struct ScriptAuthz {
+    pub config: ScriptAuthzConfig,
+    engine: Engine,
+    script: RwLock<LoadedScript>,
+}
+
+impl ScriptAuthz {
+
+    pub fn new (config: ScriptAuthzConfig) -> anyhow::Result<Self> {
+        let engine = new_engine(&config);
+        let modified = get_modified_time(&config);
+        let ast = engine.compile_file(config.script_path.clone())
+            .map_err(|e| anyhow::anyhow!("could not compile authorization script: {}", e))?;
+        Ok(ScriptAuthz {
+            config,
+            engine,
+            script: RwLock::new(LoadedScript { modified, ast: Arc::new(ast) }),
+        })
+    }
+
+    /// Returns the current script, reloading it first if the file changed. If
+    /// the new script does not compile, the previous one remains in use.
+    fn get_script (&self) -> std::io::Result<Arc<AST>> {
+        let modified = get_modified_time(&self.config);
+        {
+            let script = self.script.read().map_err(|_| std::io::Error::other("script lock poisoned"))?;
+            if script.modified == modified {
+                return Ok(script.ast.clone());
+            }
+        }
+        let mut script = self.script.write().map_err(|_| std::io::Error::other("script lock poisoned"))?;
+        if script.modified != modified {
+            script.modified = modified;
+            match self.engine.compile_file(self.config.script_path.clone()) {
+                Ok(ast) => {
+                    log::info!("Reloaded authorization script {}", self.config.script_path.display());
+                    script.ast = Arc::new(ast);
+                },
+                Err(e) => log::error!("Could not reload authorization script: {}", e),
+            };
+        }
+        Ok(script.ast.clone())
+    }
+
+    fn authorize (
+        &self,
+        session: &Session,
+        op: &str,
+        path: &[String],
+        args: Map,
+    ) -> std::io::Result<bool> {
+        let ast = self.get_script()?;
+        let result = self.engine.call_fn::<bool>(
+            &mut Scope::new(),
+            &ast,
+            AUTHORIZE_FN,
+            (session_to_map(session), op.to_string(), path_to_array(path), args),
+        );
+        match result {
+            Ok(permitted) => Ok(permitted),
+            Err(e) => {
+                log::warn!("Authorization script failed for {}: {}", op, e);
+                Ok(false)
+            },
+        }
+    }
+
+    /// Like `authorize()`, but for operations that create an object at `path`,
+    /// whose name must also be allowed by the script, if it restricts names.
+    fn authorize_creation (
+        &self,
+        session: &Session,
+        op: &str,
+        path: &[String],
+        args: Map,
+    ) -> std::io::Result<bool> {
+        let ast = self.get_script()?;
+        let restricts_names = ast.iter_functions()
+            .any(|f| f.name == ALLOW_FILE_NAME_FN && f.params.len() == 1);
+        if let (true, Some(name)) = (restricts_names, path.last()) {
+            let result = self.engine.call_fn::<bool>(
+                &mut Scope::new(),
+                &ast,
+                ALLOW_FILE_NAME_FN,
+                (name.clone(),),
+            );
+            match result {
+                Ok(true) => {},
+                Ok(false) => return Ok(false),
+                Err(e) => {
+                    log::warn!("Authorization script failed for file name {}: {}", name, e);
+                    return Ok(false);
+                },
+            };
+        }
+        self.authorize(session, op, path, args)
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for ScriptAuthz {
+
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let (path, mut args) = match subtree_to_args(arg.subtree.as_ref()) {
+            Some(subtree) => subtree,
+            None => return Ok(false),
+        };
+        args.insert("minsize".into(), (arg.minsize as i64).into());
+        args.insert("maxsize".into(), (arg.maxsize as i64).into());
+        self.authorize(session, "watch_many", &path, args)
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "make_directory", &path, args([
+            ("uid", (arg.uid as i64).into()),
+            ("gid", (arg.gid as i64).into()),
+            ("perms", perms_to_dynamic(arg.perms.as_ref())),
+        ]))
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "upload", &path, args([
+            ("uid", (arg.uid as i64).into()),
+            ("gid", (arg.gid as i64).into()),
+            ("perms", perms_to_dynamic(arg.perms.as_ref())),
+            ("size", (arg.data.len() as i64).into()),
+            ("next", arg.next.into()),
+            ("force", arg.force.into()),
+            ("incomplete", arg.incomplete.into()),
+            ("reserve", (arg.reserve as i64).into()),
+            ("continued", (!arg.continuation.is_empty()).into()),
+            ("part", (arg.part as i64).into()),
+            ("parts", (arg.parts.len() as i64).into()),
+        ]))
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "append", &path, args([
+            ("version", requested_version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+            ("size", (arg.data.len() as i64).into()),
+        ]))
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "patch", &path, args([
+            ("version", version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+            ("size", (arg.data.len() as i64).into()),
+            ("offset", (arg.offset as i64).into()),
+            ("length", (arg.length as i64).into()),
+            ("finish", arg.finish.into()),
+        ]))
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "download", &path, args([
+            ("version", requested_version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+            ("offset", (arg.offset as i64).into()),
+            ("length", (arg.length as i64).into()),
+            ("format", DocumentFormat::from_i32(arg.format)
+                .map(|f| f.as_str_name().to_lowercase())
+                .unwrap_or_default()
+                .into()),
+        ]))
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "delete", &path, args([
+            ("version", requested_version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+            ("shred", arg.shred.into()),
+            ("upload", (!arg.continuation.is_empty()).into()),
+        ]))
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "list", &path, args([
+            ("attrs", arg.attrs.into()),
+            ("limit", (arg.limit as i64).into()),
+            ("skip", (arg.skip as i64).into()),
+            ("folders", arg.folders.into()),
+            ("thumbnails", arg.thumbnails.into()),
+        ]))
+    }
+
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "move", &arg.destination, args([
+            ("source", path_to_array(&source).into()),
+            ("next", arg.next.into()),
+        ]))
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "copy", &arg.destination, args([
+            ("source", path_to_array(&source).into()),
+            ("next", arg.next.into()),
+        ]))
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        self.authorize(session, "list_incomplete_uploads", &arg.subtree, args([
+            ("limit", (arg.limit as i64).into()),
+            ("skip", (arg.skip as i64).into()),
+        ]))
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "get_presigned_download", &path, args([
+            ("version", version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+        ]))
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "get_presigned_upload", &path, args([
+            ("size_limit", (arg.size_limit as i64).into()),
+        ]))
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "watch_once", &path, Map::new())
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "get_attributes", &path, args([
+            ("version", version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+        ]))
+    }
+
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        let attrs = arg.attrs.as_ref();
+        self.authorize(session, "set_attributes", &path, args([
+            ("uid", attrs.and_then(|a| a.uid).map(|uid| (uid as i64).into()).unwrap_or(Dynamic::UNIT)),
+            ("gid", attrs.and_then(|a| a.gid).map(|gid| (gid as i64).into()).unwrap_or(Dynamic::UNIT)),
+            ("perms", perms_to_dynamic(attrs.and_then(|a| a.perms.as_ref()))),
+        ]))
+    }
+
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let (path, mut args) = match subtree_to_args(arg.subtree.as_ref()) {
+            Some(subtree) => subtree,
+            None => return Ok(false),
+        };
+        args.insert("shred".into(), arg.shred.into());
+        self.authorize(session, "delete_many", &path, args)
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "get_service_info", &[], Map::new())
+    }
+
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let (path, mut args) = match subtree_to_args(arg.subtree.as_ref()) {
+            Some(subtree) => subtree,
+            None => return Ok(false),
+        };
+        args.insert("limit".into(), (arg.limit as i64).into());
+        args.insert("skip".into(), (arg.skip as i64).into());
+        args.insert("folders".into(), arg.folders.into());
+        self.authorize(session, "get_audit_trail", &path, args)
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "start_transaction", &[], Map::new())
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "commit_transaction", &[], args([
+            ("txid", request.get_ref().txid.clone().into()),
+        ]))
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "abort_transaction", &[], args([
+            ("txid", request.get_ref().txid.clone().into()),
+        ]))
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let source = arg.source.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        let path = arg.destination.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize_creation(session, "create_link", &path, args([
+            ("source", path_to_array(&source).into()),
+            ("hard", arg.hard.into()),
+        ]))
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "unlink", &path, Map::new())
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let (path, mut args) = match subtree_to_args(arg.subtree.as_ref()) {
+            Some(subtree) => subtree,
+            None => return Ok(false),
+        };
+        let labels: Array = arg.predicates.iter()
+            .map(|p| p.name.clone().into())
+            .collect();
+        args.insert("labels".into(), labels.into());
+        args.insert("attrs".into(), arg.attrs.into());
+        args.insert("text".into(), arg.text.clone().into());
+        args.insert("name".into(), arg.name.clone().into());
+        self.authorize(session, "search", &path, args)
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        let (kind, expr) = match arg.query.as_ref() {
+            Some(query_arg::Query::JsonPath(expr)) => ("json_path", expr.clone()),
+            Some(query_arg::Query::XPath(expr)) => ("x_path", expr.clone()),
+            Some(query_arg::Query::Csv(_)) => ("csv", String::new()),
+            None => ("", String::new()),
+        };
+        self.authorize(session, "query", &path, args([
+            ("version", requested_version_to_dynamic(arg.target.as_ref().and_then(|t| t.version.as_ref()))),
+            ("kind", kind.into()),
+            ("expression", expr.into()),
+        ]))
+    }
+
+}
@@ -10,7 +10,7 @@ use crate::grpc::remotefs::{
     MakeDirectoryResult, MoveArg, MoveResult, PatchArg, PatchResult, SetAttributesArg,
     SetAttributesResult, StartTransactionArg, StartTransactionResult, UnlinkArg, UnlinkResult,
     UploadArg, UploadResult, WatchManyArg, WatchOnceArg, WatchOnceResult,
-    GetAvailableSaslMechanismsResult, AuthenticateArg, AuthenticateResult,
+    GetAvailableSaslMechanismsResult, AuthenticateArg, AuthenticateResult, SearchArg, QueryArg,
 };
 use crate::authn::Session;
 
@@ -163,7 +163,7 @@ impl Authorizer for SimpleAuthz {
         session: &Session,
         request: &tonic::Request<DeleteManyArg>,
     ) -> std::io::Result<bool> {
-        unimplemented!()
+        Ok(session.auth_mech != "ANONYMOUS")
     }
 
     async fn is_authz_get_service_info(
@@ -222,4 +222,20 @@ impl Authorizer for SimpleAuthz {
         Ok(session.auth_mech != "ANONYMOUS")
     }
 
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
 }
@@ -0,0 +1,653 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use redb::Database;
+use crate::authz::Authorizer;
+use crate::config::UnixPermissionsConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, GetAttributesArg, GetAuditTrailArg, GetPresignedDownloadArg,
+    GetPresignedUploadArg, GetServiceInfoArg, ListArg, ListIncompleteUploadsArg,
+    MakeDirectoryArg, MoveArg, PatchArg, SetAttributesArg, StartTransactionArg, UnlinkArg,
+    UploadArg, WatchManyArg, WatchOnceArg, SearchArg, QueryArg,
+};
+use crate::authn::{Session, UserId};
+use crate::storage::database::{
+    get_path_permissions, ObjectPermissions, OBJ_TYPE_FOLDER, ROOT_FSID,
+    UNIX_PERM_G_X, UNIX_PERM_O_X, UNIX_PERM_SETGID, UNIX_PERM_STICKY, UNIX_PERM_U_X,
+};
+use crate::utils::unix_perms_to_u16;
+
+/// The superuser, who bypasses most permission checks.
+pub const SUPERUSER_UID: u32 = 0;
+
+// Permissions within a single class (user, group, or other).
+const PERM_R: u16 = 0o4;
+const PERM_W: u16 = 0o2;
+const PERM_X: u16 = 0o1;
+
+/// The permissions that the storage gives objects if none are requested.
+const DEFAULT_PERMS: u16 = 0o755;
+
+/// The uid and all gids of a user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnixIdentity {
+    pub uid: u32,
+    pub gids: HashSet<u32>,
+}
+
+struct UnixGroup {
+    gid: u32,
+    members: Vec<UserId>,
+}
+
+/// The permissions of the objects along a path.
+struct Lineage {
+    /// The root folder, followed by each folder that has to be traversed to
+    /// reach the target, for as far as the path exists.
+    folders: Vec<ObjectPermissions>,
+    parent: Option<ObjectPermissions>,
+    target: Option<ObjectPermissions>,
+}
+
+impl UnixIdentity {
+
+    pub fn is_superuser (&self) -> bool {
+        self.uid == SUPERUSER_UID
+    }
+
+    fn has_perms (&self, obj: &ObjectPermissions, perms: u16) -> bool {
+        if self.is_superuser() {
+            // Even the superuser cannot execute a file that nobody can.
+            return perms & PERM_X == 0
+                || obj.r#type == OBJ_TYPE_FOLDER
+                || obj.flags & (UNIX_PERM_U_X | UNIX_PERM_G_X | UNIX_PERM_O_X) > 0;
+        }
+        // Like Unix, only the first matching class is considered.
+        let class_perms = if self.uid == obj.uid {
+            obj.flags >> 6
+        } else if self.gids.contains(&obj.gid) {
+            obj.flags >> 3
+        } else {
+            obj.flags
+        };
+        class_perms & perms == perms
+    }
+
+    fn can_traverse (&self, lineage: &Lineage) -> bool {
+        lineage.folders.iter()
+            .take_while(|f| f.r#type == OBJ_TYPE_FOLDER)
+            .all(|f| self.has_perms(f, PERM_X))
+    }
+
+    /// Whether the user may create an object owned by `uid` and `gid` within
+    /// `parent`. Within a setgid folder, the group is inherited instead.
+    fn may_create (&self, parent: &ObjectPermissions, uid: u32, gid: u32) -> bool {
+        if !self.has_perms(parent, PERM_W | PERM_X) {
+            return false;
+        }
+        self.is_superuser()
+            || (uid == self.uid && (parent.flags & UNIX_PERM_SETGID > 0 || self.gids.contains(&gid)))
+    }
+
+    /// Whether the user may remove or rename `target` within `parent`. Within
+    /// a sticky folder, only the owners of the target or folder may do so.
+    fn may_unlink (&self, parent: &ObjectPermissions, target: Option<&ObjectPermissions>) -> bool {
+        if !self.has_perms(parent, PERM_W | PERM_X) {
+            return false;
+        }
+        parent.flags & UNIX_PERM_STICKY == 0
+            || self.is_superuser()
+            || parent.uid == self.uid
+            || target.is_none_or(|t| t.uid == self.uid)
+    }
+
+    /// Only the superuser may give objects away. Owners may change the group
+    /// to any group of which they are a member.
+    fn may_change_owner (&self, target: &ObjectPermissions, uid: u32, gid: u32) -> bool {
+        if uid == target.uid && gid == target.gid {
+            return true;
+        }
+        self.is_superuser()
+            || (self.uid == target.uid && uid == target.uid && self.gids.contains(&gid))
+    }
+
+    fn may_change_mode (&self, target: &ObjectPermissions, flags: u16) -> bool {
+        flags == target.flags || self.is_superuser() || self.uid == target.uid
+    }
+
+}
+
+fn parse_passwd (passwd: &str) -> anyhow::Result<HashMap<UserId, (u32, u32)>> {
+    let mut users = HashMap::new();
+    for line in passwd.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 4 {
+            anyhow::bail!("malformed passwd entry: {}", line);
+        }
+        users.insert(fields[0].to_string(), (fields[2].parse()?, fields[3].parse()?));
+    }
+    Ok(users)
+}
+
+fn parse_group (group: &str) -> anyhow::Result<HashMap<String, UnixGroup>> {
+    let mut groups = HashMap::new();
+    for line in group.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 3 {
+            anyhow::bail!("malformed group entry: {}", line);
+        }
+        let members = fields.get(3)
+            .map(|m| m.split(',').filter(|m| !m.is_empty()).map(|m| m.to_string()).collect())
+            .unwrap_or_default();
+        groups.insert(fields[0].to_string(), UnixGroup { gid: fields[2].parse()?, members });
+    }
+    Ok(groups)
+}
+
+fn read_to_string (path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))
+}
+
+/// An authorizer that enforces the uid, gid, and Unix permissions recorded
+/// by the `DatabaseStorage`, much like a Unix file system would.
+pub struct UnixPermissionsAuthz {
+    pub db: Arc<Database>,
+    pub config: UnixPermissionsConfig,
+    users: HashMap<UserId, (u32, u32)>,
+    groups: HashMap<String, UnixGroup>,
+}
+
+impl UnixPermissionsAuthz {
+
+    pub fn new (db: Arc<Database>, config: UnixPermissionsConfig) -> anyhow::Result<Self> {
+        let users = parse_passwd(&read_to_string(&config.passwd_path)?)?;
+        let groups = match config.group_path.as_ref() {
+            Some(group_path) => parse_group(&read_to_string(group_path)?)?,
+            None => HashMap::new(),
+        };
+        Ok(UnixPermissionsAuthz { db, config, users, groups })
+    }
+
+    /// Maps the session to a uid, and the gids of its primary group, every
+    /// group that lists the user as a member, and every group asserted by the
+    /// authenticator. Unknown and anonymous users are "nobody."
+    pub fn get_identity (&self, session: &Session) -> UnixIdentity {
+        let mut gids = HashSet::new();
+        let user = session.user_id.as_ref().and_then(|u| self.users.get(u));
+        let uid = match user {
+            Some(&(uid, gid)) => {
+                gids.insert(gid);
+                uid
+            },
+            None => {
+                gids.insert(self.config.nobody_gid);
+                self.config.nobody_uid
+            },
+        };
+        if let Some(user_id) = session.user_id.as_ref().filter(|_| user.is_some()) {
+            for group in self.groups.values() {
+                if group.members.contains(user_id) {
+                    gids.insert(group.gid);
+                }
+            }
+        }
+        for group_name in session.groups.iter() {
+            if let Some(group) = self.groups.get(group_name) {
+                gids.insert(group.gid);
+            }
+        }
+        UnixIdentity { uid, gids }
+    }
+
+    fn root_permissions (&self) -> ObjectPermissions {
+        ObjectPermissions {
+            id: ROOT_FSID,
+            r#type: OBJ_TYPE_FOLDER,
+            uid: self.config.root_uid,
+            gid: self.config.root_gid,
+            // The storage does not know the group of the root folder, so it
+            // cannot be inherited.
+            flags: self.config.root_mode & !UNIX_PERM_SETGID,
+        }
+    }
+
+    fn get_lineage (&self, path: &[String]) -> std::io::Result<Lineage> {
+        let mut objects = vec![self.root_permissions()];
+        objects.extend(get_path_permissions(&self.db, path)
+            .map_err(|e| std::io::Error::other(e.message().to_string()))?);
+        let n = path.len();
+        let parent = if n > 0 {
+            objects.get(n - 1).filter(|p| p.r#type == OBJ_TYPE_FOLDER).copied()
+        } else {
+            None
+        };
+        let target = if n == 0 || parent.is_some() {
+            objects.get(n).copied()
+        } else {
+            None
+        };
+        objects.truncate(n);
+        Ok(Lineage { folders: objects, parent, target })
+    }
+
+    /// Checks that the user may traverse to the target, and has `perms` on it
+    /// if it exists. Missing objects are left for the storage to report.
+    fn check_target (&self, session: &Session, path: &[String], perms: u16) -> std::io::Result<bool> {
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        Ok(id.can_traverse(&lineage)
+            && lineage.target.is_none_or(|t| id.has_perms(&t, perms)))
+    }
+
+    /// Checks that the user may remove the target from its parent folder.
+    fn check_unlink (&self, session: &Session, path: &[String]) -> std::io::Result<bool> {
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        Ok(id.can_traverse(&lineage)
+            && lineage.parent.is_none_or(|p| id.may_unlink(&p, lineage.target.as_ref())))
+    }
+
+    /// Checks that the user may create a new object owned by `uid` and `gid`,
+    /// or write to the existing one, changing its ownership and permissions to
+    /// those given.
+    fn check_create (
+        &self,
+        session: &Session,
+        path: &[String],
+        uid: u32,
+        gid: u32,
+        flags: u16,
+    ) -> std::io::Result<bool> {
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        if !id.can_traverse(&lineage) {
+            return Ok(false);
+        }
+        Ok(match (lineage.parent, lineage.target) {
+            (_, Some(t)) => id.has_perms(&t, PERM_W)
+                && id.may_change_owner(&t, uid, gid)
+                && id.may_change_mode(&t, flags),
+            (Some(p), None) => id.may_create(&p, uid, gid),
+            (None, None) => true,
+        })
+    }
+
+    /// Checks that the user may write to the target, or, if it does not exist
+    /// yet, as when filling in a reserved upload, create it within its parent.
+    fn check_write_or_create (&self, session: &Session, path: &[String]) -> std::io::Result<bool> {
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        if !id.can_traverse(&lineage) {
+            return Ok(false);
+        }
+        Ok(match (lineage.parent, lineage.target) {
+            (_, Some(t)) => id.has_perms(&t, PERM_W),
+            (Some(p), None) => id.has_perms(&p, PERM_W | PERM_X),
+            (None, None) => true,
+        })
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for UnixPermissionsAuthz {
+
+    // The targets are not known until after the stream has started.
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        Ok(self.get_identity(session).is_superuser())
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let path = match req.target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        Ok(id.can_traverse(&lineage)
+            && lineage.parent.is_none_or(|p| id.may_create(&p, req.uid, req.gid)))
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let path = match req.target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        let flags = req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(DEFAULT_PERMS);
+        self.check_create(session, path, req.uid, req.gid, flags)
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, PERM_W),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_write_or_create(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, PERM_R),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_unlink(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        // Reading the attributes of entries requires traversing the folder.
+        let perms = if req.attrs { PERM_R | PERM_X } else { PERM_R };
+        match req.target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, perms),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let path = match req.target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        let id = self.get_identity(session);
+        let src = self.get_lineage(path)?;
+        let dest = self.get_lineage(&req.destination)?;
+        if !id.can_traverse(&src) || !id.can_traverse(&dest) {
+            return Ok(false);
+        }
+        if src.parent.is_some_and(|p| !id.may_unlink(&p, src.target.as_ref())) {
+            return Ok(false);
+        }
+        if dest.parent.is_some_and(|p| !id.has_perms(&p, PERM_W | PERM_X)) {
+            return Ok(false);
+        }
+        // Moving a folder elsewhere changes its parent, which is like writing
+        // to its ".." entry.
+        let reparented = src.parent.map(|p| p.id) != dest.parent.map(|p| p.id);
+        Ok(match src.target {
+            Some(t) if t.r#type == OBJ_TYPE_FOLDER && reparented => id.has_perms(&t, PERM_W),
+            _ => true,
+        })
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let path = match req.target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        if !self.check_target(session, path, PERM_R)? {
+            return Ok(false);
+        }
+        let id = self.get_identity(session);
+        let dest = self.get_lineage(&req.destination)?;
+        Ok(id.can_traverse(&dest)
+            && dest.parent.is_none_or(|p| id.has_perms(&p, PERM_W | PERM_X)))
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        self.check_target(session, &request.get_ref().subtree, PERM_R | PERM_X)
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, PERM_R),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        let path = match request.get_ref().target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        Ok(id.can_traverse(&lineage) && match (lineage.parent, lineage.target) {
+            (_, Some(t)) => id.has_perms(&t, PERM_W),
+            (Some(p), None) => id.has_perms(&p, PERM_W | PERM_X),
+            (None, None) => true,
+        })
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, PERM_R),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, 0),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let path = match req.target.as_ref() {
+            Some(target) => &target.path,
+            None => return Ok(true),
+        };
+        let id = self.get_identity(session);
+        let lineage = self.get_lineage(path)?;
+        if !id.can_traverse(&lineage) {
+            return Ok(false);
+        }
+        let (target, attrs) = match (lineage.target, req.attrs.as_ref()) {
+            (Some(target), Some(attrs)) => (target, attrs),
+            _ => return Ok(true),
+        };
+        let uid = attrs.uid.unwrap_or(target.uid);
+        let gid = attrs.gid.unwrap_or(target.gid);
+        let flags = attrs.perms.as_ref().map(unix_perms_to_u16).unwrap_or(target.flags);
+        // Other attributes, such as timestamps, may be changed by writers.
+        Ok(id.may_change_owner(&target, uid, gid)
+            && id.may_change_mode(&target, flags)
+            && (id.is_superuser() || id.uid == target.uid || id.has_perms(&target, PERM_W)))
+    }
+
+    // Checking every object within the subtree would be too expensive.
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        Ok(self.get_identity(session).is_superuser())
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    // The audit trail reveals activity by other users.
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        Ok(self.get_identity(session).is_superuser())
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        let (source, destination) = match (req.source.as_ref(), req.destination.as_ref()) {
+            (Some(source), Some(destination)) => (source, destination),
+            _ => return Ok(true),
+        };
+        let id = self.get_identity(session);
+        let dest = self.get_lineage(&destination.path)?;
+        if !id.can_traverse(&dest) || dest.parent.is_some_and(|p| !id.has_perms(&p, PERM_W | PERM_X)) {
+            return Ok(false);
+        }
+        if !req.hard {
+            return Ok(true);
+        }
+        // Like Linux's protected_hardlinks, users may only hard-link objects
+        // that they own or can both read and write.
+        let src = self.get_lineage(&source.path)?;
+        Ok(id.can_traverse(&src) && src.target.is_none_or(|t| {
+            id.is_superuser() || id.uid == t.uid || id.has_perms(&t, PERM_R | PERM_W)
+        }))
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_unlink(session, &target.path),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        let base = request.get_ref().subtree.as_ref()
+            .map(|s| s.base.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|pc| String::from_utf8(pc.clone()).ok())
+            .collect::<Option<Vec<String>>>();
+        // Like listing with attributes, this requires traversing the base.
+        match base {
+            Some(path) => self.check_target(session, &path, PERM_R | PERM_X),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_target(session, &target.path, PERM_R),
+            None => Ok(true),
+        }
+    }
+
+}
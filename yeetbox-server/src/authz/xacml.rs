@@ -0,0 +1,1237 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use anyhow::{anyhow, bail};
+use prost::Message;
+use redb::Database;
+use roxmltree::Node;
+use crate::authz::Authorizer;
+use crate::config::XacmlConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, GetAttributesArg, GetAuditTrailArg, GetPresignedDownloadArg,
+    GetPresignedUploadArg, GetServiceInfoArg, ListArg, ListIncompleteUploadsArg,
+    MakeDirectoryArg, MoveArg, PatchArg, SetAttributesArg, StartTransactionArg, UnlinkArg,
+    UploadArg, WatchManyArg, WatchOnceArg, ObjectType, SubtreeSpec, SearchArg, QueryArg,
+};
+use crate::authn::Session;
+use crate::storage::database::{get_path_attributes, validate_path};
+use crate::utils::unix_perms_to_u16;
+
+pub const SUBJECT_CATEGORY: &str = "urn:oasis:names:tc:xacml:1.0:subject-category:access-subject";
+pub const RESOURCE_CATEGORY: &str = "urn:oasis:names:tc:xacml:3.0:attribute-category:resource";
+pub const ACTION_CATEGORY: &str = "urn:oasis:names:tc:xacml:3.0:attribute-category:action";
+
+pub const SUBJECT_ID: &str = "urn:oasis:names:tc:xacml:1.0:subject:subject-id";
+pub const SUBJECT_IP_ADDRESS: &str = "urn:oasis:names:tc:xacml:3.0:subject:authn-locality:ip-address";
+pub const SUBJECT_GROUP: &str = "urn:yeetbox:subject:group";
+pub const SUBJECT_AUTHN_MECHANISM: &str = "urn:yeetbox:subject:authn-mechanism";
+
+/// The name of the RPC, such as `Upload`.
+pub const ACTION_ID: &str = "urn:oasis:names:tc:xacml:1.0:action:action-id";
+
+/// The path of the target, such as `/projects/x`.
+pub const RESOURCE_ID: &str = "urn:oasis:names:tc:xacml:1.0:resource:resource-id";
+/// The paths of each folder containing the target, including `/`.
+pub const RESOURCE_ANCESTOR: &str = "urn:yeetbox:resource:ancestor";
+/// The path of the destination of `Move`, `Copy`, and `CreateLink`.
+pub const RESOURCE_DESTINATION: &str = "urn:yeetbox:resource:destination";
+pub const RESOURCE_TYPE: &str = "urn:yeetbox:resource:type";
+pub const RESOURCE_UID: &str = "urn:yeetbox:resource:uid";
+pub const RESOURCE_GID: &str = "urn:yeetbox:resource:gid";
+pub const RESOURCE_MODE: &str = "urn:yeetbox:resource:mode";
+pub const RESOURCE_SIZE: &str = "urn:yeetbox:resource:size";
+/// Followed by the name of an arbitrary attribute of the target.
+pub const RESOURCE_ATTR_PREFIX: &str = "urn:yeetbox:resource:attr:";
+
+/// Logs the decision, along with the `message` assignment, if any.
+pub const OBLIGATION_LOG: &str = "urn:yeetbox:obligation:log";
+/// Issues a unique stamp identifying the user, time, action, and resource.
+pub const OBLIGATION_MICROSTAMP: &str = "urn:yeetbox:obligation:microstamp";
+
+const XS_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+const XS_ANY_URI: &str = "http://www.w3.org/2001/XMLSchema#anyURI";
+const XS_INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+const XS_BOOLEAN: &str = "http://www.w3.org/2001/XMLSchema#boolean";
+
+const FUNCTION_PREFIXES: [&str; 2] = [
+    "urn:oasis:names:tc:xacml:1.0:function:",
+    "urn:oasis:names:tc:xacml:3.0:function:",
+];
+
+/// The reason for an Indeterminate result.
+type Eval<T> = std::result::Result<T, String>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    String,
+    Integer,
+    Boolean,
+}
+
+impl DataType {
+
+    fn from_uri (uri: &str) -> anyhow::Result<Self> {
+        match uri {
+            // URIs are only compared as strings.
+            XS_STRING | XS_ANY_URI => Ok(DataType::String),
+            XS_INTEGER => Ok(DataType::Integer),
+            XS_BOOLEAN => Ok(DataType::Boolean),
+            _ => bail!("unsupported data type {}", uri),
+        }
+    }
+
+    fn from_name (name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(DataType::String),
+            "integer" => Some(DataType::Integer),
+            "boolean" => Some(DataType::Boolean),
+            _ => None,
+        }
+    }
+
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Value {
+
+    fn parse (data_type: DataType, text: &str) -> anyhow::Result<Self> {
+        let text = text.trim();
+        match data_type {
+            DataType::String => Ok(Value::String(text.to_string())),
+            DataType::Integer => Ok(Value::Integer(text.parse()?)),
+            DataType::Boolean => match text {
+                "true" | "1" => Ok(Value::Boolean(true)),
+                "false" | "0" => Ok(Value::Boolean(false)),
+                _ => bail!("invalid boolean {}", text),
+            },
+        }
+    }
+
+    fn data_type (&self) -> DataType {
+        match self {
+            Value::String(_) => DataType::String,
+            Value::Integer(_) => DataType::Integer,
+            Value::Boolean(_) => DataType::Boolean,
+        }
+    }
+
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// The supported subset of the standard functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Equal(DataType),
+    StringEqualIgnoreCase,
+    IntegerGreaterThan,
+    IntegerGreaterThanOrEqual,
+    IntegerLessThan,
+    IntegerLessThanOrEqual,
+    StringStartsWith,
+    StringEndsWith,
+    StringContains,
+    And,
+    Or,
+    Not,
+    OneAndOnly(DataType),
+    BagSize(DataType),
+    IsIn(DataType),
+    AtLeastOneMemberOf(DataType),
+}
+
+impl Function {
+
+    fn from_id (id: &str) -> anyhow::Result<Self> {
+        let name = FUNCTION_PREFIXES.iter()
+            .find_map(|prefix| id.strip_prefix(prefix))
+            .ok_or_else(|| anyhow!("unsupported function {}", id))?;
+        let f = match name {
+            "string-equal-ignore-case" => Function::StringEqualIgnoreCase,
+            "integer-greater-than" => Function::IntegerGreaterThan,
+            "integer-greater-than-or-equal" => Function::IntegerGreaterThanOrEqual,
+            "integer-less-than" => Function::IntegerLessThan,
+            "integer-less-than-or-equal" => Function::IntegerLessThanOrEqual,
+            "string-starts-with" => Function::StringStartsWith,
+            "string-ends-with" => Function::StringEndsWith,
+            "string-contains" => Function::StringContains,
+            "and" => Function::And,
+            "or" => Function::Or,
+            "not" => Function::Not,
+            _ => {
+                let (dt, rest) = name.split_once('-')
+                    .and_then(|(dt, rest)| Some((DataType::from_name(dt)?, rest)))
+                    .ok_or_else(|| anyhow!("unsupported function {}", id))?;
+                match rest {
+                    "equal" => Function::Equal(dt),
+                    "one-and-only" => Function::OneAndOnly(dt),
+                    "bag-size" => Function::BagSize(dt),
+                    "is-in" => Function::IsIn(dt),
+                    "at-least-one-member-of" => Function::AtLeastOneMemberOf(dt),
+                    _ => bail!("unsupported function {}", id),
+                }
+            },
+        };
+        Ok(f)
+    }
+
+    /// Returns `None` if the function takes any number of arguments.
+    fn arity (self) -> Option<usize> {
+        match self {
+            Function::And | Function::Or => None,
+            Function::Not | Function::OneAndOnly(_) | Function::BagSize(_) => Some(1),
+            _ => Some(2),
+        }
+    }
+
+    /// Whether this function may be used in a `<Match>`, where it is called
+    /// with the literal value and each value of the attribute.
+    fn is_match_function (self) -> bool {
+        matches!(self,
+            Function::Equal(_)
+            | Function::StringEqualIgnoreCase
+            | Function::IntegerGreaterThan
+            | Function::IntegerGreaterThanOrEqual
+            | Function::IntegerLessThan
+            | Function::IntegerLessThanOrEqual
+            | Function::StringStartsWith
+            | Function::StringEndsWith
+            | Function::StringContains
+        )
+    }
+
+    fn call_binary (self, a: &Value, b: &Value) -> Eval<bool> {
+        match (self, a, b) {
+            (Function::Equal(dt), a, b) if a.data_type() == dt && b.data_type() == dt => Ok(a == b),
+            (Function::StringEqualIgnoreCase, Value::String(a), Value::String(b)) => Ok(a.to_lowercase() == b.to_lowercase()),
+            (Function::IntegerGreaterThan, Value::Integer(a), Value::Integer(b)) => Ok(a > b),
+            (Function::IntegerGreaterThanOrEqual, Value::Integer(a), Value::Integer(b)) => Ok(a >= b),
+            (Function::IntegerLessThan, Value::Integer(a), Value::Integer(b)) => Ok(a < b),
+            (Function::IntegerLessThanOrEqual, Value::Integer(a), Value::Integer(b)) => Ok(a <= b),
+            // Per XACML 3.0, the first argument is the prefix, suffix, or substring.
+            (Function::StringStartsWith, Value::String(a), Value::String(b)) => Ok(b.starts_with(a.as_str())),
+            (Function::StringEndsWith, Value::String(a), Value::String(b)) => Ok(b.ends_with(a.as_str())),
+            (Function::StringContains, Value::String(a), Value::String(b)) => Ok(b.contains(a.as_str())),
+            _ => Err(format!("invalid arguments to {:?}", self)),
+        }
+    }
+
+}
+
+#[derive(Clone, Debug)]
+struct Designator {
+    category: String,
+    attribute_id: String,
+    data_type: DataType,
+    must_be_present: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+    Value(Value),
+    Designator(Designator),
+    Apply(Function, Vec<Expression>),
+}
+
+#[derive(Clone, Debug)]
+enum Evaluated {
+    Value(Value),
+    Bag(Vec<Value>),
+}
+
+impl Evaluated {
+
+    fn into_value (self, data_type: DataType) -> Eval<Value> {
+        match self {
+            Evaluated::Value(v) if v.data_type() == data_type => Ok(v),
+            _ => Err(format!("expected a single {:?}", data_type)),
+        }
+    }
+
+    fn into_bag (self, data_type: DataType) -> Eval<Vec<Value>> {
+        match self {
+            Evaluated::Bag(bag) if bag.iter().all(|v| v.data_type() == data_type) => Ok(bag),
+            _ => Err(format!("expected a bag of {:?}", data_type)),
+        }
+    }
+
+    fn into_bool (self) -> Eval<bool> {
+        match self.into_value(DataType::Boolean)? {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(String::from("expected a boolean")),
+        }
+    }
+
+}
+
+#[derive(Clone, Debug)]
+struct Match {
+    function: Function,
+    value: Value,
+    designator: Designator,
+}
+
+/// A `<Target>` is a conjunction of `<AnyOf>`s, which are disjunctions of
+/// `<AllOf>`s, which are conjunctions of `<Match>`es. An empty target matches
+/// every request.
+#[derive(Clone, Debug, Default)]
+struct Target {
+    any_ofs: Vec<Vec<Vec<Match>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Effect {
+    Permit,
+    Deny,
+}
+
+impl Effect {
+
+    fn parse (effect: &str) -> anyhow::Result<Self> {
+        match effect {
+            "Permit" => Ok(Effect::Permit),
+            "Deny" => Ok(Effect::Deny),
+            _ => bail!("invalid effect {}", effect),
+        }
+    }
+
+}
+
+#[derive(Clone, Debug)]
+struct ObligationExpression {
+    id: String,
+    fulfill_on: Effect,
+    assignments: Vec<(String, Expression)>,
+}
+
+/// An obligation of a decision, which must be fulfilled for it to stand.
+#[derive(Clone, Debug)]
+pub struct Obligation {
+    pub id: String,
+    pub assignments: Vec<(String, Value)>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    id: String,
+    effect: Effect,
+    target: Target,
+    condition: Option<Expression>,
+    obligations: Vec<ObligationExpression>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CombiningAlgorithm {
+    DenyOverrides,
+    PermitOverrides,
+    FirstApplicable,
+    DenyUnlessPermit,
+    PermitUnlessDeny,
+}
+
+impl CombiningAlgorithm {
+
+    fn from_id (id: &str) -> anyhow::Result<Self> {
+        // Rule- and policy-combining algorithms of the same name are the same,
+        // and the ordered variants are how these are evaluated anyway.
+        match id.rsplit(':').next() {
+            Some("deny-overrides" | "ordered-deny-overrides") => Ok(CombiningAlgorithm::DenyOverrides),
+            Some("permit-overrides" | "ordered-permit-overrides") => Ok(CombiningAlgorithm::PermitOverrides),
+            Some("first-applicable") => Ok(CombiningAlgorithm::FirstApplicable),
+            Some("deny-unless-permit") => Ok(CombiningAlgorithm::DenyUnlessPermit),
+            Some("permit-unless-deny") => Ok(CombiningAlgorithm::PermitUnlessDeny),
+            _ => bail!("unsupported combining algorithm {}", id),
+        }
+    }
+
+}
+
+#[derive(Clone, Debug)]
+enum PolicyChildren {
+    Rules(Vec<Rule>),
+    Policies(Vec<Policy>),
+}
+
+/// A `<Policy>` of rules, or a `<PolicySet>` of policies.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    id: String,
+    target: Target,
+    algorithm: CombiningAlgorithm,
+    children: PolicyChildren,
+    obligations: Vec<ObligationExpression>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Permit,
+    Deny,
+    NotApplicable,
+    Indeterminate,
+}
+
+impl From<Effect> for Decision {
+    fn from(effect: Effect) -> Self {
+        match effect {
+            Effect::Permit => Decision::Permit,
+            Effect::Deny => Decision::Deny,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub decision: Decision,
+    pub obligations: Vec<Obligation>,
+}
+
+impl Outcome {
+
+    fn of (decision: Decision) -> Self {
+        Outcome { decision, obligations: vec![] }
+    }
+
+}
+
+/// The attributes of a request, keyed by category and attribute ID.
+#[derive(Clone, Debug, Default)]
+pub struct RequestContext {
+    attributes: HashMap<(String, String), Vec<Value>>,
+}
+
+impl RequestContext {
+
+    pub fn add (&mut self, category: &str, attribute_id: &str, value: Value) {
+        self.attributes
+            .entry((category.to_string(), attribute_id.to_string()))
+            .or_default()
+            .push(value);
+    }
+
+    fn designate (&self, d: &Designator) -> Eval<Vec<Value>> {
+        let bag: Vec<Value> = self.attributes
+            .get(&(d.category.clone(), d.attribute_id.clone()))
+            .map(|values| values.iter()
+                .filter(|v| v.data_type() == d.data_type)
+                .cloned()
+                .collect())
+            .unwrap_or_default();
+        if bag.is_empty() && d.must_be_present {
+            return Err(format!("missing attribute {}", d.attribute_id));
+        }
+        Ok(bag)
+    }
+
+}
+
+fn evaluate (expr: &Expression, ctx: &RequestContext) -> Eval<Evaluated> {
+    let (function, args) = match expr {
+        Expression::Value(v) => return Ok(Evaluated::Value(v.clone())),
+        Expression::Designator(d) => return Ok(Evaluated::Bag(ctx.designate(d)?)),
+        Expression::Apply(function, args) => (*function, args),
+    };
+    match function {
+        Function::And => {
+            for arg in args {
+                if !evaluate(arg, ctx)?.into_bool()? {
+                    return Ok(Evaluated::Value(Value::Boolean(false)));
+                }
+            }
+            return Ok(Evaluated::Value(Value::Boolean(true)));
+        },
+        Function::Or => {
+            for arg in args {
+                if evaluate(arg, ctx)?.into_bool()? {
+                    return Ok(Evaluated::Value(Value::Boolean(true)));
+                }
+            }
+            return Ok(Evaluated::Value(Value::Boolean(false)));
+        },
+        _ => {},
+    };
+    let mut args = args.iter()
+        .map(|arg| evaluate(arg, ctx))
+        .collect::<Eval<Vec<Evaluated>>>()?
+        .into_iter();
+    let mut next_arg = || args.next().ok_or_else(|| String::from("too few arguments"));
+    let result = match function {
+        Function::Not => Value::Boolean(!next_arg()?.into_bool()?),
+        Function::OneAndOnly(dt) => {
+            let mut bag = next_arg()?.into_bag(dt)?;
+            if bag.len() != 1 {
+                return Err(format!("expected one value, but got {}", bag.len()));
+            }
+            bag.remove(0)
+        },
+        Function::BagSize(dt) => Value::Integer(next_arg()?.into_bag(dt)?.len() as i64),
+        Function::IsIn(dt) => {
+            let value = next_arg()?.into_value(dt)?;
+            Value::Boolean(next_arg()?.into_bag(dt)?.contains(&value))
+        },
+        Function::AtLeastOneMemberOf(dt) => {
+            let a = next_arg()?.into_bag(dt)?;
+            let b = next_arg()?.into_bag(dt)?;
+            Value::Boolean(a.iter().any(|v| b.contains(v)))
+        },
+        f => {
+            let a = match next_arg()? {
+                Evaluated::Value(v) => v,
+                Evaluated::Bag(_) => return Err(format!("{:?} does not take bags", f)),
+            };
+            let b = match next_arg()? {
+                Evaluated::Value(v) => v,
+                Evaluated::Bag(_) => return Err(format!("{:?} does not take bags", f)),
+            };
+            Value::Boolean(f.call_binary(&a, &b)?)
+        },
+    };
+    Ok(Evaluated::Value(result))
+}
+
+/// Combines three-valued results, where `Err` is Indeterminate, such that
+/// false wins over Indeterminate, which wins over true.
+fn all_of (results: impl Iterator<Item = Eval<bool>>) -> Eval<bool> {
+    let mut indeterminate: Option<String> = None;
+    for result in results {
+        match result {
+            Ok(true) => {},
+            Ok(false) => return Ok(false),
+            Err(e) => indeterminate = Some(e),
+        };
+    }
+    match indeterminate {
+        Some(e) => Err(e),
+        None => Ok(true),
+    }
+}
+
+/// Like `all_of()`, but true wins over Indeterminate, which wins over false.
+fn any_of (results: impl Iterator<Item = Eval<bool>>) -> Eval<bool> {
+    let mut indeterminate: Option<String> = None;
+    for result in results {
+        match result {
+            Ok(true) => return Ok(true),
+            Ok(false) => {},
+            Err(e) => indeterminate = Some(e),
+        };
+    }
+    match indeterminate {
+        Some(e) => Err(e),
+        None => Ok(false),
+    }
+}
+
+impl Match {
+
+    fn evaluate (&self, ctx: &RequestContext) -> Eval<bool> {
+        let bag = ctx.designate(&self.designator)?;
+        any_of(bag.iter().map(|v| self.function.call_binary(&self.value, v)))
+    }
+
+}
+
+impl Target {
+
+    fn evaluate (&self, ctx: &RequestContext) -> Eval<bool> {
+        all_of(self.any_ofs.iter().map(|any_of_| {
+            any_of(any_of_.iter().map(|all_of_| {
+                all_of(all_of_.iter().map(|m| m.evaluate(ctx)))
+            }))
+        }))
+    }
+
+}
+
+fn evaluate_obligations (
+    obligations: &[ObligationExpression],
+    effect: Effect,
+    ctx: &RequestContext,
+) -> Eval<Vec<Obligation>> {
+    let mut ret: Vec<Obligation> = Vec::new();
+    for o in obligations.iter().filter(|o| o.fulfill_on == effect) {
+        let mut assignments: Vec<(String, Value)> = Vec::with_capacity(o.assignments.len());
+        for (attribute_id, expr) in o.assignments.iter() {
+            match evaluate(expr, ctx)? {
+                Evaluated::Value(v) => assignments.push((attribute_id.clone(), v)),
+                Evaluated::Bag(bag) => assignments.extend(bag.into_iter().map(|v| (attribute_id.clone(), v))),
+            };
+        }
+        ret.push(Obligation { id: o.id.clone(), assignments });
+    }
+    Ok(ret)
+}
+
+impl Rule {
+
+    fn evaluate (&self, ctx: &RequestContext) -> Outcome {
+        let applicable = self.target.evaluate(ctx).and_then(|matched| match (matched, self.condition.as_ref()) {
+            (true, Some(condition)) => evaluate(condition, ctx)?.into_bool(),
+            (matched, _) => Ok(matched),
+        });
+        match applicable {
+            Ok(true) => {},
+            Ok(false) => return Outcome::of(Decision::NotApplicable),
+            Err(e) => {
+                log::debug!("XACML rule {} is indeterminate: {}", self.id, e);
+                return Outcome::of(Decision::Indeterminate);
+            },
+        };
+        match evaluate_obligations(&self.obligations, self.effect, ctx) {
+            Ok(obligations) => Outcome { decision: self.effect.into(), obligations },
+            Err(e) => {
+                log::debug!("XACML rule {} is indeterminate: {}", self.id, e);
+                Outcome::of(Decision::Indeterminate)
+            },
+        }
+    }
+
+}
+
+/// Combines the outcomes of the children of a policy, only evaluating them
+/// until the result is certain. Obligations are kept from each child with
+/// the same decision as the result.
+fn combine (algorithm: CombiningAlgorithm, mut outcomes: impl Iterator<Item = Outcome>) -> Outcome {
+    let (overriding, fallback) = match algorithm {
+        CombiningAlgorithm::DenyOverrides | CombiningAlgorithm::PermitUnlessDeny => (Decision::Deny, Decision::Permit),
+        CombiningAlgorithm::PermitOverrides | CombiningAlgorithm::DenyUnlessPermit => (Decision::Permit, Decision::Deny),
+        CombiningAlgorithm::FirstApplicable => {
+            return outcomes
+                .find(|o| o.decision != Decision::NotApplicable)
+                .unwrap_or(Outcome::of(Decision::NotApplicable));
+        },
+    };
+    let mut indeterminate = false;
+    let mut fallbacks: Option<Outcome> = None;
+    for outcome in outcomes {
+        if outcome.decision == overriding {
+            return outcome;
+        }
+        if outcome.decision == Decision::Indeterminate {
+            indeterminate = true;
+        }
+        if outcome.decision == fallback {
+            fallbacks.get_or_insert(Outcome::of(fallback)).obligations.extend(outcome.obligations);
+        }
+    }
+    match algorithm {
+        // These algorithms never return NotApplicable or Indeterminate.
+        CombiningAlgorithm::DenyUnlessPermit | CombiningAlgorithm::PermitUnlessDeny => {
+            fallbacks.unwrap_or(Outcome::of(fallback))
+        },
+        _ if indeterminate => Outcome::of(Decision::Indeterminate),
+        _ => fallbacks.unwrap_or(Outcome::of(Decision::NotApplicable)),
+    }
+}
+
+impl Policy {
+
+    pub fn evaluate (&self, ctx: &RequestContext) -> Outcome {
+        match self.target.evaluate(ctx) {
+            Ok(true) => {},
+            Ok(false) => return Outcome::of(Decision::NotApplicable),
+            Err(e) => {
+                log::debug!("XACML policy {} is indeterminate: {}", self.id, e);
+                return Outcome::of(Decision::Indeterminate);
+            },
+        };
+        let mut outcome = match &self.children {
+            PolicyChildren::Rules(rules) => combine(self.algorithm, rules.iter().map(|r| r.evaluate(ctx))),
+            PolicyChildren::Policies(policies) => combine(self.algorithm, policies.iter().map(|p| p.evaluate(ctx))),
+        };
+        let effect = match outcome.decision {
+            Decision::Permit => Effect::Permit,
+            Decision::Deny => Effect::Deny,
+            _ => return outcome,
+        };
+        match evaluate_obligations(&self.obligations, effect, ctx) {
+            Ok(obligations) => outcome.obligations.extend(obligations),
+            Err(e) => {
+                log::debug!("XACML policy {} is indeterminate: {}", self.id, e);
+                return Outcome::of(Decision::Indeterminate);
+            },
+        };
+        outcome
+    }
+
+}
+
+// Parsing of the XML representation of policies.
+
+fn elements <'a, 'input> (node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(|n| n.is_element())
+}
+
+fn required_attribute <'a> (node: Node<'a, '_>, name: &str) -> anyhow::Result<&'a str> {
+    node.attribute(name)
+        .ok_or_else(|| anyhow!("<{}> is missing {}", node.tag_name().name(), name))
+}
+
+fn parse_value (node: Node) -> anyhow::Result<Value> {
+    let data_type = DataType::from_uri(required_attribute(node, "DataType")?)?;
+    Value::parse(data_type, node.text().unwrap_or(""))
+}
+
+fn parse_designator (node: Node) -> anyhow::Result<Designator> {
+    Ok(Designator {
+        category: required_attribute(node, "Category")?.to_string(),
+        attribute_id: required_attribute(node, "AttributeId")?.to_string(),
+        data_type: DataType::from_uri(required_attribute(node, "DataType")?)?,
+        must_be_present: node.attribute("MustBePresent") == Some("true"),
+    })
+}
+
+fn parse_expression (node: Node) -> anyhow::Result<Expression> {
+    match node.tag_name().name() {
+        "AttributeValue" => Ok(Expression::Value(parse_value(node)?)),
+        "AttributeDesignator" => Ok(Expression::Designator(parse_designator(node)?)),
+        "Apply" => {
+            let function = Function::from_id(required_attribute(node, "FunctionId")?)?;
+            let args = elements(node)
+                .filter(|n| n.tag_name().name() != "Description")
+                .map(parse_expression)
+                .collect::<anyhow::Result<Vec<Expression>>>()?;
+            if function.arity().is_some_and(|arity| arity != args.len()) {
+                bail!("wrong number of arguments to {:?}", function);
+            }
+            Ok(Expression::Apply(function, args))
+        },
+        other => bail!("unsupported expression <{}>", other),
+    }
+}
+
+fn parse_match (node: Node) -> anyhow::Result<Match> {
+    let function = Function::from_id(required_attribute(node, "MatchId")?)?;
+    if !function.is_match_function() {
+        bail!("{:?} cannot be used in <Match>", function);
+    }
+    let mut value: Option<Value> = None;
+    let mut designator: Option<Designator> = None;
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "AttributeValue" => value = Some(parse_value(child)?),
+            "AttributeDesignator" => designator = Some(parse_designator(child)?),
+            other => bail!("unsupported element <{}> in <Match>", other),
+        };
+    }
+    Ok(Match {
+        function,
+        value: value.ok_or_else(|| anyhow!("<Match> is missing <AttributeValue>"))?,
+        designator: designator.ok_or_else(|| anyhow!("<Match> is missing <AttributeDesignator>"))?,
+    })
+}
+
+fn parse_target (node: Node) -> anyhow::Result<Target> {
+    let mut target = Target::default();
+    for any_of_ in elements(node) {
+        let mut all_ofs = Vec::new();
+        for all_of_ in elements(any_of_) {
+            all_ofs.push(elements(all_of_)
+                .map(parse_match)
+                .collect::<anyhow::Result<Vec<Match>>>()?);
+        }
+        target.any_ofs.push(all_ofs);
+    }
+    Ok(target)
+}
+
+fn parse_obligations (node: Node) -> anyhow::Result<Vec<ObligationExpression>> {
+    let mut ret = Vec::new();
+    for o in elements(node) {
+        let mut assignments = Vec::new();
+        for a in elements(o) {
+            let expr = elements(a)
+                .next()
+                .ok_or_else(|| anyhow!("<AttributeAssignmentExpression> is empty"))?;
+            assignments.push((
+                required_attribute(a, "AttributeId")?.to_string(),
+                parse_expression(expr)?,
+            ));
+        }
+        ret.push(ObligationExpression {
+            id: required_attribute(o, "ObligationId")?.to_string(),
+            fulfill_on: Effect::parse(required_attribute(o, "FulfillOn")?)?,
+            assignments,
+        });
+    }
+    Ok(ret)
+}
+
+fn parse_rule (node: Node) -> anyhow::Result<Rule> {
+    let mut rule = Rule {
+        id: required_attribute(node, "RuleId")?.to_string(),
+        effect: Effect::parse(required_attribute(node, "Effect")?)?,
+        target: Target::default(),
+        condition: None,
+        obligations: vec![],
+    };
+    for child in elements(node) {
+        match child.tag_name().name() {
+            "Description" | "AdviceExpressions" => {},
+            "Target" => rule.target = parse_target(child)?,
+            "Condition" => {
+                let expr = elements(child)
+                    .next()
+                    .ok_or_else(|| anyhow!("<Condition> is empty"))?;
+                rule.condition = Some(parse_expression(expr)?);
+            },
+            "ObligationExpressions" => rule.obligations = parse_obligations(child)?,
+            other => bail!("unsupported element <{}> in rule {}", other, rule.id),
+        };
+    }
+    Ok(rule)
+}
+
+/// Parses a `<Policy>` or `<PolicySet>`. Elements that would change the
+/// meaning of the policy, but are not supported, are rejected.
+pub fn parse_policy (node: Node) -> anyhow::Result<Policy> {
+    let (id, algorithm, children) = match node.tag_name().name() {
+        "Policy" => (
+            required_attribute(node, "PolicyId")?,
+            required_attribute(node, "RuleCombiningAlgId")?,
+            PolicyChildren::Rules(vec![]),
+        ),
+        "PolicySet" => (
+            required_attribute(node, "PolicySetId")?,
+            required_attribute(node, "PolicyCombiningAlgId")?,
+            PolicyChildren::Policies(vec![]),
+        ),
+        other => bail!("expected <Policy> or <PolicySet>, but got <{}>", other),
+    };
+    let mut policy = Policy {
+        id: id.to_string(),
+        target: Target::default(),
+        algorithm: CombiningAlgorithm::from_id(algorithm)?,
+        children,
+        obligations: vec![],
+    };
+    for child in elements(node) {
+        match (child.tag_name().name(), &mut policy.children) {
+            ("Description" | "PolicyIssuer" | "PolicyDefaults" | "PolicySetDefaults" | "AdviceExpressions", _) => {},
+            ("Target", _) => policy.target = parse_target(child)?,
+            ("ObligationExpressions", _) => policy.obligations = parse_obligations(child)?,
+            ("Rule", PolicyChildren::Rules(rules)) => rules.push(parse_rule(child)?),
+            ("Policy" | "PolicySet", PolicyChildren::Policies(policies)) => policies.push(parse_policy(child)?),
+            (other, _) => bail!("unsupported element <{}> in {}", other, policy.id),
+        };
+    }
+    Ok(policy)
+}
+
+fn path_to_string (path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+fn subtree_path (subtree: Option<&SubtreeSpec>) -> Option<Vec<String>> {
+    subtree
+        .map(|s| s.base.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|pc| String::from_utf8(pc.clone()).ok())
+        .collect()
+}
+
+/// Interprets an arbitrary attribute as a value, if it is of a wrapper type,
+/// or is otherwise UTF-8 text.
+fn any_to_value (any: &prost_types::Any) -> Option<Value> {
+    let type_name = any.type_url.rsplit('/').next().unwrap_or_default();
+    match type_name {
+        "google.protobuf.StringValue" => String::decode(any.value.as_slice()).ok().map(Value::String),
+        "google.protobuf.Int64Value" => i64::decode(any.value.as_slice()).ok().map(Value::Integer),
+        "google.protobuf.UInt64Value" => u64::decode(any.value.as_slice()).ok().map(|i| Value::Integer(i as i64)),
+        "google.protobuf.Int32Value" => i32::decode(any.value.as_slice()).ok().map(|i| Value::Integer(i as i64)),
+        "google.protobuf.UInt32Value" => u32::decode(any.value.as_slice()).ok().map(|i| Value::Integer(i as i64)),
+        "google.protobuf.BoolValue" => bool::decode(any.value.as_slice()).ok().map(Value::Boolean),
+        _ => String::from_utf8(any.value.clone()).ok().map(Value::String),
+    }
+}
+
+fn to_io_error (e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+/// A policy decision point that evaluates a subset of XACML 3.0. Only a
+/// Permit, all of whose obligations are fulfilled, authorizes an operation.
+pub struct XacmlAuthz {
+    db: Arc<Database>,
+    pub policy: Policy,
+}
+
+impl XacmlAuthz {
+
+    pub fn new (db: Arc<Database>, config: XacmlConfig) -> anyhow::Result<Self> {
+        let xml = std::fs::read_to_string(&config.policy_path)?;
+        let doc = roxmltree::Document::parse(&xml)?;
+        let policy = parse_policy(doc.root_element())?;
+        Ok(XacmlAuthz { db, policy })
+    }
+
+    fn get_request_context (
+        &self,
+        session: &Session,
+        action: &str,
+        path: &[String],
+        destination: Option<&[String]>,
+    ) -> std::io::Result<RequestContext> {
+        let mut ctx = RequestContext::default();
+        if let Some(user_id) = session.user_id.as_ref() {
+            ctx.add(SUBJECT_CATEGORY, SUBJECT_ID, Value::String(user_id.clone()));
+        }
+        for group in session.groups.iter() {
+            ctx.add(SUBJECT_CATEGORY, SUBJECT_GROUP, Value::String(group.clone()));
+        }
+        ctx.add(SUBJECT_CATEGORY, SUBJECT_AUTHN_MECHANISM, Value::String(session.auth_mech.clone()));
+        ctx.add(SUBJECT_CATEGORY, SUBJECT_IP_ADDRESS, Value::String(session.peer_ip.to_string()));
+        ctx.add(ACTION_CATEGORY, ACTION_ID, Value::String(action.to_string()));
+        ctx.add(RESOURCE_CATEGORY, RESOURCE_ID, Value::String(path_to_string(path)));
+        for i in 0..path.len() {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_ANCESTOR, Value::String(path_to_string(&path[0..i])));
+        }
+        if let Some(destination) = destination {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_DESTINATION, Value::String(path_to_string(destination)));
+        }
+        let attrs = match get_path_attributes(&self.db, path).map_err(to_io_error)? {
+            Some(attrs) => attrs,
+            None => return Ok(ctx),
+        };
+        if let Some(obj_type) = attrs.r#type.and_then(ObjectType::from_i32) {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_TYPE, Value::String(obj_type.as_str_name().to_lowercase()));
+        }
+        if let Some(uid) = attrs.uid {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_UID, Value::Integer(uid as i64));
+        }
+        if let Some(gid) = attrs.gid {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_GID, Value::Integer(gid as i64));
+        }
+        if let Some(perms) = attrs.perms.as_ref() {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_MODE, Value::Integer(unix_perms_to_u16(perms) as i64));
+        }
+        if let Some(size) = attrs.size {
+            ctx.add(RESOURCE_CATEGORY, RESOURCE_SIZE, Value::Integer(size as i64));
+        }
+        for (name, any) in attrs.other_metadata.iter() {
+            if let Some(value) = any_to_value(any) {
+                ctx.add(RESOURCE_CATEGORY, &format!("{}{}", RESOURCE_ATTR_PREFIX, name), value);
+            }
+        }
+        Ok(ctx)
+    }
+
+    /// Returns false if any obligation is not understood, in which case, the
+    /// decision may not be enforced.
+    fn fulfill_obligations (
+        &self,
+        session: &Session,
+        action: &str,
+        path: &[String],
+        outcome: &Outcome,
+    ) -> bool {
+        let user_id = session.user_id.as_deref().unwrap_or("(anonymous)");
+        let resource = path_to_string(path);
+        for o in outcome.obligations.iter() {
+            match o.id.as_str() {
+                OBLIGATION_LOG => {
+                    let message = o.assignments.iter()
+                        .filter(|(attribute_id, _)| attribute_id == "message")
+                        .map(|(_, v)| v.to_string())
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    log::info!("XACML {:?}: {} {} {} {}", outcome.decision, user_id, action, resource, message);
+                },
+                OBLIGATION_MICROSTAMP => {
+                    let stamp = ulid::Ulid::new();
+                    log::info!("Microstamp {} issued to {} for {} {}", stamp, user_id, action, resource);
+                },
+                other => {
+                    log::error!("Could not fulfill unrecognized XACML obligation {}", other);
+                    return false;
+                },
+            };
+        }
+        true
+    }
+
+    fn authorize (
+        &self,
+        session: &Session,
+        action: &str,
+        path: &[String],
+        destination: Option<&[String]>,
+    ) -> std::io::Result<bool> {
+        // Paths that could be mistaken for others once joined into a
+        // resource ID are never permitted.
+        let path = match validate_path(path) {
+            Ok(path) => path,
+            Err(_) => return Ok(false),
+        };
+        let destination = match destination.map(validate_path).transpose() {
+            Ok(destination) => destination,
+            Err(_) => return Ok(false),
+        };
+        let ctx = self.get_request_context(session, action, &path, destination.as_deref())?;
+        let outcome = self.policy.evaluate(&ctx);
+        let fulfilled = self.fulfill_obligations(session, action, &path, &outcome);
+        Ok(fulfilled && outcome.decision == Decision::Permit)
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for XacmlAuthz {
+
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.authorize(session, "WatchMany", &path, None),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "MakeDirectory", &path, None)
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Upload", &path, None)
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Append", &path, None)
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Patch", &path, None)
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Download", &path, None)
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Delete", &path, None)
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "List", &path, None)
+    }
+
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Move", &path, Some(&arg.destination))
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Copy", &path, Some(&arg.destination))
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "ListIncompleteUploads", &request.get_ref().subtree, None)
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "GetPresignedDownload", &path, None)
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "GetPresignedUpload", &path, None)
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "WatchOnce", &path, None)
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "GetAttributes", &path, None)
+    }
+
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "SetAttributes", &path, None)
+    }
+
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.authorize(session, "DeleteMany", &path, None),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "GetServiceInfo", &[], None)
+    }
+
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.authorize(session, "GetAuditTrail", &path, None),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "StartTransaction", &[], None)
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "CommitTransaction", &[], None)
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        self.authorize(session, "AbortTransaction", &[], None)
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let arg = request.get_ref();
+        let path = arg.source.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        let destination = arg.destination.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "CreateLink", &path, Some(&destination))
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Unlink", &path, None)
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        match subtree_path(request.get_ref().subtree.as_ref()) {
+            Some(path) => self.authorize(session, "Search", &path, None),
+            None => Ok(false),
+        }
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        let path = request.get_ref().target.as_ref().map(|t| t.path.clone()).unwrap_or_default();
+        self.authorize(session, "Query", &path, None)
+    }
+
+}
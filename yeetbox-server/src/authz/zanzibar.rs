@@ -0,0 +1,757 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use redb::{Database, ReadableTable, TableDefinition};
+use crate::authz::Authorizer;
+use crate::config::ZanzibarConfig;
+use crate::grpc::remotefs::{
+    AbortTransactionArg, AppendArg, CommitTransactionArg, CopyArg, CreateLinkArg, DeleteArg,
+    DeleteManyArg, DownloadArg, GetAttributesArg, GetAuditTrailArg, GetPresignedDownloadArg,
+    GetPresignedUploadArg, GetServiceInfoArg, ListArg, ListIncompleteUploadsArg,
+    MakeDirectoryArg, MoveArg, PatchArg, SetAttributesArg, StartTransactionArg, UnlinkArg,
+    UploadArg, WatchManyArg, WatchOnceArg, FileSystemError, RelationshipTuple,
+    ReadRelationshipsArg, ReadRelationshipsResult, WriteRelationshipsArg,
+    WriteRelationshipsResult, SearchArg, QueryArg,
+};
+use crate::authn::Session;
+use crate::storage::database::validate_path;
+
+type TupleKey = (&'static str, &'static str, &'static str);
+
+// This is where relationship tuples are stored, keyed by object, relation,
+// and user, in that order.
+const TUPLES_TABLE: TableDefinition<TupleKey, ()> = TableDefinition::new("rel_tuples");
+
+/// The namespace of files and folders, whose IDs are their paths.
+pub const FS_NAMESPACE: &str = "fs";
+
+/// The relation from an object to the objects that it inherits from. For files
+/// and folders, this is implied by the path, but it may also be written.
+pub const PARENT_RELATION: &str = "parent";
+pub const OWNER_RELATION: &str = "owner";
+pub const EDITOR_RELATION: &str = "editor";
+pub const VIEWER_RELATION: &str = "viewer";
+pub const MEMBER_RELATION: &str = "member";
+
+/// Users with the `admin` relation to this object may administer tuples.
+pub const ADMIN_OBJECT: &str = "system:yeetbox";
+pub const ADMIN_RELATION: &str = "admin";
+
+/// Bounds the nesting of usersets in a check. The implied parents of files
+/// and folders do not count towards it, since a path has finitely many.
+const MAX_CHECK_DEPTH: u32 = 32;
+
+const DEFAULT_READ_LIMIT: usize = 1000;
+
+/// How the users that have a relation are determined, as in the userset
+/// rewrite rules of Zanzibar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UsersetRewrite {
+    /// The users of the tuples written for this relation.
+    This,
+    /// The users that have another relation to the same object.
+    ComputedUserset(String),
+    /// The users that have `computed_userset` to each object that is the user
+    /// of a `tupleset` tuple of this object.
+    TupleToUserset {
+        tupleset: String,
+        computed_userset: String,
+    },
+}
+
+/// The relations of a type of object. A relation has the union of the users
+/// of each of its rewrites. Undefined relations only have their own tuples.
+#[derive(Clone, Debug, Default)]
+pub struct NamespaceConfig {
+    pub relations: HashMap<String, Vec<UsersetRewrite>>,
+}
+
+impl NamespaceConfig {
+
+    pub fn relation (mut self, name: &str, rewrites: Vec<UsersetRewrite>) -> Self {
+        self.relations.insert(name.to_string(), rewrites);
+        self
+    }
+
+}
+
+fn inherited (relation: &str) -> UsersetRewrite {
+    UsersetRewrite::TupleToUserset {
+        tupleset: PARENT_RELATION.to_string(),
+        computed_userset: relation.to_string(),
+    }
+}
+
+/// Owners are editors, and editors are viewers, and each is inherited from the
+/// parent folder.
+pub fn default_namespaces () -> HashMap<String, NamespaceConfig> {
+    use UsersetRewrite::{ComputedUserset, This};
+    let fs = NamespaceConfig::default()
+        .relation(OWNER_RELATION, vec![This, inherited(OWNER_RELATION)])
+        .relation(EDITOR_RELATION, vec![
+            This,
+            ComputedUserset(OWNER_RELATION.to_string()),
+            inherited(EDITOR_RELATION),
+        ])
+        .relation(VIEWER_RELATION, vec![
+            This,
+            ComputedUserset(EDITOR_RELATION.to_string()),
+            inherited(VIEWER_RELATION),
+        ]);
+    HashMap::from([
+        (FS_NAMESPACE.to_string(), fs),
+    ])
+}
+
+/// Returns the object ID of the file or folder at `path`, or `None` if the path
+/// is not valid, in which case its ID could be mistaken for another's.
+pub fn fs_object (path: &[String]) -> Option<String> {
+    let path = validate_path(path).ok()?;
+    Some(format!("{}:/{}", FS_NAMESPACE, path.join("/")))
+}
+
+fn fs_parent (object: &str) -> Option<String> {
+    let path = object.strip_prefix(FS_NAMESPACE)?.strip_prefix(':')?;
+    let (parent, _) = path.rsplit_once('/')?;
+    if parent.is_empty() {
+        if path.len() > 1 {
+            return Some(format!("{}:/", FS_NAMESPACE));
+        }
+        return None;
+    }
+    Some(format!("{}:{}", FS_NAMESPACE, parent))
+}
+
+/// Normalizes the object ID of a file or folder, so that tuples written for
+/// it apply to however the client spells the path.
+fn normalize_object (object: &str) -> String {
+    match object.strip_prefix(FS_NAMESPACE).and_then(|o| o.strip_prefix(':')) {
+        Some(path) => {
+            let path: Vec<String> = path.split('/')
+                .filter(|pc| !pc.is_empty())
+                .map(|pc| pc.to_string())
+                .collect();
+            fs_object(&path).unwrap_or_else(|| object.to_string())
+        },
+        None => object.to_string(),
+    }
+}
+
+fn is_valid_relation (relation: &str) -> bool {
+    !relation.is_empty() && !relation.contains(['#', '@', ':'])
+}
+
+fn is_valid_object (object: &str) -> bool {
+    object.split_once(':').is_some_and(|(ns, id)| !ns.is_empty() && !id.is_empty())
+}
+
+/// Checks and normalizes a tuple written by an administrator.
+fn validate_tuple (tuple: &RelationshipTuple) -> std::result::Result<(String, String, String), tonic::Status> {
+    if !is_valid_object(&tuple.object) || !is_valid_relation(&tuple.relation) {
+        return Err(tonic::Status::invalid_argument("malformed relationship tuple"));
+    }
+    let user = if tuple.user == "*" {
+        tuple.user.clone()
+    } else {
+        match tuple.user.rsplit_once('#') {
+            Some((object, relation)) if is_valid_object(object) && is_valid_relation(relation) => {
+                format!("{}#{}", normalize_object(object), relation)
+            },
+            None if is_valid_object(&tuple.user) => tuple.user.clone(),
+            _ => return Err(tonic::Status::invalid_argument("malformed relationship tuple")),
+        }
+    };
+    Ok((normalize_object(&tuple.object), tuple.relation.clone(), user))
+}
+
+fn subtree_path (base: &[Vec<u8>]) -> Option<Vec<String>> {
+    base.iter()
+        .map(|pc| String::from_utf8(pc.clone()).ok())
+        .collect()
+}
+
+fn to_io_error (e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+/// The user on whose behalf a check is performed.
+struct Subject<'a> {
+    user_id: Option<&'a str>,
+    groups: &'a [String],
+}
+
+impl <'a> Subject<'a> {
+
+    fn from_session (session: &'a Session) -> Self {
+        Subject {
+            user_id: session.user_id.as_deref(),
+            groups: &session.groups,
+        }
+    }
+
+    /// Whether the subject is the user of a tuple, without evaluating usersets.
+    /// Groups asserted by the authenticator are memberships of those groups.
+    fn matches (&self, user: &str) -> bool {
+        if user == "*" {
+            return true;
+        }
+        if user == "user:*" {
+            return self.user_id.is_some();
+        }
+        if let Some(user_id) = user.strip_prefix("user:") {
+            return self.user_id == Some(user_id);
+        }
+        let group = user.strip_prefix("group:")
+            .and_then(|g| g.strip_suffix(MEMBER_RELATION))
+            .and_then(|g| g.strip_suffix('#'));
+        match group {
+            Some(group) => self.groups.iter().any(|g| g == group),
+            None => false,
+        }
+    }
+
+}
+
+/// The relations that have already been checked for the subject of a single
+/// check, so that each is only evaluated once, and cycles are stopped.
+#[derive(Default)]
+struct CheckMemo {
+    results: HashMap<(String, String), bool>,
+    pending: HashSet<(String, String)>,
+    /// Whether a relation was taken to be absent because it was still pending,
+    /// in which case a negative result may not be remembered.
+    cut: bool,
+}
+
+/// An authorizer that evaluates Zanzibar-style relationship tuples, such that
+/// reading requires the `viewer` relation, writing requires `editor`, and
+/// changing attributes requires `owner`.
+pub struct ZanzibarAuthz {
+    pub db: Arc<Database>,
+    pub config: ZanzibarConfig,
+    pub namespaces: HashMap<String, NamespaceConfig>,
+}
+
+impl ZanzibarAuthz {
+
+    pub fn new (db: Arc<Database>, config: ZanzibarConfig) -> anyhow::Result<Self> {
+        let w = db.begin_write()?;
+        w.open_table(TUPLES_TABLE)?;
+        w.commit()?;
+        Ok(ZanzibarAuthz {
+            db,
+            config,
+            namespaces: default_namespaces(),
+        })
+    }
+
+    fn users_of <T> (&self, tuples: &T, object: &str, relation: &str) -> std::result::Result<Vec<String>, redb::Error>
+        where T: ReadableTable<TupleKey, ()> {
+        let mut users = vec![];
+        for entry in tuples.range((object, relation, "")..)? {
+            let (key, _) = entry?;
+            let (o, r, u) = key.value();
+            if o != object || r != relation {
+                break;
+            }
+            users.push(u.to_string());
+        }
+        Ok(users)
+    }
+
+    fn check <T> (
+        &self,
+        tuples: &T,
+        object: &str,
+        relation: &str,
+        subject: &Subject,
+        depth: u32,
+        memo: &mut CheckMemo,
+    ) -> std::result::Result<bool, redb::Error>
+        where T: ReadableTable<TupleKey, ()> {
+        let key = (object.to_string(), relation.to_string());
+        if let Some(found) = memo.results.get(&key) {
+            return Ok(*found);
+        }
+        if memo.pending.contains(&key) {
+            memo.cut = true;
+            return Ok(false);
+        }
+        if depth == 0 {
+            log::warn!("Relationship check of {}#{} exceeded the maximum depth", object, relation);
+            return Ok(false);
+        }
+        let outer_cut = std::mem::replace(&mut memo.cut, false);
+        memo.pending.insert(key.clone());
+        let found = self.check_rewrites(tuples, object, relation, subject, depth, memo);
+        memo.pending.remove(&key);
+        let found = found?;
+        if found || !memo.cut {
+            memo.results.insert(key, found);
+        }
+        memo.cut |= outer_cut;
+        Ok(found)
+    }
+
+    fn check_rewrites <T> (
+        &self,
+        tuples: &T,
+        object: &str,
+        relation: &str,
+        subject: &Subject,
+        depth: u32,
+        memo: &mut CheckMemo,
+    ) -> std::result::Result<bool, redb::Error>
+        where T: ReadableTable<TupleKey, ()> {
+        let namespace = object.split_once(':').map_or("", |(ns, _)| ns);
+        let this_only = [UsersetRewrite::This];
+        let rewrites = self.namespaces.get(namespace)
+            .and_then(|ns| ns.relations.get(relation))
+            .map_or(this_only.as_slice(), |r| r.as_slice());
+        for rewrite in rewrites {
+            let found = match rewrite {
+                UsersetRewrite::This => {
+                    let mut found = false;
+                    for user in self.users_of(tuples, object, relation)? {
+                        if subject.matches(&user) {
+                            found = true;
+                            break;
+                        }
+                        if let Some((userset_object, userset_relation)) = user.rsplit_once('#') {
+                            if self.check(tuples, userset_object, userset_relation, subject, depth - 1, memo)? {
+                                found = true;
+                                break;
+                            }
+                        }
+                    }
+                    found
+                },
+                UsersetRewrite::ComputedUserset(computed) => {
+                    self.check(tuples, object, computed, subject, depth - 1, memo)?
+                },
+                UsersetRewrite::TupleToUserset { tupleset, computed_userset } => {
+                    let mut related: Vec<(String, u32)> = self.users_of(tuples, object, tupleset)?
+                        .into_iter()
+                        .map(|o| (o, depth - 1))
+                        .collect();
+                    if namespace == FS_NAMESPACE && tupleset == PARENT_RELATION {
+                        related.extend(fs_parent(object).map(|o| (o, depth)));
+                    }
+                    let mut found = false;
+                    for (related_object, depth) in related {
+                        if self.check(tuples, &related_object, computed_userset, subject, depth, memo)? {
+                            found = true;
+                            break;
+                        }
+                    }
+                    found
+                },
+            };
+            if found {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether the session has `relation` to `object`.
+    pub fn check_session (&self, session: &Session, object: &str, relation: &str) -> std::io::Result<bool> {
+        let r = self.db.begin_read().map_err(to_io_error)?;
+        let tuples = r.open_table(TUPLES_TABLE).map_err(to_io_error)?;
+        self.check(&tuples, object, relation, &Subject::from_session(session), MAX_CHECK_DEPTH, &mut CheckMemo::default())
+            .map_err(to_io_error)
+    }
+
+    fn check_path (&self, session: &Session, path: &[String], relation: &str) -> std::io::Result<bool> {
+        match fs_object(path) {
+            Some(object) => self.check_session(session, &object, relation),
+            None => Ok(false),
+        }
+    }
+
+    fn is_admin (&self, session: &Session) -> std::io::Result<bool> {
+        if session.user_id.as_ref().is_some_and(|u| self.config.admins.contains(u)) {
+            return Ok(true);
+        }
+        self.check_session(session, ADMIN_OBJECT, ADMIN_RELATION)
+    }
+
+}
+
+#[tonic::async_trait]
+impl Authorizer for ZanzibarAuthz {
+
+    // The targets are not known until after the stream has started.
+    async fn is_authz_watch_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchManyArg>,
+    ) -> std::io::Result<bool> {
+        self.is_admin(session)
+    }
+
+    async fn is_authz_make_directory(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MakeDirectoryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_append(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AppendArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_patch(
+        &self,
+        session: &Session,
+        request: &tonic::Request<PatchArg>,
+    ) -> std::io::Result<bool> {
+        // A reserved upload's target usually does not exist yet, in which case
+        // this falls back to the parent, just like creating the target would.
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_delete(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_move(
+        &self,
+        session: &Session,
+        request: &tonic::Request<MoveArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match req.target.as_ref() {
+            Some(target) => Ok(self.check_path(session, &target.path, EDITOR_RELATION)?
+                && self.check_path(session, &req.destination, EDITOR_RELATION)?),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_copy(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CopyArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match req.target.as_ref() {
+            Some(target) => Ok(self.check_path(session, &target.path, VIEWER_RELATION)?
+                && self.check_path(session, &req.destination, EDITOR_RELATION)?),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_list_incomplete_uploads(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ListIncompleteUploadsArg>,
+    ) -> std::io::Result<bool> {
+        self.check_path(session, &request.get_ref().subtree, VIEWER_RELATION)
+    }
+
+    async fn is_authz_get_presigned_download(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedDownloadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_presigned_upload(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetPresignedUploadArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_watch_once(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WatchOnceArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_set_attributes(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SetAttributesArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, OWNER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    // Since relations are only ever inherited, editors of the base are editors
+    // of everything beneath it.
+    async fn is_authz_delete_many(
+        &self,
+        session: &Session,
+        request: &tonic::Request<DeleteManyArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, EDITOR_RELATION),
+            Some(None) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_get_service_info(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetServiceInfoArg>,
+    ) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    // The audit trail reveals activity by other users.
+    async fn is_authz_get_audit_trail(
+        &self,
+        session: &Session,
+        request: &tonic::Request<GetAuditTrailArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, OWNER_RELATION),
+            Some(None) => Ok(false),
+            None => self.is_admin(session),
+        }
+    }
+
+    async fn is_authz_start_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<StartTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_commit_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CommitTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_abort_transaction(
+        &self,
+        session: &Session,
+        request: &tonic::Request<AbortTransactionArg>,
+    ) -> std::io::Result<bool> {
+        Ok(session.auth_mech != "ANONYMOUS")
+    }
+
+    async fn is_authz_create_link(
+        &self,
+        session: &Session,
+        request: &tonic::Request<CreateLinkArg>,
+    ) -> std::io::Result<bool> {
+        let req = request.get_ref();
+        match (req.source.as_ref(), req.destination.as_ref()) {
+            (Some(source), Some(destination)) => Ok(self.check_path(session, &source.path, VIEWER_RELATION)?
+                && self.check_path(session, &destination.path, EDITOR_RELATION)?),
+            _ => Ok(true),
+        }
+    }
+
+    async fn is_authz_unlink(
+        &self,
+        session: &Session,
+        request: &tonic::Request<UnlinkArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, EDITOR_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_search(
+        &self,
+        session: &Session,
+        request: &tonic::Request<SearchArg>,
+    ) -> std::io::Result<bool> {
+        let subtree = request.get_ref().subtree.as_ref();
+        match subtree.map(|s| subtree_path(&s.base)) {
+            Some(Some(path)) => self.check_path(session, &path, VIEWER_RELATION),
+            Some(None) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    async fn is_authz_query(
+        &self,
+        session: &Session,
+        request: &tonic::Request<QueryArg>,
+    ) -> std::io::Result<bool> {
+        match request.get_ref().target.as_ref() {
+            Some(target) => self.check_path(session, &target.path, VIEWER_RELATION),
+            None => Ok(true),
+        }
+    }
+
+    async fn write_relationships(
+        &self,
+        session: &Session,
+        request: &tonic::Request<WriteRelationshipsArg>,
+    ) -> std::result::Result<WriteRelationshipsResult, tonic::Status> {
+        if !self.is_admin(session)? {
+            return Ok(WriteRelationshipsResult {
+                err: Some(FileSystemError::not_permitted()),
+            });
+        }
+        let req = request.get_ref();
+        let deletes = req.deletes.iter()
+            .map(validate_tuple)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let writes = req.writes.iter()
+            .map(validate_tuple)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        {
+            let mut tuples = w.open_table(TUPLES_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to tuples table"))?;
+            for (object, relation, user) in deletes.iter() {
+                tuples.remove((object.as_str(), relation.as_str(), user.as_str()))
+                    .map_err(|_| tonic::Status::internal("could not delete tuple"))?;
+            }
+            for (object, relation, user) in writes.iter() {
+                tuples.insert((object.as_str(), relation.as_str(), user.as_str()), ())
+                    .map_err(|_| tonic::Status::internal("could not write tuple"))?;
+            }
+        }
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        log::info!(
+            "{:?} wrote {} and deleted {} relationship tuples",
+            session.user_id,
+            writes.len(),
+            deletes.len(),
+        );
+        Ok(WriteRelationshipsResult::default())
+    }
+
+    async fn read_relationships(
+        &self,
+        session: &Session,
+        request: &tonic::Request<ReadRelationshipsArg>,
+    ) -> std::result::Result<ReadRelationshipsResult, tonic::Status> {
+        if !self.is_admin(session)? {
+            return Ok(ReadRelationshipsResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            });
+        }
+        let req = request.get_ref();
+        let object = if req.object.is_empty() { String::new() } else { normalize_object(&req.object) };
+        let limit = if req.limit == 0 { DEFAULT_READ_LIMIT } else { req.limit as usize };
+        let r = self.db.begin_read()
+            .map_err(|_| tonic::Status::internal("could not read from database"))?;
+        let tuples = r.open_table(TUPLES_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from tuples table"))?;
+        let iter = tuples.range((object.as_str(), "", "")..)
+            .map_err(|_| tonic::Status::internal("could not read from tuples table"))?;
+        let mut ret = vec![];
+        for entry in iter {
+            let (key, _) = entry
+                .map_err(|_| tonic::Status::internal("could not read from tuples table"))?;
+            let (o, r, u) = key.value();
+            if !object.is_empty() && o != object {
+                break;
+            }
+            if (!req.relation.is_empty() && r != req.relation) || (!req.user.is_empty() && u != req.user) {
+                continue;
+            }
+            ret.push(RelationshipTuple {
+                object: o.to_string(),
+                relation: r.to_string(),
+                user: u.to_string(),
+            });
+            if ret.len() >= req.skip as usize + limit {
+                break;
+            }
+        }
+        Ok(ReadRelationshipsResult {
+            err: None,
+            tuples: ret.into_iter().skip(req.skip as usize).take(limit).collect(),
+        })
+    }
+
+}
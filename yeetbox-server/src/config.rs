@@ -1,14 +1,208 @@
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::grpc::remotefs::{DistinguishedNameRestrictions, SecurityLabel};
+use crate::x509::Clearance;
+
 // TODO: Deserialize from: https://crates.io/crates/serde_kdl
 
 #[derive(Debug, Clone)]
 pub struct SimpleAuthConfig {
     pub username: String,
+    /// Either the cleartext password, or a stored SCRAM-SHA-256 credential in
+    /// the format of IETF RFC 5803.
     pub password: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// If present, clients may present certificates issued by this CA, and
+    /// authenticate using them via SASL EXTERNAL.
+    pub client_ca_path: Option<PathBuf>,
+    /// If false, clients that do not present a certificate are refused.
+    pub client_auth_optional: bool,
+}
+
+/// Settings for SASL EXTERNAL, which uses TLS client certificates.
+#[derive(Debug, Clone)]
+pub struct ExternalAuthConfig {
+    /// The short name (e.g. `cn`) of the subject DN attribute whose value
+    /// becomes the user ID.
+    pub user_id_attribute: String,
+    /// Each non-empty field must match the subject DN, or the certificate is
+    /// not accepted for authentication.
+    pub restrictions: DistinguishedNameRestrictions,
+}
+
+impl Default for ExternalAuthConfig {
+    fn default() -> Self {
+        ExternalAuthConfig {
+            user_id_attribute: String::from("cn"),
+            restrictions: DistinguishedNameRestrictions::default(),
+        }
+    }
+}
+
+/// Settings for SASL OAUTHBEARER, which accepts JWT access tokens issued by
+/// an OpenID Connect provider.
+#[derive(Debug, Clone)]
+pub struct OAuthBearerConfig {
+    /// A JSON Web Key Set (IETF RFC 7517) containing the keys with which the
+    /// tokens are signed.
+    pub jwks_path: PathBuf,
+    /// The required value of the `iss` claim.
+    pub issuer: String,
+    /// The `aud` claim must contain this value.
+    pub audience: String,
+    /// The claim whose value becomes the user ID.
+    pub user_id_claim: String,
+    /// The claim listing the groups of which the user is a member, either as
+    /// an array or as a space-separated string.
+    pub groups_claim: String,
+    /// Allowed clock skew, in seconds, when checking `exp` and `nbf`.
+    pub leeway: u64,
+}
+
+impl OAuthBearerConfig {
+    pub fn new (jwks_path: PathBuf, issuer: String, audience: String) -> Self {
+        OAuthBearerConfig {
+            jwks_path,
+            issuer,
+            audience,
+            user_id_claim: String::from("sub"),
+            groups_claim: String::from("groups"),
+            leeway: 60,
+        }
+    }
+}
+
+/// Settings for enforcing Unix ownership and permissions.
+#[derive(Debug, Clone)]
+pub struct UnixPermissionsConfig {
+    /// A file in the format of `/etc/passwd`, mapping user IDs to uids and
+    /// primary gids.
+    pub passwd_path: PathBuf,
+    /// A file in the format of `/etc/group`, mapping group names to gids and
+    /// listing their members.
+    pub group_path: Option<PathBuf>,
+    pub root_uid: u32,
+    pub root_gid: u32,
+    /// The permissions of the root folder, which has no record of its own.
+    pub root_mode: u16,
+    /// The uid and gid of anonymous users and users not in the passwd file.
+    pub nobody_uid: u32,
+    pub nobody_gid: u32,
+}
+
+impl UnixPermissionsConfig {
+    pub fn new (passwd_path: PathBuf, group_path: Option<PathBuf>) -> Self {
+        UnixPermissionsConfig {
+            passwd_path,
+            group_path,
+            root_uid: 0,
+            root_gid: 0,
+            // Like `/tmp`: anyone may create objects, but only delete their own.
+            root_mode: 0o1777,
+            nobody_uid: 65534,
+            nobody_gid: 65534,
+        }
+    }
+}
+
+/// Settings for authorization using Zanzibar-style relationship tuples.
+#[derive(Debug, Clone, Default)]
+pub struct ZanzibarConfig {
+    /// Users who may administer tuples, in addition to those with the `admin`
+    /// relation to `system:yeetbox`.
+    pub admins: Vec<String>,
+}
+
+/// Settings for role-based authorization.
+#[derive(Debug, Clone, Default)]
+pub struct RbacConfig {
+    /// Users who may administer roles and grants, in addition to those granted
+    /// a role with the `admin` permission on the root.
+    pub admins: Vec<String>,
+}
+
+/// Settings for authorization by a Rhai script.
+#[derive(Debug, Clone)]
+pub struct ScriptAuthzConfig {
+    /// The script, which is reloaded whenever its modification time changes.
+    pub script_path: PathBuf,
+    /// The maximum number of operations per call, which bounds its run time.
+    pub max_operations: u64,
+    pub max_call_levels: usize,
+    pub max_expr_depth: usize,
+    /// Limits on the sizes of values, which bound the memory of the script.
+    pub max_string_size: usize,
+    pub max_array_size: usize,
+    pub max_map_size: usize,
+}
+
+impl ScriptAuthzConfig {
+    pub fn new (script_path: PathBuf) -> Self {
+        ScriptAuthzConfig {
+            script_path,
+            max_operations: 100_000,
+            max_call_levels: 32,
+            max_expr_depth: 64,
+            max_string_size: 64 * 1024,
+            max_array_size: 10_000,
+            max_map_size: 10_000,
+        }
+    }
+}
+
+/// Settings for authorization by an XACML policy decision point.
+#[derive(Debug, Clone)]
+pub struct XacmlConfig {
+    /// An XACML 3.0 `<Policy>` or `<PolicySet>` in XML.
+    pub policy_path: PathBuf,
+}
+
+/// Settings for mandatory access control using security labels.
+#[derive(Debug, Clone)]
+pub struct ClearanceAuthzConfig {
+    /// The label of objects that have none, and are in no labeled folder.
+    pub default_label: SecurityLabel,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub simple_auth: Option<SimpleAuthConfig>,
+    /// Whether to offer PLAIN, which transfers passwords in cleartext.
+    pub allow_plain_authn: bool,
+    /// If absent, the server listens in plaintext.
+    pub tls: Option<TlsConfig>,
+    /// If absent, SASL EXTERNAL is not offered.
+    pub external_authn: Option<ExternalAuthConfig>,
+    /// If absent, SASL OAUTHBEARER is not offered.
+    pub oauthbearer_authn: Option<OAuthBearerConfig>,
+    /// If present, Unix ownership and permissions are enforced instead of
+    /// the simple authorizer.
+    pub unix_permissions: Option<UnixPermissionsConfig>,
+    /// If present, relationship tuples are used for authorization instead.
+    pub zanzibar: Option<ZanzibarConfig>,
+    /// If present, roles granted on subtrees are used for authorization instead.
+    pub rbac: Option<RbacConfig>,
+    /// If present, a Rhai script makes authorization decisions instead.
+    pub script_authz: Option<ScriptAuthzConfig>,
+    /// If present, an XACML policy makes authorization decisions instead.
+    pub xacml: Option<XacmlConfig>,
+    /// If present, security labels and clearances are enforced instead.
+    pub clearance_authz: Option<ClearanceAuthzConfig>,
+    /// The clearances of users, by user ID, for those that do not present
+    /// a certificate containing any.
+    pub clearances: HashMap<String, Vec<Clearance>>,
+    /// Sessions that go unused for this long expire.
+    pub session_idle_timeout: chrono::Duration,
+    /// Sessions expire this long after authentication, even if in use.
+    pub session_absolute_timeout: chrono::Duration,
+    /// Incomplete uploads that go untouched for this long are removed. They
+    /// are kept indefinitely if this is not set.
+    pub upload_idle_timeout: Option<chrono::Duration>,
     // TODO: In the future, the SASL server config will go here.
 }
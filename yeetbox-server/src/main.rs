@@ -8,15 +8,17 @@ mod storage;
 mod time64;
 mod utils;
 mod web;
-use authn::Authenticator;
+mod x509;
+use authn::{Authenticator, MultiAuth, SessionInterceptor, SessionStore};
 use authz::Authorizer;
-use config::Config;
+use config::{Config, ExternalAuthConfig, OAuthBearerConfig, SimpleAuthConfig, TlsConfig, UnixPermissionsConfig, ZanzibarConfig, RbacConfig, ScriptAuthzConfig, XacmlConfig, ClearanceAuthzConfig};
 use grpc::remotefs::file_system_service_server::{FileSystemService, FileSystemServiceServer};
 
 use logging::get_default_log4rs_config;
+use grpc::remotefs::SecurityLabel;
 use storage::database::DatabaseStorage;
 use storage::Storage;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::{transport::{Certificate, Identity, Server, ServerTlsConfig}, Request, Response, Status};
 // use warp::Filter;
 // use warp::http::StatusCode;
 // use chrono::prelude::*;
@@ -28,11 +30,16 @@ use tokio::sync::Mutex;
 // use std::rc::Rc;
 // use utils::grpc_timestamp_to_chrono;
 
+/// How often incomplete uploads are checked for having gone idle.
+const UPLOAD_REAP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct FileSystemServiceProvider {
     pub authn: Arc<Mutex<dyn Authenticator + Send + Sync + 'static>>,
     pub authz: Arc<Mutex<dyn Authorizer + Send + Sync + 'static>>,
     pub storage: Arc<Mutex<dyn Storage + Send + Sync + 'static>>,
+    // This is a synchronous mutex, because it is shared with the interceptor.
+    pub sessions: Arc<std::sync::Mutex<SessionStore>>,
     pub config: Arc<Config>,
 }
 
@@ -41,23 +48,199 @@ pub struct FileSystemServiceProvider {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     log4rs::init_config(get_default_log4rs_config()).unwrap();
     let addr = "127.0.0.1:50051".parse()?;
-    let authenticator = Arc::new(Mutex::new(authn::SimpleAuth::new()));
-    let authorizer = Arc::new(Mutex::new(authz::simple::SimpleAuthz::new()));
-    let storage = Arc::new(Mutex::new(DatabaseStorage::new()));
+    let simple_auth_config = match (std::env::var("YEETBOX_USERNAME"), std::env::var("YEETBOX_PASSWORD")) {
+        (Ok(username), Ok(password)) => Some(SimpleAuthConfig { username, password }),
+        _ => None,
+    };
+    let tls_config = match (std::env::var("YEETBOX_TLS_CERT"), std::env::var("YEETBOX_TLS_KEY")) {
+        (Ok(cert_path), Ok(key_path)) => Some(TlsConfig {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: std::env::var("YEETBOX_TLS_CLIENT_CA").ok().map(|p| p.into()),
+            client_auth_optional: std::env::var("YEETBOX_TLS_CLIENT_AUTH_REQUIRED").is_err(),
+        }),
+        _ => None,
+    };
+    let external_authn_config = tls_config.as_ref()
+        .and_then(|tls| tls.client_ca_path.as_ref())
+        .map(|_| ExternalAuthConfig::default());
+    let oauthbearer_config = match (
+        std::env::var("YEETBOX_OAUTH_JWKS"),
+        std::env::var("YEETBOX_OAUTH_ISSUER"),
+        std::env::var("YEETBOX_OAUTH_AUDIENCE"),
+    ) {
+        (Ok(jwks_path), Ok(issuer), Ok(audience)) => Some(OAuthBearerConfig::new(jwks_path.into(), issuer, audience)),
+        _ => None,
+    };
+    let unix_permissions_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("unix") => Some(UnixPermissionsConfig::new(
+            std::env::var("YEETBOX_UNIX_PASSWD")
+                .map_err(|_| "YEETBOX_UNIX_PASSWD is required for unix authorization")?
+                .into(),
+            std::env::var("YEETBOX_UNIX_GROUP").ok().map(|p| p.into()),
+        )),
+        _ => None,
+    };
+    let zanzibar_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("zanzibar") => Some(ZanzibarConfig {
+            admins: std::env::var("YEETBOX_ZANZIBAR_ADMINS")
+                .map(|admins| admins.split(',').map(|a| a.trim().to_string()).collect())
+                .unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let rbac_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("rbac") => Some(RbacConfig {
+            admins: std::env::var("YEETBOX_RBAC_ADMINS")
+                .map(|admins| admins.split(',').map(|a| a.trim().to_string()).collect())
+                .unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let script_authz_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("script") => Some(ScriptAuthzConfig::new(
+            std::env::var("YEETBOX_AUTHZ_SCRIPT")
+                .map_err(|_| "YEETBOX_AUTHZ_SCRIPT is required for script authorization")?
+                .into(),
+        )),
+        _ => None,
+    };
+    let xacml_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("xacml") => Some(XacmlConfig {
+            policy_path: std::env::var("YEETBOX_XACML_POLICY")
+                .map_err(|_| "YEETBOX_XACML_POLICY is required for xacml authorization")?
+                .into(),
+        }),
+        _ => None,
+    };
+    let clearance_authz_config = match std::env::var("YEETBOX_AUTHZ").as_deref() {
+        Ok("clearance") => Some(ClearanceAuthzConfig {
+            default_label: SecurityLabel {
+                policy_id: std::env::var("YEETBOX_SECURITY_POLICY")
+                    .map_err(|_| "YEETBOX_SECURITY_POLICY is required for clearance authorization")?,
+                classification: match std::env::var("YEETBOX_DEFAULT_CLASSIFICATION") {
+                    Ok(c) => x509::parse_classification(&c).ok_or("invalid YEETBOX_DEFAULT_CLASSIFICATION")?,
+                    Err(_) => x509::CLASSIFICATION_UNCLASSIFIED,
+                },
+                categories: vec![],
+            },
+        }),
+        _ => None,
+    };
+    let upload_idle_timeout = match std::env::var("YEETBOX_UPLOAD_TTL").as_deref() {
+        Ok("0") => None,
+        Ok(secs) => Some(chrono::Duration::seconds(
+            secs.parse().map_err(|_| "invalid YEETBOX_UPLOAD_TTL")?
+        )),
+        Err(_) => Some(chrono::Duration::days(1)),
+    };
+    let clearances = match std::env::var("YEETBOX_CLEARANCES") {
+        Ok(path) => x509::parse_clearances(&tokio::fs::read_to_string(path).await?)?,
+        Err(_) => Default::default(),
+    };
+    let config = Config {
+        simple_auth: simple_auth_config,
+        allow_plain_authn: std::env::var("YEETBOX_DISABLE_PLAIN").is_err(),
+        tls: tls_config,
+        external_authn: external_authn_config,
+        oauthbearer_authn: oauthbearer_config,
+        unix_permissions: unix_permissions_config,
+        zanzibar: zanzibar_config,
+        rbac: rbac_config,
+        script_authz: script_authz_config,
+        xacml: xacml_config,
+        clearance_authz: clearance_authz_config,
+        clearances,
+        session_idle_timeout: chrono::Duration::minutes(30),
+        session_absolute_timeout: chrono::Duration::hours(12),
+        upload_idle_timeout,
+    };
+    let mut simple_auth = authn::SimpleAuth::new();
+    simple_auth.allow_plain = config.allow_plain_authn;
+    simple_auth.external = config.external_authn.clone();
+    if let Some(user) = config.simple_auth.as_ref() {
+        simple_auth.add_user(&user.username, &user.password);
+    }
+    let mut server = Server::builder();
+    if let Some(tls) = config.tls.as_ref() {
+        let cert = tokio::fs::read(&tls.cert_path).await?;
+        let key = tokio::fs::read(&tls.key_path).await?;
+        simple_auth.tls_server_end_point = Some(x509::tls_server_end_point(&cert)?);
+        let mut tls_config = ServerTlsConfig::new()
+            .identity(Identity::from_pem(cert, key));
+        if let Some(client_ca_path) = tls.client_ca_path.as_ref() {
+            let client_ca = tokio::fs::read(client_ca_path).await?;
+            tls_config = tls_config
+                .client_ca_root(Certificate::from_pem(client_ca))
+                .client_auth_optional(tls.client_auth_optional);
+        }
+        server = server.tls_config(tls_config)?;
+    }
+    let mut authenticator = MultiAuth::new();
+    authenticator.add(simple_auth);
+    if let Some(oauthbearer_config) = config.oauthbearer_authn.as_ref() {
+        authenticator.add(authn::oauthbearer::OAuthBearerAuth::new(oauthbearer_config.clone())?);
+    }
+    let authenticator = Arc::new(Mutex::new(authenticator));
+    let db_storage = DatabaseStorage::new();
+    let authorizer: Arc<Mutex<dyn Authorizer + Send + Sync + 'static>> =
+        if let Some(unix_config) = config.unix_permissions.as_ref() {
+            Arc::new(Mutex::new(
+                authz::unix::UnixPermissionsAuthz::new(db_storage.db.clone(), unix_config.clone())?
+            ))
+        } else if let Some(zanzibar_config) = config.zanzibar.as_ref() {
+            Arc::new(Mutex::new(
+                authz::zanzibar::ZanzibarAuthz::new(db_storage.db.clone(), zanzibar_config.clone())?
+            ))
+        } else if let Some(rbac_config) = config.rbac.as_ref() {
+            Arc::new(Mutex::new(
+                authz::rbac::RbacAuthz::new(db_storage.db.clone(), rbac_config.clone())?
+            ))
+        } else if let Some(script_config) = config.script_authz.as_ref() {
+            Arc::new(Mutex::new(authz::script::ScriptAuthz::new(script_config.clone())?))
+        } else if let Some(xacml_config) = config.xacml.as_ref() {
+            Arc::new(Mutex::new(
+                authz::xacml::XacmlAuthz::new(db_storage.db.clone(), xacml_config.clone())?
+            ))
+        } else if let Some(clearance_config) = config.clearance_authz.as_ref() {
+            Arc::new(Mutex::new(
+                authz::clearance::ClearanceAuthz::new(db_storage.db.clone(), clearance_config.clone())
+            ))
+        } else {
+            Arc::new(Mutex::new(authz::simple::SimpleAuthz::new()))
+        };
+    let storage = Arc::new(Mutex::new(db_storage));
+    if let Some(max_idle) = config.upload_idle_timeout {
+        let storage = storage.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(UPLOAD_REAP_INTERVAL);
+            loop {
+                interval.tick().await;
+                match storage.lock().await.remove_idle_uploads(max_idle).await {
+                    Ok(0) => {},
+                    Ok(n) => log::info!("Removed {} idle uploads", n),
+                    Err(e) => log::error!("Could not remove idle uploads: {}", e),
+                };
+            }
+        });
+    }
+    let sessions = Arc::new(std::sync::Mutex::new(SessionStore::new(
+        config.session_idle_timeout,
+        config.session_absolute_timeout,
+    )));
     let fs_provider = FileSystemServiceProvider {
         authn: authenticator,
         authz: authorizer,
         storage,
-        config: Arc::new(Config {
-            simple_auth: None,
-        }),
+        sessions: sessions.clone(),
+        config: Arc::new(config),
     };
 
-    let fs_server = FileSystemServiceServer::new(fs_provider);
-    // let fs_with_auth = FileSystemServiceServer::with_interceptor(fs_server, interceptor);
+    let interceptor = SessionInterceptor { sessions };
+    let fs_server = FileSystemServiceServer::with_interceptor(fs_provider, interceptor);
 
     log::info!("Listening on {}", addr);
-    Server::builder()
+    server
         .add_service(fs_server)
         .serve(addr).await?;
     // tokio::spawn(
@@ -10,12 +10,82 @@ use crate::grpc::remotefs::{
     SetAttributesResult, StartTransactionArg, StartTransactionResult, UnlinkArg, UnlinkResult,
     UploadArg, UploadResult, WatchManyArg, WatchOnceArg, WatchOnceResult,
     GetAvailableSaslMechanismsResult, AuthenticateArg, AuthenticateResult,
+    FileSystemError, FileSystemErrorCode, LogoutArg, LogoutResult,
+    ReadRelationshipsArg, ReadRelationshipsResult, WriteRelationshipsArg,
+    WriteRelationshipsResult, ReadRolesArg, ReadRolesResult, WriteRolesArg, WriteRolesResult,
+    SearchArg, SearchResult, FileId, QueryArg, QueryResult, RequestedFileId, RequestedFileVersion,
 };
+use crate::authn::{get_bearer_token, InvalidSession, Session};
 use crate::{FileSystemServiceProvider, FileSystemService, Storage};
+use std::cmp::min;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// How many bytes each message of a `DownloadStream` carries at most.
+const STREAM_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// How many messages of a `DownloadStream` may be read ahead of the client.
+const STREAM_BUFFER_SIZE: usize = 4;
+
+impl FileSystemError {
+
+    pub fn not_permitted () -> Self {
+        FileSystemError {
+            code: FileSystemErrorCode::NotPermitted.into(),
+            lazy: false,
+        }
+    }
+
+    pub fn too_large () -> Self {
+        FileSystemError {
+            code: FileSystemErrorCode::TooLarge.into(),
+            lazy: false,
+        }
+    }
+
+    pub fn invalid_operand_type () -> Self {
+        FileSystemError {
+            code: FileSystemErrorCode::InvalidOperandType.into(),
+            lazy: false,
+        }
+    }
+
+    pub fn not_representable () -> Self {
+        FileSystemError {
+            code: FileSystemErrorCode::NotRepresentable.into(),
+            lazy: false,
+        }
+    }
+
+    pub fn hash_mismatch () -> Self {
+        FileSystemError {
+            code: FileSystemErrorCode::HashMismatch.into(),
+            lazy: false,
+        }
+    }
+
+}
+
+impl FileSystemServiceProvider {
+
+    /// Returns the `Session` that the `SessionInterceptor` resolved from the
+    /// bearer token of the request. Every RPC other than those used to
+    /// authenticate requires a session, even if it is only an anonymous one.
+    fn get_session<T> (&self, request: &tonic::Request<T>) -> Result<Session, tonic::Status> {
+        if request.extensions().get::<InvalidSession>().is_some() {
+            return Err(tonic::Status::unauthenticated("invalid or expired session token"));
+        }
+        request.extensions().get::<Session>()
+            .cloned()
+            .ok_or_else(|| tonic::Status::unauthenticated("not authenticated"))
+    }
+
+}
 
 #[tonic::async_trait]
 impl FileSystemService for FileSystemServiceProvider {
     type WatchManyStream = tonic::codec::Streaming<FileSystemEvent>;
+    type DownloadStreamStream = ReceiverStream<Result<DownloadResult, tonic::Status>>;
 
     async fn get_available_sasl_mechanisms(
         &self,
@@ -24,7 +94,12 @@ impl FileSystemService for FileSystemServiceProvider {
         tonic::Response<GetAvailableSaslMechanismsResult>,
         tonic::Status,
     > {
-        unimplemented!()
+        let authn = self.authn.lock().await;
+        let mechanisms = authn.get_auth_mechs().await
+            .into_iter()
+            .filter(|m| !m.do_not_advertise)
+            .collect();
+        Ok(tonic::Response::new(GetAvailableSaslMechanismsResult { mechanisms }))
     }
 
     async fn authenticate(
@@ -34,13 +109,56 @@ impl FileSystemService for FileSystemServiceProvider {
         tonic::Response<AuthenticateResult>,
         tonic::Status,
     > {
-        unimplemented!()
+        let peer = request.remote_addr()
+            .ok_or_else(|| tonic::Status::internal("could not determine peer address"))?;
+        let mechanism = request.get_ref().mechanism.clone();
+        let outcome = self.authn.lock().await
+            .attempt_authn(&request).await
+            .map_err(|_| tonic::Status::invalid_argument("malformed assertion"))?;
+        let mut result = outcome.result;
+        match outcome.session {
+            Some(mut session) => {
+                if session.clearances.is_empty() {
+                    if let Some(clearances) = session.user_id.as_ref().and_then(|u| self.config.clearances.get(u)) {
+                        session.clearances = clearances.clone();
+                    }
+                }
+                let (token, expires) = self.sessions.lock()
+                    .map_err(|_| tonic::Status::internal("session store poisoned"))?
+                    .create(session);
+                result.session_token = token;
+                result.valid_until = Some(crate::utils::chrono_to_grpc_timestamp(expires));
+                log::info!("Peer {} authenticated using {}", peer, mechanism);
+            },
+            None => if result.decision == Some(false) {
+                log::warn!("Peer {} failed to authenticate using {}", peer, mechanism);
+            },
+        };
+        Ok(tonic::Response::new(result))
+    }
+
+    async fn logout(
+        &self,
+        request: tonic::Request<LogoutArg>,
+    ) -> std::result::Result<tonic::Response<LogoutResult>, tonic::Status> {
+        let token = get_bearer_token(request.metadata())
+            .ok_or_else(|| tonic::Status::unauthenticated("no session token supplied"))?;
+        let ok = self.sessions.lock()
+            .map_err(|_| tonic::Status::internal("session store poisoned"))?
+            .remove(token);
+        Ok(tonic::Response::new(LogoutResult { ok }))
     }
 
     async fn watch_many(
         &self,
         request: tonic::Request<WatchManyArg>,
     ) -> std::result::Result<tonic::Response<Self::WatchManyStream>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_watch_many(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -48,6 +166,14 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<MakeDirectoryArg>,
     ) -> std::result::Result<tonic::Response<MakeDirectoryResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_make_directory(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(MakeDirectoryResult {
+                err: Some(FileSystemError::not_permitted()),
+            }));
+        }
         let store = self.storage.lock().await;
         store.make_directory(request).await
     }
@@ -56,6 +182,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<UploadArg>,
     ) -> std::result::Result<tonic::Response<UploadResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_upload(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(UploadResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.upload(request).await
     }
@@ -64,6 +199,14 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<AppendArg>,
     ) -> std::result::Result<tonic::Response<AppendResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_append(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(AppendResult {
+                err: Some(FileSystemError::not_permitted()),
+            }));
+        }
         let store = self.storage.lock().await;
         store.append(request).await
     }
@@ -72,21 +215,155 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<PatchArg>,
     ) -> std::result::Result<tonic::Response<PatchResult>, tonic::Status> {
-        unimplemented!()
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_patch(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(PatchResult {
+                err: Some(FileSystemError::not_permitted()),
+            }));
+        }
+        let store = self.storage.lock().await;
+        store.patch(request).await
     }
 
     async fn download(
         &self,
         request: tonic::Request<DownloadArg>,
     ) -> std::result::Result<tonic::Response<DownloadResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_download(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(DownloadResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.download(request).await
     }
 
+    /// Each message of the stream is written as its own `Upload`, continuing
+    /// the previous one, so a stream is authorized as an upload of its first
+    /// message, and the storage is only locked while a message is written.
+    async fn upload_stream(
+        &self,
+        request: tonic::Request<tonic::Streaming<UploadArg>>,
+    ) -> std::result::Result<tonic::Response<UploadResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let (metadata, extensions, mut stream) = request.into_parts();
+        let mut first = match stream.message().await? {
+            Some(first) => first,
+            None => return Err(tonic::Status::invalid_argument("stream is empty")),
+        };
+        if first.incomplete || first.reserve > 0 || first.part > 0 || !first.parts.is_empty() || !first.continuation.is_empty() {
+            return Err(tonic::Status::invalid_argument("streams cannot be reserved, continued, or uploaded in parts"));
+        }
+        let authorized = self.authz.lock().await
+            .is_authz_upload(&session, &tonic::Request::from_parts(metadata, extensions, first.clone())).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(UploadResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let mut data = std::mem::take(&mut first.data);
+        let mut continuation: Vec<u8> = Vec::new();
+        loop {
+            // The next message is awaited before this one is written, so that
+            // this one is known to be the last if there is none.
+            let next = stream.message().await?;
+            let chunk = UploadArg {
+                data,
+                incomplete: next.is_some(),
+                continuation,
+                ..first.clone()
+            };
+            let result = self.storage.lock().await
+                .upload(tonic::Request::new(chunk)).await?
+                .into_inner();
+            if result.err.is_some() {
+                return Ok(tonic::Response::new(result));
+            }
+            match next {
+                Some(next) => {
+                    data = next.data;
+                    continuation = result.continuation;
+                },
+                None => return Ok(tonic::Response::new(result)),
+            };
+        }
+    }
+
+    /// The file is read in chunks by `Download`, and the storage is only
+    /// locked while a chunk is read. The chunks are sent through a bounded
+    /// channel, so reading waits for a slow client.
+    async fn download_stream(
+        &self,
+        request: tonic::Request<DownloadArg>,
+    ) -> std::result::Result<tonic::Response<Self::DownloadStreamStream>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_download(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
+        let mut arg = request.into_inner();
+        let storage = self.storage.clone();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER_SIZE);
+        tokio::spawn(async move {
+            let mut remaining = arg.length;
+            loop {
+                let chunk = DownloadArg {
+                    length: if arg.length == 0 { STREAM_CHUNK_SIZE } else { min(remaining, STREAM_CHUNK_SIZE) },
+                    ..arg.clone()
+                };
+                let result = storage.lock().await
+                    .download(tonic::Request::new(chunk)).await
+                    .map(|r| r.into_inner());
+                let mut result = match result {
+                    Ok(result) if result.err.is_none() => result,
+                    other => {
+                        let _ = tx.send(other).await;
+                        return;
+                    },
+                };
+                // Every chunk after the first is read from the same version.
+                if let (Some(target), Some(version)) = (arg.target.as_mut(), result.version.as_ref()) {
+                    target.version = Some(RequestedFileVersion {
+                        major: version.major,
+                        minor: None,
+                    });
+                }
+                arg.offset += result.data.len() as u64;
+                remaining = remaining.saturating_sub(result.data.len() as u64);
+                let last = !result.more || (arg.length != 0 && remaining == 0);
+                if !last {
+                    result.hashes = None;
+                }
+                if tx.send(Ok(result)).await.is_err() || last {
+                    // Either the client has gone away, or everything was sent.
+                    return;
+                }
+            }
+        });
+        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+    }
+
     async fn delete(
         &self,
         request: tonic::Request<DeleteArg>,
     ) -> std::result::Result<tonic::Response<DeleteResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_delete(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(DeleteResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.delete(request).await
     }
@@ -95,6 +372,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<ListArg>,
     ) -> std::result::Result<tonic::Response<ListResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_list(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(ListResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.list(request).await
     }
@@ -103,6 +389,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<MoveArg>,
     ) -> std::result::Result<tonic::Response<MoveResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_move(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(MoveResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.r#move(request).await
     }
@@ -111,6 +406,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<CopyArg>,
     ) -> std::result::Result<tonic::Response<CopyResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_copy(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(CopyResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         let store = self.storage.lock().await;
         store.copy(request).await
     }
@@ -119,13 +423,58 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<ListIncompleteUploadsArg>,
     ) -> std::result::Result<tonic::Response<ListIncompleteUploadsResult>, tonic::Status> {
-        unimplemented!()
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_list_incomplete_uploads(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(ListIncompleteUploadsResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let mut response = {
+            let store = self.storage.lock().await;
+            store.list_incomplete_uploads(request).await?
+        };
+        // Continuations grant access to the uploads they identify, so uploads
+        // are only listed to those who could have begun them.
+        let authz = self.authz.lock().await;
+        let mut entries = Vec::with_capacity(response.get_ref().entries.len());
+        for entry in response.get_mut().entries.drain(..) {
+            let attrs = entry.attrs.clone().unwrap_or_default();
+            let upload = tonic::Request::new(UploadArg {
+                target: Some(FileId {
+                    path: entry.path.clone(),
+                    version: None,
+                }),
+                uid: attrs.uid.unwrap_or_default(),
+                gid: attrs.gid.unwrap_or_default(),
+                perms: attrs.perms,
+                reserve: entry.reserved,
+                ..Default::default()
+            });
+            if !authz.is_authz_upload(&session, &upload).await? {
+                continue;
+            }
+            entries.push(entry);
+        }
+        response.get_mut().entries = entries;
+        Ok(response)
     }
 
     async fn get_presigned_download(
         &self,
         request: tonic::Request<GetPresignedDownloadArg>,
     ) -> std::result::Result<tonic::Response<GetPresignedDownloadResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_get_presigned_download(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(GetPresignedDownloadResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         unimplemented!()
     }
 
@@ -133,6 +482,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<GetPresignedUploadArg>,
     ) -> std::result::Result<tonic::Response<GetPresignedUploadResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_get_presigned_upload(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(GetPresignedUploadResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         unimplemented!()
     }
 
@@ -140,6 +498,15 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<WatchOnceArg>,
     ) -> std::result::Result<tonic::Response<WatchOnceResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_watch_once(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(WatchOnceResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         unimplemented!()
     }
 
@@ -147,20 +514,49 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<GetAttributesArg>,
     ) -> std::result::Result<tonic::Response<GetAttributesResult>, tonic::Status> {
-        unimplemented!()
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_get_attributes(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(GetAttributesResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let store = self.storage.lock().await;
+        store.get_attributes(request).await
     }
 
     async fn set_attributes(
         &self,
         request: tonic::Request<SetAttributesArg>,
     ) -> std::result::Result<tonic::Response<SetAttributesResult>, tonic::Status> {
-        unimplemented!()
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_set_attributes(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(SetAttributesResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let store = self.storage.lock().await;
+        store.set_attributes(request).await
     }
 
     async fn delete_many(
         &self,
         request: tonic::Request<DeleteManyArg>,
     ) -> std::result::Result<tonic::Response<DeleteManyResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_delete_many(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(DeleteManyResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
         unimplemented!()
     }
 
@@ -168,6 +564,12 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<GetServiceInfoArg>,
     ) -> std::result::Result<tonic::Response<GetServiceInfoResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_get_service_info(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -175,6 +577,12 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<GetAuditTrailArg>,
     ) -> std::result::Result<tonic::Response<GetAuditTrailResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_get_audit_trail(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -182,6 +590,12 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<StartTransactionArg>,
     ) -> std::result::Result<tonic::Response<StartTransactionResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_start_transaction(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -189,6 +603,12 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<CommitTransactionArg>,
     ) -> std::result::Result<tonic::Response<CommitTransactionResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_commit_transaction(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -196,6 +616,12 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<AbortTransactionArg>,
     ) -> std::result::Result<tonic::Response<AbortTransactionResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_abort_transaction(&session, &request).await?;
+        if !authorized {
+            return Err(tonic::Status::permission_denied("not permitted"));
+        }
         unimplemented!()
     }
 
@@ -203,6 +629,14 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<CreateLinkArg>,
     ) -> std::result::Result<tonic::Response<CreateLinkResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_create_link(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(CreateLinkResult {
+                err: Some(FileSystemError::not_permitted()),
+            }));
+        }
         unimplemented!()
     }
 
@@ -210,6 +644,141 @@ impl FileSystemService for FileSystemServiceProvider {
         &self,
         request: tonic::Request<UnlinkArg>,
     ) -> std::result::Result<tonic::Response<UnlinkResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_unlink(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(UnlinkResult {
+                err: Some(FileSystemError::not_permitted()),
+            }));
+        }
         unimplemented!()
     }
+
+    async fn search(
+        &self,
+        request: tonic::Request<SearchArg>,
+    ) -> std::result::Result<tonic::Response<SearchResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_search(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(SearchResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let mut arg = request.into_inner();
+        let searches_text = !arg.text.is_empty();
+        // Results reveal the labels of objects, so they are only returned if
+        // the attributes of the object could be read directly. The snippets
+        // and scores of a full-text search reveal the contents of objects, so
+        // those results also require that the object could be downloaded.
+        // Results are dropped before a page is filled, so that only the last
+        // page is short, and the page is continued from where it left off.
+        let mut entries = Vec::new();
+        let mut page_size: Option<usize> = None;
+        let continuation = loop {
+            let page = {
+                let store = self.storage.lock().await;
+                store.search(tonic::Request::new(arg.clone())).await?.into_inner()
+            };
+            if page.err.is_some() {
+                return Ok(tonic::Response::new(page));
+            }
+            let page_size = *page_size.get_or_insert(page.entries.len());
+            let authz = self.authz.lock().await;
+            for entry in page.entries {
+                let get_attrs = tonic::Request::new(GetAttributesArg {
+                    target: Some(FileId {
+                        path: entry.path.clone(),
+                        version: None,
+                    }),
+                });
+                if !authz.is_authz_get_attributes(&session, &get_attrs).await? {
+                    continue;
+                }
+                if searches_text {
+                    let download = tonic::Request::new(DownloadArg {
+                        target: Some(RequestedFileId {
+                            path: entry.path.clone(),
+                            version: None,
+                        }),
+                        ..Default::default()
+                    });
+                    if !authz.is_authz_download(&session, &download).await? {
+                        continue;
+                    }
+                }
+                entries.push(entry);
+            }
+            if page.continuation.is_empty() || entries.len() >= page_size {
+                break page.continuation;
+            }
+            arg.limit = (page_size - entries.len()) as u32;
+            arg.continuation = page.continuation;
+        };
+        Ok(tonic::Response::new(SearchResult {
+            entries,
+            continuation,
+            ..Default::default()
+        }))
+    }
+
+    async fn query(
+        &self,
+        request: tonic::Request<QueryArg>,
+    ) -> std::result::Result<tonic::Response<QueryResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let authorized = self.authz.lock().await
+            .is_authz_query(&session, &request).await?;
+        if !authorized {
+            return Ok(tonic::Response::new(QueryResult {
+                err: Some(FileSystemError::not_permitted()),
+                ..Default::default()
+            }));
+        }
+        let store = self.storage.lock().await;
+        store.query(request).await
+    }
+
+    async fn write_relationships(
+        &self,
+        request: tonic::Request<WriteRelationshipsArg>,
+    ) -> std::result::Result<tonic::Response<WriteRelationshipsResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let result = self.authz.lock().await
+            .write_relationships(&session, &request).await?;
+        Ok(tonic::Response::new(result))
+    }
+
+    async fn read_relationships(
+        &self,
+        request: tonic::Request<ReadRelationshipsArg>,
+    ) -> std::result::Result<tonic::Response<ReadRelationshipsResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let result = self.authz.lock().await
+            .read_relationships(&session, &request).await?;
+        Ok(tonic::Response::new(result))
+    }
+
+    async fn write_roles(
+        &self,
+        request: tonic::Request<WriteRolesArg>,
+    ) -> std::result::Result<tonic::Response<WriteRolesResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let result = self.authz.lock().await
+            .write_roles(&session, &request).await?;
+        Ok(tonic::Response::new(result))
+    }
+
+    async fn read_roles(
+        &self,
+        request: tonic::Request<ReadRolesArg>,
+    ) -> std::result::Result<tonic::Response<ReadRolesResult>, tonic::Status> {
+        let session = self.get_session(&request)?;
+        let result = self.authz.lock().await
+            .read_roles(&session, &request).await?;
+        Ok(tonic::Response::new(result))
+    }
 }
@@ -10,22 +10,36 @@ use crate::grpc::remotefs::{
     MakeDirectoryResult, MoveArg, MoveResult, PatchArg, PatchResult, SetAttributesArg,
     SetAttributesResult, StartTransactionArg, StartTransactionResult, UnlinkArg, UnlinkResult,
     UploadArg, UploadResult, WatchManyArg, WatchOnceArg, WatchOnceResult, ListEntry,
-    FsAttributes, UnixPermissions, ObjectType
+    FsAttributes, UnixPermissions, ObjectType, SearchArg, SearchResult, SearchEntry,
+    AttributePredicate, BaseNameFilter, attribute_predicate, attribute_value,
+    QueryArg, QueryResult, FileSystemError, query_arg, DocumentFormat, Hashes,
+    IncompleteUpload,
 };
 use crate::storage::Storage;
+use crate::storage::fulltext;
+use crate::storage::fuzzy;
+use crate::storage::hashes::{self, ContentHasher};
+use crate::storage::query::{self, DEFAULT_QUERY_LIMIT, MAX_QUERY_LIMIT, MAX_QUERY_SIZE};
+use crate::storage::translate::{self, TranslationError, MAX_TRANSLATION_SIZE};
 use std::cmp::min;
+use std::collections::HashMap;
 use std::mem;
+use std::ops::Bound;
 use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::MetadataExt;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::fs::{self, read};
 use tokio::fs::{
     create_dir, create_dir_all, read_dir, read_link, rename, symlink, try_exists,
     File, OpenOptions, remove_dir_all, metadata, remove_file, copy
 };
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use bytemuck::Zeroable;
+use prost::Message;
+use sha2::{Digest, Sha512};
 use ulid::Ulid;
-use crate::utils::{system_time_to_grpc_timestamp, unix_perms_to_u16};
+use crate::utils::{system_time_to_grpc_timestamp, unix_perms_to_u16, u16_to_unix_perms};
 use redb::{Database, ReadOnlyTable, ReadableTable, Table, TableDefinition, WriteTransaction, Error as RedbError};
 use unicode_normalization::UnicodeNormalization;
 use crate::time64::{Time64, TIME64_UNKNOWN_TIME};
@@ -35,6 +49,14 @@ const SEQ_TABLE_NAME: &str = "seq";
 const VER_TABLE_NAME: &str = "ver";
 const STATS_TABLE_NAME: &str = "stats";
 const ATTRS_TABLE_NAME: &str = "attrs";
+const ATTR_INDEX_TABLE_NAME: &str = "attr_index";
+const ATTR_INDEX_BY_ID_TABLE_NAME: &str = "attr_index_by_id";
+const FS_KEYS_TABLE_NAME: &str = "fs_keys";
+const UPLOADS_TABLE_NAME: &str = "uploads";
+const RESERVATIONS_TABLE_NAME: &str = "reservations";
+const UPLOAD_RANGES_TABLE_NAME: &str = "upload_ranges";
+const UPLOAD_PARTS_TABLE_NAME: &str = "upload_parts";
+const META_TABLE_NAME: &str = "meta";
 
 // This is the table where file system objects are stored.
 const FS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new(FS_TABLE_NAME);
@@ -48,9 +70,50 @@ const VER_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new(VER_TABLE_
 // This is where user quotas, overall storage stats, etc. are stored.
 const STATS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new(STATS_TABLE_NAME);
 
-// This is where user quotas, overall storage stats, etc. are stored.
+// This is where arbitrary attributes of file system objects are stored, keyed
+// by `make_attr_key()`. Values are encoded `google.protobuf.Any` messages.
 const ATTRS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new(ATTRS_TABLE_NAME);
 
+// This is where the labels of the latest version of each object are indexed by
+// name, value, and the ID of the object, in that order, so that objects can be
+// searched for by label. Values are encoded by `encode_index_value()`.
+const ATTR_INDEX_TABLE: TableDefinition<(&str, &[u8], u64), ()> = TableDefinition::new(ATTR_INDEX_TABLE_NAME);
+
+// This is where the indexed labels of each object are stored, keyed by the ID
+// of the object and the name of the label, so that they can be tested and
+// removed from the index without reading the attrs table.
+const ATTR_INDEX_BY_ID_TABLE: TableDefinition<(u64, &str), &[u8]> = TableDefinition::new(ATTR_INDEX_BY_ID_TABLE_NAME);
+
+// This is where the key of each object in the fs table is stored, keyed by the
+// ID of the object, so that the path to an object can be found from its ID.
+const FS_KEYS_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new(FS_KEYS_TABLE_NAME);
+
+// This is where uploads that have not yet become a version of a file are
+// stored, keyed by the ULID of their blob, which is their continuation token.
+// Values are an `UploadRecordValue` followed by the path of the target.
+const UPLOADS_TABLE: TableDefinition<u128, &[u8]> = TableDefinition::new(UPLOADS_TABLE_NAME);
+
+// This is where the ULID of the upload that reserved each path is stored,
+// keyed by `make_upload_path_key()`, so that patches can find it.
+const RESERVATIONS_TABLE: TableDefinition<&[u8], u128> = TableDefinition::new(RESERVATIONS_TABLE_NAME);
+
+// This is where the ranges of reserved uploads that have been written are
+// stored, keyed by the ULID of the upload and the start of the range, with the
+// end of the range as the value. Adjacent ranges are merged.
+const UPLOAD_RANGES_TABLE: TableDefinition<(u128, u64), u64> = TableDefinition::new(UPLOAD_RANGES_TABLE_NAME);
+
+// This is where the numbered parts of uploads are stored, keyed by the ULID of
+// the upload and the number of the part. Values are `UploadPartValue`s.
+const UPLOAD_PARTS_TABLE: TableDefinition<(u128, u32), &[u8]> = TableDefinition::new(UPLOAD_PARTS_TABLE_NAME);
+
+// This is where facts about the layout of the database itself are stored, so
+// that databases written by older versions of the server can be migrated.
+const META_TABLE: TableDefinition<&str, u64> = TableDefinition::new(META_TABLE_NAME);
+
+// The key in the meta table of the size of the `VersionRecordValue`s that the
+// versions table was written with.
+const VERSION_RECORD_SIZE_KEY: &str = "version_record_size";
+
 const HEAD_FILE_NAME: &str = "_head";
 const BLOBS_DIR_NAME: &str = "_blobs";
 const LATEST_FILE_NAME: &str = "_latest";
@@ -65,6 +128,30 @@ const MINOR_VERSION_0: &str = "000000000000";
 /// limits that Tonic puts on message decoding sizes.
 const MAX_READ_SIZE: usize = 8 * 1024 * 1024;
 
+/// The highest part number of an upload, which is also the most parts that
+/// may be assembled into a file.
+const MAX_UPLOAD_PARTS: u32 = 10_000;
+
+/// The number of incomplete uploads listed if the client does not specify.
+const DEFAULT_UPLOADS_LIMIT: usize = 100;
+
+/// The most incomplete uploads that will be listed at once.
+const MAX_UPLOADS_LIMIT: usize = 1000;
+
+/// The number of search results returned if the client does not specify.
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+/// The most search results that will be returned at once.
+const MAX_SEARCH_LIMIT: usize = 1000;
+
+// These prefix encoded index values, so that values of different types never
+// compare equal, and those of each type are contiguous.
+const INDEX_TYPE_BOOLEAN: u8 = 1;
+const INDEX_TYPE_INTEGER: u8 = 2;
+const INDEX_TYPE_REAL: u8 = 3;
+const INDEX_TYPE_TEXT: u8 = 4;
+const INDEX_TYPE_TIME: u8 = 5;
+
 /**
  * Uses the upper 44 bits for seconds since the Unix Epoch.
  * Uses the lower 20 bits for nanoseconds.
@@ -188,7 +275,10 @@ impl FsRecordValue {
     pub fn obj_type (&self) -> Option<ObjectType> {
         let obj_type: u8 = self.r#type;
         match obj_type {
-            OBJ_TYPE_FILE => Some(ObjectType::File),
+            OBJ_TYPE_NORMAL_BLOB
+            | OBJ_TYPE_VERSION_BLOB
+            | OBJ_TYPE_APPEND_BLOB
+            | OBJ_TYPE_BLOCK_BLOB => Some(ObjectType::File),
             OBJ_TYPE_FOLDER => Some(ObjectType::Folder),
             OBJ_TYPE_SYMLINK => Some(ObjectType::Symlink),
             OBJ_TYPE_FIFO => Some(ObjectType::Fifo),
@@ -249,11 +339,104 @@ pub struct VersionRecordValue {
     pub storage_tier: u16,
     pub other: u32, // Reserved for future use + padding
 
+    /// The hashes of the first `length` bytes of the blob.
+    pub hashes: HashesValue,
+
     // TODO: [u8; N] = First N bytes of the file, which can be useful for searching file magic like "ELF" or "JFIF"
     // It must be at least 16 bytes, since bytes 6 through 9 are used for JPEG images.
     // 128 bytes or so might be long enough to store most titles and text previews
 }
 
+/// The hashes of the contents of a version, which are all zeroes if they were
+/// never computed, as is the case for folders.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct HashesValue {
+    pub sha2_512: [u8; 64],
+    pub sha3_512: [u8; 64],
+    pub xxh64: [u8; 8],
+}
+
+impl HashesValue {
+
+    /// Returns the hashes, unless they were never computed.
+    pub fn known (&self) -> Option<Hashes> {
+        if bytemuck::bytes_of(self).iter().all(|b| *b == 0) {
+            return None;
+        }
+        Some(Hashes {
+            sha2_512: self.sha2_512.to_vec(),
+            sha3_512: self.sha3_512.to_vec(),
+            xxh64: self.xxh64.to_vec(),
+        })
+    }
+
+}
+
+impl From<&Hashes> for HashesValue {
+
+    fn from (hashes: &Hashes) -> Self {
+        let mut value = HashesValue::zeroed();
+        value.sha2_512.copy_from_slice(&hashes.sha2_512);
+        value.sha3_512.copy_from_slice(&hashes.sha3_512);
+        value.xxh64.copy_from_slice(&hashes.xxh64);
+        value
+    }
+
+}
+
+/// An upload that has not yet become a version of a file, which is what the
+/// multipart upload flows call version "0.1."
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct UploadRecordValue {
+    /// The time at which the upload began.
+    pub create_time: Time64,
+
+    /// The time at which data was last written to the upload.
+    pub modify_time: Time64,
+
+    /// The number of bytes reserved, which will be the length of the file, or
+    /// `UNKNOWN_SIZE` if the upload is not reserved.
+    pub length: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub flags: u16, // Unix Permissions and other flags.
+    pub next: u8, // 1 if a new version is to be created if the file exists.
+    pub force: u8, // 1 if a new version is to be created even if it is unchanged.
+    pub other2: u32, // for future use + alignment.
+
+    // Variable length part: the path of the target, each component of which
+    // is followed by a null.
+}
+
+impl Default for UploadRecordValue {
+    fn default() -> Self {
+        UploadRecordValue {
+            create_time: Time64::now(),
+            modify_time: Time64::now(),
+            length: UNKNOWN_SIZE,
+            uid: 0,
+            gid: 0,
+            flags: 0o755,
+            next: 0,
+            force: 0,
+            other2: 0,
+        }
+    }
+}
+
+/// A numbered part of an upload, which is stored in its own blob until the
+/// parts are assembled.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct UploadPartValue {
+    /// The time at which the part was uploaded.
+    pub modify_time: Time64,
+    pub length: u64,
+    pub sha2_512: [u8; 64],
+}
+
 fn get_next_id (w: &WriteTransaction<'_>, table_name: &str) -> std::result::Result<FileSystemId, RedbError> {
     let mut seq_writer = w.open_table(SEQ_TABLE)?;
     let last_id = seq_writer.get(table_name)?
@@ -271,6 +454,387 @@ fn make_key (parent_id: FileSystemId, file_name: &str) -> Vec<u8> {
     ].concat()
 }
 
+fn encode_upload_path (path: &[String]) -> Vec<u8> {
+    path.iter()
+        .flat_map(|pc| pc.as_bytes().iter().copied().chain([0]))
+        .collect()
+}
+
+fn decode_upload_path (encoded: &[u8]) -> Vec<String> {
+    encoded.split(|b| *b == 0)
+        .map(|pc| String::from_utf8_lossy(pc).into_owned())
+        .filter(|pc| !pc.is_empty())
+        .collect()
+}
+
+/// Normalizes a path the way that objects are named: every component is in
+/// Unicode normalization form KD, and the last one, which names the target, is
+/// trimmed of whitespace. Paths must be normalized before being looked up, so
+/// that the authorizers and the storage always resolve the same object.
+pub fn normalize_path (path: &[String]) -> Vec<String> {
+    path.iter()
+        .enumerate()
+        .map(|(i, pc)| {
+            let pc = if i + 1 == path.len() { pc.trim() } else { pc.as_str() };
+            pc.nfkd().collect::<String>()
+        })
+        .collect()
+}
+
+/// Normalizes a path, as `normalize_path()` does, and checks that each of its
+/// components could name an object. Components may not be empty, nor contain
+/// '/' or NUL, since the authorizers join components with '/', and a path that
+/// could be split differently could be authorized as a different object.
+pub fn validate_path (path: &[String]) -> std::result::Result<Vec<String>, tonic::Status> {
+    let path = normalize_path(path);
+    if path.iter().any(|pc| pc.is_empty() || pc.contains(['/', '\0'])) {
+        return Err(tonic::Status::invalid_argument("invalid path component"));
+    }
+    Ok(path)
+}
+
+/// Returns the key of a path in the reservations table, which is normalized
+/// the same way as the keys of the fs table.
+fn make_upload_path_key (path: &[String]) -> Vec<u8> {
+    encode_upload_path(&normalize_path(path))
+}
+
+fn upload_record_from_bytes (bytes: &[u8]) -> std::result::Result<(UploadRecordValue, Vec<String>), tonic::Status> {
+    if bytes.len() < mem::size_of::<UploadRecordValue>() {
+        return Err(tonic::Status::internal("corrupted upload record"));
+    }
+    let (record, path) = bytes.split_at(mem::size_of::<UploadRecordValue>());
+    Ok((bytemuck::pod_read_unaligned(record), decode_upload_path(path)))
+}
+
+/// Records that a range of a reserved upload has been written, merging it with
+/// the ranges next to it, or returns `false` if it overlaps a range that was
+/// written already.
+fn add_upload_range (w: &WriteTransaction<'_>, ulid: u128, start: u64, end: u64) -> std::result::Result<bool, tonic::Status> {
+    let mut ranges = w.open_table(UPLOAD_RANGES_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?;
+    // Ranges never overlap each other, so the only one that could overlap this
+    // one is the last that starts before it ends.
+    let before = ranges.range((ulid, 0)..(ulid, end))
+        .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?
+        .next_back()
+        .transpose()
+        .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?
+        .map(|(k, v)| (k.value().1, v.value()));
+    let mut merged_start = start;
+    let mut merged_end = end;
+    if let Some((before_start, before_end)) = before {
+        if before_end > start {
+            return Ok(false);
+        }
+        if before_end == start {
+            ranges.remove((ulid, before_start))
+                .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?;
+            merged_start = before_start;
+        }
+    }
+    let after_end = ranges.remove((ulid, end))
+        .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?
+        .map(|v| v.value());
+    if let Some(after_end) = after_end {
+        merged_end = after_end;
+    }
+    ranges.insert((ulid, merged_start), merged_end)
+        .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?;
+    Ok(true)
+}
+
+/// Decodes a continuation token, which is the ULID of the blob of an upload.
+fn decode_continuation (continuation: &[u8]) -> std::result::Result<Ulid, tonic::Status> {
+    let a: [u8; 16] = continuation
+        .try_into()
+        .map_err(|_| tonic::Status::invalid_argument("invalid continuation token"))?;
+    Ok(Ulid::from(a))
+}
+
+/// Reads the record of an upload that is being continued to `path`, which may
+/// not be a reserved upload, since those are only written by patching.
+fn find_continued_upload <'a, R> (ulid: u128, path: &[String], uploads: &'a R) -> std::result::Result<UploadRecordValue, tonic::Status>
+    where R: ReadableTable<u128, &'static [u8]> {
+    let maybe_upload = uploads.get(ulid)
+        .map_err(|_| tonic::Status::internal("could not read from uploads table"))?
+        .map(|v| upload_record_from_bytes(v.value()))
+        .transpose()?;
+    let (record, upload_path) = match maybe_upload {
+        Some(found) => found,
+        None => return Err(tonic::Status::invalid_argument("no such upload")),
+    };
+    if make_upload_path_key(&upload_path) != make_upload_path_key(path) {
+        return Err(tonic::Status::invalid_argument("continuation is for a different target"));
+    }
+    if record.length != UNKNOWN_SIZE {
+        return Err(tonic::Status::invalid_argument("reserved uploads may only be written by patching"));
+    }
+    Ok(record)
+}
+
+fn put_upload (w: &WriteTransaction<'_>, ulid: u128, record: &UploadRecordValue, path: &[String]) -> std::result::Result<(), tonic::Status> {
+    let record_bytes = [
+        bytemuck::bytes_of(record),
+        encode_upload_path(path).as_slice(),
+    ].concat();
+    let mut uploads = w.open_table(UPLOADS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to uploads table"))?;
+    uploads.insert(ulid, record_bytes.as_slice())
+        .map_err(|_| tonic::Status::internal("could not write to uploads table"))?;
+    Ok(())
+}
+
+/// Removes the records of an upload, but not its blobs. If the upload existed,
+/// the numbers of its parts are returned, so their blobs can be removed.
+fn remove_upload (w: &WriteTransaction<'_>, ulid: u128) -> std::result::Result<Option<Vec<u32>>, tonic::Status> {
+    let mut uploads = w.open_table(UPLOADS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to uploads table"))?;
+    let removed = uploads.remove(ulid)
+        .map_err(|_| tonic::Status::internal("could not write to uploads table"))?
+        .map(|v| upload_record_from_bytes(v.value()))
+        .transpose()?;
+    let (record, path) = match removed {
+        Some(removed) => removed,
+        None => return Ok(None),
+    };
+    if record.length != UNKNOWN_SIZE {
+        let mut reservations = w.open_table(RESERVATIONS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to reservations table"))?;
+        reservations.remove(make_upload_path_key(&path).as_slice())
+            .map_err(|_| tonic::Status::internal("could not write to reservations table"))?;
+        let mut ranges = w.open_table(UPLOAD_RANGES_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?;
+        let starts = ranges.range((ulid, 0)..=(ulid, u64::MAX))
+            .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?
+            .map(|entry| entry.map(|(k, _)| k.value().1))
+            .collect::<std::result::Result<Vec<u64>, _>>()
+            .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?;
+        for start in starts {
+            ranges.remove((ulid, start))
+                .map_err(|_| tonic::Status::internal("could not write to upload ranges table"))?;
+        }
+    }
+    let mut upload_parts = w.open_table(UPLOAD_PARTS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to upload parts table"))?;
+    let parts = upload_parts.range((ulid, 0)..=(ulid, u32::MAX))
+        .map_err(|_| tonic::Status::internal("could not read from upload parts table"))?
+        .map(|entry| entry.map(|(k, _)| k.value().1))
+        .collect::<std::result::Result<Vec<u32>, _>>()
+        .map_err(|_| tonic::Status::internal("could not read from upload parts table"))?;
+    for number in parts.iter() {
+        upload_parts.remove((ulid, *number))
+            .map_err(|_| tonic::Status::internal("could not write to upload parts table"))?;
+    }
+    Ok(Some(parts))
+}
+
+/// Returns whether every byte of a reserved upload of `length` bytes has been
+/// written, which is when its ranges have been merged into one.
+fn is_upload_filled (w: &WriteTransaction<'_>, ulid: u128, length: u64) -> std::result::Result<bool, tonic::Status> {
+    if length == 0 {
+        return Ok(true);
+    }
+    let ranges = w.open_table(UPLOAD_RANGES_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?;
+    let filled = ranges.get((ulid, 0))
+        .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?
+        .is_some_and(|end| end.value() == length);
+    Ok(filled)
+}
+
+/// The contents, ownership, and permissions of a new version of a file.
+struct NewVersion {
+    ulid: Ulid,
+    blob_path: PathBuf,
+    length: u64,
+    uid: u32,
+    gid: u32,
+    flags: u16,
+    hashes: Hashes,
+
+    /// Whether a version is written even if the latest version of the file
+    /// has the same contents.
+    force: bool,
+}
+
+/// The version that a blob was recorded as.
+struct WrittenVersion {
+    version: FileVersion,
+
+    /// The blob was not recorded, because the latest version of the file
+    /// already has the same contents, so the blob should be deleted, and
+    /// `version` is that latest version.
+    unchanged: bool,
+}
+
+/// Records a blob as the next version of the file named `file_name` in the
+/// folder at `dir_path`, creating the file if it does not exist, or failing if
+/// it does and `next` is false. Unless `force` is set, no version is written
+/// if the latest version of the file has the same hashes, and that version
+/// takes the ownership and permissions of the new one instead.
+async fn write_version (
+    w: &WriteTransaction<'_>,
+    dir_path: &[String],
+    file_name: &str,
+    next: bool,
+    new_version: NewVersion,
+) -> std::result::Result<WrittenVersion, tonic::Status> {
+    let mut fs_writer = w.open_table(FS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+    let parent_rec = if dir_path.is_empty() {
+        None
+    } else {
+        match lookup_path(dir_path, &fs_writer)? {
+            Some((_, value)) => Some(fs_record_from_bytes(&value)),
+            None => return Err(tonic::Status::invalid_argument("no such parent path")),
+        }
+    };
+    if parent_rec.is_some_and(|p| p.r#type != OBJ_TYPE_FOLDER) {
+        return Err(tonic::Status::invalid_argument("cannot place under non-folder"));
+    }
+    let parent_id = parent_rec.as_ref().map(children_id).unwrap_or(ROOT_FSID);
+    let key = make_key(parent_id, file_name);
+    let mut existing_file_id: Option<FileSystemId> = None;
+    let mut create_time: Option<Time64> = None;
+    let mut access_time: Option<Time64> = None;
+    let mut latest_version: Option<FsVersion> = None;
+    {
+        let maybe_existing = fs_writer.get(key.as_slice())
+            .map_err(|_| tonic::Status::internal("error trying to check if file exists already"))?;
+        if let Some(existing) = maybe_existing {
+            let prev_fs_record = fs_record_from_bytes(existing.value());
+            // Only files can have another version.
+            if !next || prev_fs_record.r#type != OBJ_TYPE_VERSION_BLOB {
+                return Err(tonic::Status::invalid_argument("object already exists with that name"));
+            }
+            existing_file_id = Some(prev_fs_record.id);
+            create_time = Some(prev_fs_record.create_time);
+            access_time = Some(prev_fs_record.access_time);
+            latest_version = Some(prev_fs_record.latest_version);
+        }
+    }
+    if let (Some(file_id), Some(version), false) = (existing_file_id, latest_version, new_version.force) {
+        let mut v = w.open_table(VER_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+        let head = get_version_record(&FsRecordValue {
+            id: file_id,
+            latest_version: version,
+            ..Default::default()
+        }, &v)?;
+        let unchanged_head = head.filter(|head| head.length == new_version.length
+            && head.hashes.known().as_ref() == Some(&new_version.hashes));
+        if let Some(mut head) = unchanged_head {
+            // The ownership and permissions that a new version would have had
+            // are applied to the latest version instead.
+            if (head.uid, head.gid, head.flags) != (new_version.uid, new_version.gid, new_version.flags) {
+                head.uid = new_version.uid;
+                head.gid = new_version.gid;
+                head.flags = new_version.flags;
+                let version_key = VersionRecordKey {
+                    file_id,
+                    version,
+                };
+                let mut version_value = v.get(bytemuck::bytes_of(&version_key))
+                    .map_err(|_| tonic::Status::internal("could not read from versions table"))?
+                    .map(|v| v.value().to_vec())
+                    .ok_or_else(|| tonic::Status::internal("database corrupted: missing version"))?;
+                // The variable-length part of the record is left as it was.
+                version_value[0..mem::size_of::<VersionRecordValue>()].copy_from_slice(bytemuck::bytes_of(&head));
+                v.insert(bytemuck::bytes_of(&version_key), version_value.as_slice())
+                    .map_err(|_| tonic::Status::internal("could not write to versions table"))?;
+                let mut value = fs_writer.get(key.as_slice())
+                    .map_err(|_| tonic::Status::internal("could not read from fs table"))?
+                    .map(|v| v.value().to_vec())
+                    .ok_or_else(|| tonic::Status::internal("database corrupted: missing file"))?;
+                let mut record = fs_record_from_bytes(&value);
+                record.change_time = Time64::now();
+                value[0..mem::size_of::<FsRecordValue>()].copy_from_slice(bytemuck::bytes_of(&record));
+                fs_writer.insert(key.as_slice(), value.as_slice())
+                    .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+            }
+            return Ok(WrittenVersion {
+                version: FileVersion {
+                    major: version,
+                    minor: 0,
+                },
+                unchanged: true,
+            });
+        }
+    }
+
+    let file_id = if let Some(fid) = existing_file_id {
+        fid
+    } else {
+        get_next_id(w, FS_TABLE_NAME)
+            .map_err(|_| tonic::Status::internal("could not read and/or increment sequence for FS table"))?
+    };
+
+    let current_version = latest_version.map(|v| v + 1).unwrap_or(1);
+    let new_file_record = FsRecordValue {
+        id: file_id,
+        r#type: OBJ_TYPE_VERSION_BLOB, // FIXME: Allow creating different file types.
+        create_time: create_time.unwrap_or(Time64::now()),
+        modify_time: Time64::now(),
+        access_time: access_time.unwrap_or(Time64::now()),
+        change_time: TIME64_UNKNOWN_TIME,
+        delete_time: TIME64_UNKNOWN_TIME,
+        latest_version: current_version,
+        blob_ulid: new_version.ulid.0,
+        ..Default::default()
+    };
+    let new_value = [
+        bytemuck::bytes_of(&new_file_record),
+        file_name.as_bytes(),
+    ].concat();
+    fs_writer.insert(key.as_slice(), new_value.as_slice())
+        .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+    drop(fs_writer);
+
+    let mut v_writer = w.open_table(VER_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+    // New files in a setgid folder belong to the group of the folder.
+    let inherited_gid = if existing_file_id.is_none() {
+        get_inherited_gid(parent_rec.as_ref(), &v_writer)?
+    } else {
+        None
+    };
+    let new_file_version = VersionRecordValue {
+        create_time: Time64::now(),
+        access_time: TIME64_UNKNOWN_TIME,
+        uid: new_version.uid,
+        gid: inherited_gid.unwrap_or(new_version.gid),
+        flags: new_version.flags,
+        storage_tier: DEFAULT_STORAGE_TIER,
+        length: new_version.length,
+        other: 0,
+        hashes: HashesValue::from(&new_version.hashes),
+    };
+    let version_key = VersionRecordKey {
+        file_id,
+        version: current_version,
+    };
+    let version_value_bytes = [
+        bytemuck::bytes_of(&new_file_version),
+        new_version.blob_path.to_str().unwrap().as_bytes(),
+    ].concat();
+    v_writer.insert(bytemuck::bytes_of(&version_key), version_value_bytes.as_slice())
+        .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+    drop(v_writer);
+    set_fs_key(w, file_id, &key)?;
+    // The labels of the previous version no longer apply.
+    index_attrs_of(w, file_id, current_version)?;
+    let text = read_blob_text(file_name, &new_version.blob_path).await?;
+    fulltext::index_document(w, file_id, text.as_deref())?;
+    Ok(WrittenVersion {
+        version: FileVersion {
+            major: current_version,
+            minor: 0,
+        },
+        unchanged: false,
+    })
+}
+
 fn is_readable_obj_type (obj_type: u8) -> bool {
     [
         OBJ_TYPE_NORMAL_BLOB,
@@ -345,11 +909,644 @@ fn version_value_from_bytes (value: &[u8]) -> VersionRecordValue {
     bytemuck::pod_read_unaligned(&value[0..mem::size_of::<VersionRecordValue>()])
 }
 
+/// Reads the record of the latest version of an object, which, for folders,
+/// only holds the ownership and permissions.
+fn get_version_record <'a, R> (record: &FsRecordValue, v: &'a R) -> std::result::Result<Option<VersionRecordValue>, tonic::Status>
+    where R: ReadableTable<&'static [u8], &'static [u8]> {
+    let version_key = VersionRecordKey {
+        file_id: record.id,
+        version: record.latest_version,
+    };
+    let maybe_value = v.get(bytemuck::bytes_of(&version_key))
+        .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+    match maybe_value {
+        Some(value) if value.value().len() < mem::size_of::<VersionRecordValue>() => {
+            Err(tonic::Status::internal("corrupted version record"))
+        },
+        Some(value) => Ok(Some(version_value_from_bytes(value.value()))),
+        None => Ok(None),
+    }
+}
+
+/// Returns the name of the blob of the latest version of a file, if it has one.
+fn get_version_blob_name <'a, R> (record: &FsRecordValue, v: &'a R) -> std::result::Result<Option<String>, tonic::Status>
+    where R: ReadableTable<&'static [u8], &'static [u8]> {
+    let version_key = VersionRecordKey {
+        file_id: record.id,
+        version: record.latest_version,
+    };
+    let maybe_value = v.get(bytemuck::bytes_of(&version_key))
+        .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+    match maybe_value {
+        Some(value) if value.value().len() <= mem::size_of::<VersionRecordValue>() => Ok(None),
+        Some(value) => {
+            let blob_name = std::str::from_utf8(&value.value()[mem::size_of::<VersionRecordValue>()..])
+                .map_err(|_| tonic::Status::internal("corrupted version record"))?;
+            Ok(Some(blob_name.to_owned()))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the group that new objects within the given folder must belong to,
+/// which is only the case if the folder has the setgid bit set. `None` means
+/// the root folder, which has no record.
+fn get_inherited_gid <'a, R> (parent: Option<&FsRecordValue>, v: &'a R) -> std::result::Result<Option<u32>, tonic::Status>
+    where R: ReadableTable<&'static [u8], &'static [u8]> {
+    let parent = match parent {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    Ok(get_version_record(parent, v)?
+        .filter(|ver| ver.flags & UNIX_PERM_SETGID > 0)
+        .map(|ver| ver.gid))
+}
+
+/// The ownership and permissions of a file system object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjectPermissions {
+    pub id: FileSystemId,
+    pub r#type: u8,
+    pub uid: u32,
+    pub gid: u32,
+    /// Unix permissions, including the setuid, setgid, and sticky bits.
+    pub flags: u16,
+}
+
+/// Looks up the ownership and permissions of each object along `path`, not
+/// including the root folder. This stops early at the first object that does
+/// not exist, or after the first object that is not a folder.
+///
+/// Objects that have no version record, such as folders created before
+/// ownership was recorded, are treated as owned by root with mode 0755.
+pub fn get_path_permissions (db: &Database, path: &[String]) -> std::result::Result<Vec<ObjectPermissions>, tonic::Status> {
+    let r = db.begin_read()
+        .map_err(|_| tonic::Status::internal("could not read from database"))?;
+    let fs = r.open_table(FS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+    let v = r.open_table(VER_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+    let mut parent_id: FileSystemId = ROOT_FSID;
+    let mut ret: Vec<ObjectPermissions> = Vec::with_capacity(path.len());
+    for pc in validate_path(path)?.iter() {
+        if ret.last().is_some_and(|p| p.r#type != OBJ_TYPE_FOLDER) {
+            break;
+        }
+        let key = make_key(parent_id, pc);
+        let maybe_value = fs.get(key.as_slice())
+            .map_err(|_| tonic::Status::internal("error trying to read fs key"))?;
+        let value = match maybe_value {
+            Some(value) => value,
+            None => break,
+        };
+        let value = value.value();
+        if value.len() < mem::size_of::<FsRecordValue>() {
+            return Err(tonic::Status::internal("corrupted fs record"));
+        }
+        parent_id = u64::from_be_bytes([
+            value[0], value[1], value[2], value[3],
+            value[4], value[5], value[6], value[7],
+        ]);
+        let record = fs_record_from_bytes(value);
+        let version = get_version_record(&record, &v)?;
+        ret.push(ObjectPermissions {
+            id: record.id,
+            r#type: record.r#type,
+            uid: version.map(|ver| ver.uid).unwrap_or(0),
+            gid: version.map(|ver| ver.gid).unwrap_or(0),
+            flags: version.map(|ver| ver.flags).unwrap_or(0o755),
+        });
+    }
+    Ok(ret)
+}
+
+/// Attributes are keyed by the ID of the object in hexadecimal, a slash, and
+/// the name of the attribute, so that those of an object are contiguous.
+pub fn make_attr_key (id: FileSystemId, name: &str) -> String {
+    format!("{:016x}/{}", id, name)
+}
+
+/// Attributes of a specific version are keyed like those of the object, but
+/// with an `@` and the version in hexadecimal after the ID of the object.
+pub fn make_version_attr_key (id: FileSystemId, version: FsVersion, name: &str) -> String {
+    format!("{:016x}@{:016x}/{}", id, version, name)
+}
+
+/// Reads the attributes whose keys start with `prefix`, which must end with a
+/// slash, keyed by the remainder of the key.
+fn get_attrs_with_prefix <'a, R> (prefix: &str, attrs: &'a R) -> std::result::Result<HashMap<String, prost_types::Any>, tonic::Status>
+    where R: ReadableTable<&'static str, &'static [u8]> {
+    // '0' is the character after '/', so this is the end of the attributes with this prefix.
+    let end = format!("{}0", &prefix[..prefix.len() - 1]);
+    let iter = attrs.range(prefix..end.as_str())
+        .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+    let mut ret: HashMap<String, prost_types::Any> = HashMap::new();
+    for entry in iter {
+        let (k, v) = entry.map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+        let any = prost_types::Any::decode(v.value())
+            .map_err(|_| tonic::Status::internal("corrupted attribute"))?;
+        ret.insert(k.value()[prefix.len()..].to_string(), any);
+    }
+    Ok(ret)
+}
+
+fn get_attrs_of <'a, R> (id: FileSystemId, attrs: &'a R) -> std::result::Result<HashMap<String, prost_types::Any>, tonic::Status>
+    where R: ReadableTable<&'static str, &'static [u8]> {
+    get_attrs_with_prefix(&make_attr_key(id, ""), attrs)
+}
+
+/// Reads the attributes of an object as of a version, which are those of the
+/// object, overridden by those of the version.
+fn get_version_attrs_of <'a, R> (id: FileSystemId, version: FsVersion, attrs: &'a R) -> std::result::Result<HashMap<String, prost_types::Any>, tonic::Status>
+    where R: ReadableTable<&'static str, &'static [u8]> {
+    let mut ret = get_attrs_of(id, attrs)?;
+    ret.extend(get_attrs_with_prefix(&make_version_attr_key(id, version, ""), attrs)?);
+    Ok(ret)
+}
+
+/// Deletes all attributes of an object, including those of its versions.
+fn delete_attrs_of (id: FileSystemId, attrs: &mut Table<&'static str, &'static [u8]>) -> std::result::Result<(), tonic::Status> {
+    let start = format!("{:016x}", id);
+    // IDs are always 16 digits, and 'A' is after both '/' and '@'.
+    let end = format!("{:016x}A", id);
+    let keys = attrs.range(start.as_str()..end.as_str())
+        .map_err(|_| tonic::Status::internal("could not read from attrs table"))?
+        .map(|entry| entry.map(|(k, _)| k.value().to_string()))
+        .collect::<std::result::Result<Vec<String>, _>>()
+        .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+    for key in keys {
+        attrs.remove(key.as_str())
+            .map_err(|_| tonic::Status::internal("could not delete attribute"))?;
+    }
+    Ok(())
+}
+
+/// Copies the attributes of an object, but not those of its versions, to
+/// another object.
+fn copy_attrs_of (src_id: FileSystemId, dest_id: FileSystemId, attrs: &mut Table<&'static str, &'static [u8]>) -> std::result::Result<(), tonic::Status> {
+    let src_attrs = get_attrs_of(src_id, attrs)?;
+    for (name, value) in src_attrs {
+        attrs.insert(make_attr_key(dest_id, &name).as_str(), value.encode_to_vec().as_slice())
+            .map_err(|_| tonic::Status::internal("could not write to attrs table"))?;
+    }
+    Ok(())
+}
+
+/// Interprets a label as a value that can be indexed, if it is of a wrapper
+/// type or a timestamp. Unsigned integers that do not fit in an `int64` are not.
+fn any_to_index_value (any: &prost_types::Any) -> Option<attribute_value::Value> {
+    use attribute_value::Value;
+    let type_name = any.type_url.rsplit('/').next().unwrap_or_default();
+    let bytes = any.value.as_slice();
+    match type_name {
+        "google.protobuf.BoolValue" => bool::decode(bytes).ok().map(Value::Boolean),
+        "google.protobuf.Int32Value" => i32::decode(bytes).ok().map(|i| Value::Integer(i.into())),
+        "google.protobuf.Int64Value" => i64::decode(bytes).ok().map(Value::Integer),
+        "google.protobuf.UInt32Value" => u32::decode(bytes).ok().map(|i| Value::Integer(i.into())),
+        "google.protobuf.UInt64Value" => u64::decode(bytes).ok()
+            .and_then(|i| i64::try_from(i).ok())
+            .map(Value::Integer),
+        "google.protobuf.FloatValue" => f32::decode(bytes).ok().map(|f| Value::Real(f.into())),
+        "google.protobuf.DoubleValue" => f64::decode(bytes).ok().map(Value::Real),
+        "google.protobuf.StringValue" => String::decode(bytes).ok().map(Value::Text),
+        "google.protobuf.Timestamp" => prost_types::Timestamp::decode(bytes).ok().map(Value::Time),
+        _ => None,
+    }
+}
+
+/// Encodes a value as its type followed by bytes that sort in the same order
+/// as the values of that type. NaN cannot be encoded, since it has no order.
+fn encode_index_value (value: &attribute_value::Value) -> Option<Vec<u8>> {
+    use attribute_value::Value;
+    const SIGN_BIT: u64 = 1 << 63;
+    let ret = match value {
+        Value::Boolean(b) => vec![INDEX_TYPE_BOOLEAN, *b as u8],
+        Value::Integer(i) => [
+            [INDEX_TYPE_INTEGER].as_slice(),
+            ((*i as u64) ^ SIGN_BIT).to_be_bytes().as_slice(),
+        ].concat(),
+        Value::Real(r) => {
+            if r.is_nan() {
+                return None;
+            }
+            // Negative zero is equal to zero, so they are encoded the same way.
+            let bits = if *r == 0.0 { 0 } else { r.to_bits() };
+            // Negative numbers are flipped entirely, so that larger magnitudes
+            // sort first, and positive numbers sort after all of them.
+            let bits = if bits & SIGN_BIT > 0 { !bits } else { bits ^ SIGN_BIT };
+            [
+                [INDEX_TYPE_REAL].as_slice(),
+                bits.to_be_bytes().as_slice(),
+            ].concat()
+        },
+        Value::Text(t) => [
+            [INDEX_TYPE_TEXT].as_slice(),
+            t.as_bytes(),
+        ].concat(),
+        Value::Time(t) => [
+            [INDEX_TYPE_TIME].as_slice(),
+            ((t.seconds as u64) ^ SIGN_BIT).to_be_bytes().as_slice(),
+            (t.nanos.max(0) as u32).to_be_bytes().as_slice(),
+        ].concat(),
+    };
+    Some(ret)
+}
+
+/// Returns the least byte string that is greater than all byte strings that
+/// start with `prefix`, which must not consist entirely of `0xFF` bytes.
+fn prefix_successor (prefix: &[u8]) -> Vec<u8> {
+    let mut ret = prefix.to_vec();
+    while ret.last() == Some(&0xFF) {
+        ret.pop();
+    }
+    if let Some(last) = ret.last_mut() {
+        *last += 1;
+    }
+    ret
+}
+
+/// A search predicate, as bounds on the encoded values of a label.
+struct IndexPredicate {
+    name: String,
+    lower: Bound<Vec<u8>>,
+    upper: Bound<Vec<u8>>,
+}
+
+impl IndexPredicate {
+
+    fn compile (predicate: &AttributePredicate) -> std::result::Result<Self, tonic::Status> {
+        use attribute_predicate::Test;
+        if predicate.name.is_empty() {
+            return Err(tonic::Status::invalid_argument("predicates must name a label"));
+        }
+        let encode = |value: &crate::grpc::remotefs::AttributeValue| value.value.as_ref()
+            .and_then(encode_index_value)
+            .ok_or_else(|| tonic::Status::invalid_argument("invalid predicate value"));
+        let (lower, upper) = match predicate.test.as_ref() {
+            Some(Test::Equals(value)) => {
+                let value = encode(value)?;
+                (Bound::Included(value.clone()), Bound::Included(value))
+            },
+            Some(Test::Range(range)) => {
+                let lower = range.lower.as_ref().map(encode).transpose()?;
+                let upper = range.upper.as_ref().map(encode).transpose()?;
+                let value_type = match (lower.as_ref(), upper.as_ref()) {
+                    (Some(l), Some(u)) if l[0] != u[0] => {
+                        return Err(tonic::Status::invalid_argument("range bounds must be of the same type"));
+                    },
+                    (Some(l), _) => l[0],
+                    (None, Some(u)) => u[0],
+                    (None, None) => return Err(tonic::Status::invalid_argument("ranges must have at least one bound")),
+                };
+                // An absent bound is the start or end of the values of the type.
+                let lower = match lower {
+                    Some(l) if range.lower_exclusive => Bound::Excluded(l),
+                    Some(l) => Bound::Included(l),
+                    None => Bound::Included(vec![value_type]),
+                };
+                let upper = match upper {
+                    Some(u) if range.upper_inclusive => Bound::Included(u),
+                    Some(u) => Bound::Excluded(u),
+                    None => Bound::Excluded(vec![value_type + 1]),
+                };
+                (lower, upper)
+            },
+            Some(Test::Prefix(prefix)) => {
+                let lower = [
+                    [INDEX_TYPE_TEXT].as_slice(),
+                    prefix.as_bytes(),
+                ].concat();
+                let upper = prefix_successor(&lower);
+                (Bound::Included(lower), Bound::Excluded(upper))
+            },
+            None => return Err(tonic::Status::invalid_argument("predicates must have a test")),
+        };
+        Ok(IndexPredicate {
+            name: predicate.name.clone(),
+            lower,
+            upper,
+        })
+    }
+
+    /// The encoded value from which to start scanning the index.
+    fn start (&self) -> &[u8] {
+        match &self.lower {
+            Bound::Included(l) | Bound::Excluded(l) => l,
+            Bound::Unbounded => &[],
+        }
+    }
+
+    fn is_below (&self, value: &[u8]) -> bool {
+        match &self.lower {
+            Bound::Included(l) => value < l.as_slice(),
+            Bound::Excluded(l) => value <= l.as_slice(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn is_above (&self, value: &[u8]) -> bool {
+        match &self.upper {
+            Bound::Included(u) => value > u.as_slice(),
+            Bound::Excluded(u) => value >= u.as_slice(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn matches (&self, value: &[u8]) -> bool {
+        !self.is_below(value) && !self.is_above(value)
+    }
+
+}
+
+fn matches_name_filter (filter: &BaseNameFilter, name: &[u8]) -> bool {
+    if filter.ignore_case {
+        let lower = |s: &[u8]| String::from_utf8_lossy(s).to_lowercase();
+        let name = lower(name);
+        return name.starts_with(&lower(&filter.begins_with))
+            && name.ends_with(&lower(&filter.ends_with))
+            && name.contains(&lower(&filter.contains));
+    }
+    name.starts_with(&filter.begins_with)
+        && name.ends_with(&filter.ends_with)
+        && (filter.contains.is_empty() || name.windows(filter.contains.len()).any(|w| w == filter.contains.as_slice()))
+}
+
+/// Decodes a continuation that is the number of results already returned.
+fn decode_skip_continuation (continuation: &[u8]) -> std::result::Result<u64, tonic::Status> {
+    if continuation.is_empty() {
+        return Ok(0);
+    }
+    let skip: [u8; 8] = continuation.try_into()
+        .map_err(|_| tonic::Status::invalid_argument("invalid continuation token"))?;
+    Ok(u64::from_be_bytes(skip))
+}
+
+/// Removes the labels of an object from the index.
+fn unindex_attrs_of (w: &WriteTransaction<'_>, id: FileSystemId) -> std::result::Result<(), tonic::Status> {
+    let mut index = w.open_table(ATTR_INDEX_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    let mut by_id = w.open_table(ATTR_INDEX_BY_ID_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    let indexed = by_id.range((id, "")..(id + 1, ""))
+        .map_err(|_| tonic::Status::internal("could not read from attr index table"))?
+        .map(|entry| entry.map(|(k, v)| (k.value().1.to_string(), v.value().to_vec())))
+        .collect::<std::result::Result<Vec<(String, Vec<u8>)>, _>>()
+        .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+    for (name, value) in indexed {
+        index.remove((name.as_str(), value.as_slice(), id))
+            .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+        by_id.remove((id, name.as_str()))
+            .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    }
+    Ok(())
+}
+
+/// Replaces the indexed labels of an object with the labels of its latest
+/// version. This must be called whenever those change.
+fn index_attrs_of (w: &WriteTransaction<'_>, id: FileSystemId, latest_version: FsVersion) -> std::result::Result<(), tonic::Status> {
+    unindex_attrs_of(w, id)?;
+    let labels = {
+        let attrs = w.open_table(ATTRS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+        get_version_attrs_of(id, latest_version, &attrs)?
+    };
+    let mut index = w.open_table(ATTR_INDEX_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    let mut by_id = w.open_table(ATTR_INDEX_BY_ID_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    for (name, label) in labels.iter() {
+        let value = match any_to_index_value(label).as_ref().and_then(encode_index_value) {
+            Some(value) => value,
+            None => continue,
+        };
+        index.insert((name.as_str(), value.as_slice(), id), ())
+            .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+        by_id.insert((id, name.as_str()), value.as_slice())
+            .map_err(|_| tonic::Status::internal("could not write to attr index table"))?;
+    }
+    Ok(())
+}
+
+/// Records the key of an object in the fs table, so it can be found by ID.
+fn set_fs_key (w: &WriteTransaction<'_>, id: FileSystemId, key: &[u8]) -> std::result::Result<(), tonic::Status> {
+    let mut fs_keys = w.open_table(FS_KEYS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to fs keys table"))?;
+    fs_keys.insert(id, key)
+        .map_err(|_| tonic::Status::internal("could not write to fs keys table"))?;
+    Ok(())
+}
+
+fn remove_fs_key (w: &WriteTransaction<'_>, id: FileSystemId) -> std::result::Result<(), tonic::Status> {
+    let mut fs_keys = w.open_table(FS_KEYS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to fs keys table"))?;
+    fs_keys.remove(id)
+        .map_err(|_| tonic::Status::internal("could not write to fs keys table"))?;
+    Ok(())
+}
+
+/// Finds the record of an object by its ID, along with the IDs and names of
+/// the object and its ancestors, from the root down. This returns `None` if
+/// the object is no longer linked to the root, such as when a folder that
+/// contained it was deleted.
+fn resolve_ancestry <'a, K, F> (id: FileSystemId, fs_keys: &'a K, fs: &'a F) -> std::result::Result<Option<(FsRecordValue, Vec<(FileSystemId, String)>)>, tonic::Status>
+    where
+        K: ReadableTable<u64, &'static [u8]>,
+        F: ReadableTable<&'static [u8], &'static [u8]> {
+    let mut ancestry: Vec<(FileSystemId, String)> = Vec::new();
+    let mut record: Option<FsRecordValue> = None;
+    let mut current = id;
+    loop {
+        let key = match fs_keys.get(current)
+            .map_err(|_| tonic::Status::internal("could not read from fs keys table"))? {
+            Some(key) => key.value().to_vec(),
+            None => return Ok(None),
+        };
+        if key.len() < mem::size_of::<FileSystemId>() {
+            return Err(tonic::Status::internal("corrupted fs key"));
+        }
+        let value = match fs.get(key.as_slice())
+            .map_err(|_| tonic::Status::internal("error trying to read fs key"))? {
+            Some(value) => value.value().to_vec(),
+            None => return Ok(None),
+        };
+        if value.len() < mem::size_of::<FsRecordValue>() {
+            return Err(tonic::Status::internal("corrupted fs record"));
+        }
+        let current_record = fs_record_from_bytes(&value);
+        if current_record.id != current {
+            // Another object has since taken the name.
+            return Ok(None);
+        }
+        // Unsafe is reasonable here, because we only put UTF-8 strings here.
+        let name = unsafe { std::str::from_utf8_unchecked(&value[mem::size_of::<FsRecordValue>()..]) };
+        ancestry.push((current, name.to_owned()));
+        record.get_or_insert(current_record);
+        // Keys start with the ID of the parent, as it is read by `children_id()`.
+        let parent_key_id = u64::from_be_bytes([
+            key[0], key[1], key[2], key[3],
+            key[4], key[5], key[6], key[7],
+        ]);
+        if parent_key_id == ROOT_FSID {
+            break;
+        }
+        current = u64::from_ne_bytes(parent_key_id.to_be_bytes());
+        if ancestry.len() > u16::MAX as usize {
+            return Err(tonic::Status::internal("cycle in file system"));
+        }
+    }
+    ancestry.reverse();
+    Ok(record.map(|record| (record, ancestry)))
+}
+
+/// Reads the arbitrary attributes (`otherMetadata`) of an object.
+pub fn get_object_attrs (db: &Database, id: FileSystemId) -> std::result::Result<HashMap<String, prost_types::Any>, tonic::Status> {
+    let r = db.begin_read()
+        .map_err(|_| tonic::Status::internal("could not read from database"))?;
+    let attrs = r.open_table(ATTRS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+    get_attrs_of(id, &attrs)
+}
+
+/// Looks up the key and record of the object at `path`, including the trailing
+/// variable-length part of the record, or returns `None` if it does not exist.
+/// The root folder has no record, so this also returns `None` for it.
+fn lookup_path <'a, R> (path: &[String], fs: &'a R) -> std::result::Result<Option<(Vec<u8>, Vec<u8>)>, tonic::Status>
+    where R: ReadableTable<&'static [u8], &'static [u8]> {
+    let mut parent_id: FileSystemId = ROOT_FSID;
+    let mut ret: Option<(Vec<u8>, Vec<u8>)> = None;
+    for pc in path {
+        if ret.as_ref().is_some_and(|(_, value)| fs_record_from_bytes(value).r#type != OBJ_TYPE_FOLDER) {
+            return Ok(None);
+        }
+        let key = make_key(parent_id, pc);
+        let maybe_value = fs.get(key.as_slice())
+            .map_err(|_| tonic::Status::internal("error trying to read fs key"))?;
+        let value = match maybe_value {
+            Some(value) => value.value().to_vec(),
+            None => return Ok(None),
+        };
+        if value.len() < mem::size_of::<FsRecordValue>() {
+            return Err(tonic::Status::internal("corrupted fs record"));
+        }
+        parent_id = u64::from_be_bytes([
+            value[0], value[1], value[2], value[3],
+            value[4], value[5], value[6], value[7],
+        ]);
+        ret = Some((key, value));
+    }
+    Ok(ret)
+}
+
+/// Returns the ID under which the children of the object are keyed. This is
+/// read from the record the same way as when descending a path, which is as a
+/// big-endian integer, even though the ID is stored in native byte order.
+fn children_id (record: &FsRecordValue) -> FileSystemId {
+    u64::from_be_bytes(record.id.to_ne_bytes())
+}
+
+/// Returns the number of objects directly within the folder whose children
+/// are keyed by the given ID.
+fn count_entries <'a, R> (id: FileSystemId, fs: &'a R) -> std::result::Result<u64, tonic::Status>
+    where R: ReadableTable<&'static [u8], &'static [u8]> {
+    let next_id = id + 1;
+    let iter = fs.range(id.to_be_bytes().as_slice()..next_id.to_be_bytes().as_slice())
+        .map_err(|_| tonic::Status::internal("error trying to list subordinate entries"))?;
+    let mut entries: u64 = 0;
+    for entry in iter {
+        entry.map_err(|_| tonic::Status::internal("failed to continue listing entries"))?;
+        entries += 1;
+    }
+    Ok(entries)
+}
+
+/// Builds the attributes of an object from its record and the number and record
+/// of one of its versions. Objects without a version record, such as folders created
+/// before ownership was recorded, are treated as owned by root with mode 0755.
+fn object_attributes <'a, F, A> (
+    record: &FsRecordValue,
+    version_number: FsVersion,
+    version: Option<VersionRecordValue>,
+    fs: &'a F,
+    attrs: &'a A,
+) -> std::result::Result<FsAttributes, tonic::Status>
+    where
+        F: ReadableTable<&'static [u8], &'static [u8]>,
+        A: ReadableTable<&'static str, &'static [u8]> {
+    let is_folder = record.r#type == OBJ_TYPE_FOLDER;
+    Ok(FsAttributes {
+        r#type: record.obj_type().map(|x| x.into()),
+        uid: Some(version.map(|ver| ver.uid).unwrap_or(0)),
+        gid: Some(version.map(|ver| ver.gid).unwrap_or(0)),
+        perms: Some(u16_to_unix_perms(version.map(|ver| ver.flags).unwrap_or(0o755))),
+        create_time: record.create_time.known().map(|t| t.into()),
+        modify_time: record.modify_time.known().map(|t| t.into()),
+        access_time: record.access_time.known().map(|t| t.into()),
+        change_time: record.change_time.known().map(|t| t.into()),
+        delete_time: record.delete_time.known().map(|t| t.into()),
+        size: version
+            .filter(|ver| !is_folder && ver.length != UNKNOWN_SIZE)
+            .map(|ver| ver.length),
+        inode: Some(record.id),
+        entries: if is_folder { Some(count_entries(children_id(record), fs)?) } else { None },
+        storage_tier_id: version.map(|ver| ver.storage_tier as u32).unwrap_or(0),
+        other_metadata: get_version_attrs_of(record.id, version_number, attrs)?,
+        ..Default::default()
+    })
+}
+
+/// Looks up the attributes of the object at `path`, or returns `None` if it
+/// does not exist. The root folder has no record, so it has no attributes.
+pub fn get_path_attributes (db: &Database, path: &[String]) -> std::result::Result<Option<FsAttributes>, tonic::Status> {
+    let r = db.begin_read()
+        .map_err(|_| tonic::Status::internal("could not read from database"))?;
+    let fs = r.open_table(FS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+    let v = r.open_table(VER_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+    let attrs = r.open_table(ATTRS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+    let record = match lookup_path(&validate_path(path)?, &fs)? {
+        Some((_, value)) => fs_record_from_bytes(&value),
+        None => return Ok(None),
+    };
+    let version = get_version_record(&record, &v)?;
+    Ok(Some(object_attributes(&record, record.latest_version, version, &fs, &attrs)?))
+}
+
+fn to_time64 (timestamp: &prost_types::Timestamp) -> std::result::Result<Time64, tonic::Status> {
+    if timestamp.seconds < 0 || timestamp.nanos < 0 {
+        return Err(tonic::Status::invalid_argument("times before 1970 are not supported"));
+    }
+    Ok(Time64::from(timestamp.clone()))
+}
+
+/// Reads a whole blob into memory, such as to query or translate it, or
+/// returns `None` if it is larger than `max_size`. If `length` is given, only
+/// that much of the blob is read, since appending to a file grows the blob
+/// that its earlier versions share.
+async fn read_whole_blob (blob_path: &PathBuf, length: Option<u64>, max_size: u64) -> std::result::Result<Option<Vec<u8>>, tonic::Status> {
+    let f = File::open(blob_path).await?;
+    let mut data = Vec::new();
+    f.take(length.map_or(max_size + 1, |length| min(length, max_size + 1))).read_to_end(&mut data).await?;
+    if data.len() as u64 > max_size {
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+/// Reads and extracts the text of the start of a blob, for the full-text index.
+async fn read_blob_text (file_name: &str, blob_path: &PathBuf) -> std::result::Result<Option<String>, tonic::Status> {
+    let f = File::open(blob_path).await?;
+    let mut data = Vec::new();
+    f.take(fulltext::MAX_INDEXED_SIZE as u64).read_to_end(&mut data).await?;
+    Ok(fulltext::extract_text(file_name, &data))
+}
+
 #[derive(Debug)]
 pub struct DatabaseStorage {
     pub blobs_path: std::path::PathBuf,
     pub db_path: std::path::PathBuf,
-    pub db: Database,
+    pub db: Arc<Database>,
     pub http_url_prefix: Option<String>,
     pub tor_prefix: Option<String>,
 }
@@ -362,6 +1559,64 @@ impl DatabaseStorage {
         let w = db.begin_write().expect("failed to open writer");
         w.open_table(FS_TABLE).expect("failed to create fs table");
         w.open_table(SEQ_TABLE).expect("failed to create seq table");
+        w.open_table(VER_TABLE).expect("failed to create versions table");
+        w.open_table(ATTRS_TABLE).expect("failed to create attrs table");
+        w.open_table(ATTR_INDEX_TABLE).expect("failed to create attr index table");
+        w.open_table(ATTR_INDEX_BY_ID_TABLE).expect("failed to create attr index table");
+        w.open_table(UPLOADS_TABLE).expect("failed to create uploads table");
+        w.open_table(RESERVATIONS_TABLE).expect("failed to create reservations table");
+        w.open_table(UPLOAD_RANGES_TABLE).expect("failed to create upload ranges table");
+        w.open_table(UPLOAD_PARTS_TABLE).expect("failed to create upload parts table");
+        fulltext::create_tables(&w).expect("failed to create text index tables");
+        let fs_keys_empty = w.open_table(FS_KEYS_TABLE).expect("failed to create fs keys table")
+            .len().expect("failed to read fs keys table") == 0;
+        if fs_keys_empty {
+            // Databases created before objects were indexed are indexed when opened.
+            let objects = {
+                let fs = w.open_table(FS_TABLE).expect("failed to open fs table");
+                let iter = fs.iter().expect("failed to read fs table");
+                iter.map(|entry| {
+                    let (k, v) = entry.expect("failed to read fs table");
+                    (k.value().to_vec(), fs_record_from_bytes(v.value()))
+                }).collect::<Vec<_>>()
+            };
+            for (key, record) in objects {
+                set_fs_key(&w, record.id, &key).expect("failed to index fs table");
+                index_attrs_of(&w, record.id, record.latest_version).expect("failed to index attrs table");
+            }
+        }
+        let version_record_size = w.open_table(META_TABLE).expect("failed to create meta table")
+            .get(VERSION_RECORD_SIZE_KEY).expect("failed to read meta table")
+            .map(|v| v.value());
+        if version_record_size.is_none() {
+            // Databases created before the hashes of versions were stored have
+            // shorter version records, which are widened when opened, as if the
+            // hashes had never been computed.
+            let old_size = mem::size_of::<VersionRecordValue>() - mem::size_of::<HashesValue>();
+            let mut v = w.open_table(VER_TABLE).expect("failed to open versions table");
+            let versions = {
+                let iter = v.iter().expect("failed to read versions table");
+                iter.map(|entry| {
+                    let (k, v) = entry.expect("failed to read versions table");
+                    (k.value().to_vec(), v.value().to_vec())
+                }).collect::<Vec<_>>()
+            };
+            for (key, value) in versions {
+                if value.len() < old_size {
+                    continue;
+                }
+                let migrated = [
+                    &value[0..old_size],
+                    bytemuck::bytes_of(&HashesValue::zeroed()),
+                    &value[old_size..],
+                ].concat();
+                v.insert(key.as_slice(), migrated.as_slice()).expect("failed to migrate versions table");
+            }
+            drop(v);
+            w.open_table(META_TABLE).expect("failed to open meta table")
+                .insert(VERSION_RECORD_SIZE_KEY, mem::size_of::<VersionRecordValue>() as u64)
+                .expect("failed to write to meta table");
+        }
         w.commit().expect("failed to create tables");
         // if cfg!(debug) {
         //     let r = db.begin_read().unwrap();
@@ -376,11 +1631,360 @@ impl DatabaseStorage {
         DatabaseStorage {
             blobs_path,
             db_path,
-            db,
+            db: Arc::new(db),
             http_url_prefix: None,
             tor_prefix: None,
         }
     }
+
+    fn upload_blob_path (&self, ulid: u128) -> PathBuf {
+        let mut blob_path = self.blobs_path.clone();
+        blob_path.push(format!("{}.blob", Ulid(ulid)));
+        blob_path
+    }
+
+    fn upload_part_path (&self, ulid: u128, number: u32) -> PathBuf {
+        let mut part_path = self.blobs_path.clone();
+        part_path.push(format!("{}.{}.part", Ulid(ulid), number));
+        part_path
+    }
+
+    /// Removes the blob of an upload and the blobs of its parts, once the
+    /// records of the upload are gone. Blobs that do not exist are ignored.
+    async fn remove_upload_blobs (&self, ulid: u128, parts: &[u32]) -> std::io::Result<()> {
+        let blob_paths = std::iter::once(self.upload_blob_path(ulid))
+            .chain(parts.iter().map(|number| self.upload_part_path(ulid, *number)));
+        for blob_path in blob_paths {
+            match remove_file(&blob_path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+                r => r?,
+            };
+        }
+        Ok(())
+    }
+
+    /// Stores `req.data` as a numbered part of the upload identified by the
+    /// continuation token. Parts may be uploaded in any order, and even
+    /// concurrently, and uploading a part again replaces it. The SHA-512 hash
+    /// of the part is returned, so it can be listed when the upload is
+    /// completed.
+    async fn upload_part (
+        &self,
+        req: UploadArg,
+    ) -> std::result::Result<tonic::Response<UploadResult>, tonic::Status> {
+        let path = validate_path(&req.target.map(|t| t.path).unwrap_or_default())?;
+        if path.is_empty() {
+            return Err(tonic::Status::invalid_argument("target may not be empty"));
+        }
+        if req.part > MAX_UPLOAD_PARTS {
+            return Err(tonic::Status::invalid_argument("part number is too high"));
+        }
+        if !req.parts.is_empty() {
+            return Err(tonic::Status::invalid_argument("parts cannot be uploaded and assembled at once"));
+        }
+        if req.continuation.is_empty() {
+            return Err(tonic::Status::invalid_argument("parts may only be uploaded to an upload in progress"));
+        }
+        let ulid = decode_continuation(&req.continuation)?;
+        let hash = Sha512::digest(&req.data);
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        {
+            let mut record = {
+                let uploads = w.open_table(UPLOADS_TABLE)
+                    .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+                find_continued_upload(ulid.0, &path, &uploads)?
+            };
+            let part = UploadPartValue {
+                modify_time: Time64::now(),
+                length: req.data.len() as u64,
+                sha2_512: hash.into(),
+            };
+            let mut upload_parts = w.open_table(UPLOAD_PARTS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to upload parts table"))?;
+            upload_parts.insert((ulid.0, req.part), bytemuck::bytes_of(&part))
+                .map_err(|_| tonic::Status::internal("could not write to upload parts table"))?;
+            record.modify_time = part.modify_time;
+            put_upload(&w, ulid.0, &record, &path)?;
+        }
+        // The part record is only committed once the part has been written.
+        let mut f = File::create(self.upload_part_path(ulid.0, req.part)).await?;
+        f.write_all(&req.data).await?;
+        f.sync_all().await?;
+        drop(f);
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        Ok(tonic::Response::new(UploadResult {
+            continuation: req.continuation,
+            hashes: Some(Hashes {
+                sha2_512: hash.to_vec(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }))
+    }
+
+    /// Completes the upload identified by the continuation token by
+    /// concatenating the listed parts, in order, into a new version of the
+    /// file. Every part must have been uploaded with the listed SHA-512 hash.
+    /// Parts that were uploaded but not listed are discarded.
+    async fn assemble_parts (
+        &self,
+        req: UploadArg,
+    ) -> std::result::Result<tonic::Response<UploadResult>, tonic::Status> {
+        let mut path = validate_path(&req.target.map(|t| t.path).unwrap_or_default())?;
+        if path.is_empty() {
+            return Err(tonic::Status::invalid_argument("target may not be empty"));
+        }
+        if req.incomplete || !req.data.is_empty() {
+            return Err(tonic::Status::invalid_argument("no data may be uploaded while assembling parts"));
+        }
+        if req.continuation.is_empty() {
+            return Err(tonic::Status::invalid_argument("only an upload in progress may be assembled from parts"));
+        }
+        if req.parts.windows(2).any(|pair| pair[0].number >= pair[1].number) {
+            return Err(tonic::Status::invalid_argument("parts must be listed in ascending order"));
+        }
+        let ulid = decode_continuation(&req.continuation)?;
+        let mut length: u64 = 0;
+        {
+            let r = self.db.begin_read()
+                .map_err(|_| tonic::Status::internal("could not read from database"))?;
+            let uploads = r.open_table(UPLOADS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            find_continued_upload(ulid.0, &path, &uploads)?;
+            let upload_parts = r.open_table(UPLOAD_PARTS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from upload parts table"))?;
+            for listed in req.parts.iter() {
+                let hashes = listed.hashes.clone().unwrap_or_default();
+                if !hashes.sha3_512.is_empty() || !hashes.xxh64.is_empty() {
+                    return Err(tonic::Status::invalid_argument("only the SHA-512 hashes of parts are supported"));
+                }
+                if hashes.sha2_512.is_empty() {
+                    return Err(tonic::Status::invalid_argument(format!("part {} is listed without its SHA-512 hash", listed.number)));
+                }
+                let part: UploadPartValue = match upload_parts.get((ulid.0, listed.number))
+                    .map_err(|_| tonic::Status::internal("could not read from upload parts table"))? {
+                    Some(v) => bytemuck::pod_read_unaligned(v.value()),
+                    None => return Err(tonic::Status::invalid_argument(format!("part {} has not been uploaded", listed.number))),
+                };
+                if part.sha2_512.as_slice() != hashes.sha2_512.as_slice() {
+                    return Err(tonic::Status::invalid_argument(format!("hash of part {} does not match", listed.number)));
+                }
+                length += part.length;
+            }
+        }
+
+        let new_ulid = Ulid::new();
+        let new_blob_path = self.upload_blob_path(new_ulid.0);
+        let mut f = File::create(&new_blob_path).await?;
+        let mut hasher = ContentHasher::new();
+        let mut buf = vec![0; hashes::HASH_BUFFER_SIZE];
+        for listed in req.parts.iter() {
+            let mut part_file = File::open(self.upload_part_path(ulid.0, listed.number)).await?;
+            loop {
+                let bytes_read = part_file.read(&mut buf).await?;
+                if bytes_read == 0 {
+                    break;
+                }
+                hasher.update(&buf[..bytes_read]);
+                f.write_all(&buf[..bytes_read]).await?;
+            }
+        }
+        f.sync_all().await?;
+        drop(f);
+        let content_hashes = hasher.finish();
+        if req.hashes.as_ref().is_some_and(|expected| !hashes::matches(expected, &content_hashes)) {
+            remove_file(&new_blob_path).await?;
+            return Ok(tonic::Response::new(UploadResult {
+                err: Some(FileSystemError::hash_mismatch()),
+                ..Default::default()
+            }));
+        }
+
+        let file_name = path.pop().unwrap();
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let written = async {
+            // Another request may have completed the upload in the meantime.
+            let parts = remove_upload(&w, ulid.0)?
+                .ok_or_else(|| tonic::Status::invalid_argument("no such upload"))?;
+            let version = write_version(&w, &path, file_name.trim(), req.next, NewVersion {
+                ulid: new_ulid,
+                blob_path: new_blob_path.clone(),
+                length,
+                uid: req.uid,
+                gid: req.gid,
+                flags: req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755),
+                hashes: content_hashes.clone(),
+                force: req.force,
+            }).await?;
+            Ok::<_, tonic::Status>((parts, version))
+        }.await;
+        let (parts, written) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                drop(w);
+                remove_file(&new_blob_path).await?;
+                return Err(e);
+            },
+        };
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        self.remove_upload_blobs(ulid.0, &parts).await?;
+        if written.unchanged {
+            remove_file(&new_blob_path).await?;
+        }
+        Ok(tonic::Response::new(UploadResult {
+            version: Some(written.version),
+            hashes: Some(content_hashes),
+            unchanged: written.unchanged,
+            ..Default::default()
+        }))
+    }
+
+    /// Deletes the incomplete upload to the target that is identified by the
+    /// continuation token, along with its blobs.
+    async fn delete_upload (
+        &self,
+        req: DeleteArg,
+    ) -> std::result::Result<tonic::Response<DeleteResult>, tonic::Status> {
+        let path = validate_path(&req.target.map(|t| t.path).unwrap_or_default())?;
+        let ulid = decode_continuation(&req.continuation)?;
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let upload_path = {
+            let uploads = w.open_table(UPLOADS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            let maybe_upload = uploads.get(ulid.0)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?
+                .map(|v| upload_record_from_bytes(v.value()))
+                .transpose()?;
+            match maybe_upload {
+                Some((_, upload_path)) => upload_path,
+                None => return Err(tonic::Status::invalid_argument("no such upload")),
+            }
+        };
+        if make_upload_path_key(&upload_path) != make_upload_path_key(&path) {
+            return Err(tonic::Status::invalid_argument("continuation is for a different target"));
+        }
+        let parts = remove_upload(&w, ulid.0)?.unwrap_or_default();
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        self.remove_upload_blobs(ulid.0, &parts).await?;
+        Ok(tonic::Response::new(DeleteResult {
+            ..Default::default()
+        }))
+    }
+
+    /// Removes the uploads that have not been added to for longer than
+    /// `max_idle`, returning how many were removed.
+    pub async fn remove_idle_uploads (&self, max_idle: chrono::Duration) -> std::result::Result<usize, tonic::Status> {
+        let cutoff = chrono::Utc::now() - max_idle;
+        let cutoff = Time64::from_parts(cutoff.timestamp().max(0) as u64, 0);
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let idle: Vec<u128> = {
+            let uploads = w.open_table(UPLOADS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            let mut idle = Vec::new();
+            for entry in uploads.iter().map_err(|_| tonic::Status::internal("could not read from uploads table"))? {
+                let (k, v) = entry.map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+                let (record, _) = upload_record_from_bytes(v.value())?;
+                if record.modify_time.0 < cutoff.0 {
+                    idle.push(k.value());
+                }
+            }
+            idle
+        };
+        let mut removed = Vec::with_capacity(idle.len());
+        for ulid in idle {
+            if let Some(parts) = remove_upload(&w, ulid)? {
+                removed.push((ulid, parts));
+            }
+        }
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        for (ulid, parts) in removed.iter() {
+            self.remove_upload_blobs(*ulid, parts).await?;
+        }
+        Ok(removed.len())
+    }
+
+    /// Begins an upload of `req.reserve` bytes, filled with nulls, which are
+    /// then written by patches in any order. The upload only becomes a version
+    /// of the file once a patch finishes it, which requires that every byte
+    /// has been written. Only one upload to a path may be reserved at a time.
+    async fn reserve (
+        &self,
+        req: UploadArg,
+    ) -> std::result::Result<tonic::Response<UploadResult>, tonic::Status> {
+        let path = validate_path(&req.target.map(|t| t.path).unwrap_or_default())?;
+        if path.is_empty() {
+            return Err(tonic::Status::invalid_argument("target may not be empty"));
+        }
+        if !req.continuation.is_empty() {
+            return Err(tonic::Status::invalid_argument("reserved uploads cannot be continued"));
+        }
+        if req.data.len() as u64 > req.reserve {
+            return Err(tonic::Status::out_of_range("data exceeds the reserved length"));
+        }
+        let ulid = Ulid::new();
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        {
+            let fs = w.open_table(FS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+            let (dir_path, file_name) = path.split_at(path.len() - 1);
+            let parent_id = descend_path(dir_path, &fs)?;
+            let exists = fs.get(make_key(parent_id, file_name[0].trim()).as_slice())
+                .map_err(|_| tonic::Status::internal("error trying to check if file exists already"))?
+                .is_some();
+            if exists && !req.next {
+                return Err(tonic::Status::invalid_argument("object already exists with that name"));
+            }
+            let mut reservations = w.open_table(RESERVATIONS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to reservations table"))?;
+            let path_key = make_upload_path_key(&path);
+            let reserved = reservations.get(path_key.as_slice())
+                .map_err(|_| tonic::Status::internal("could not read from reservations table"))?
+                .is_some();
+            if reserved {
+                return Err(tonic::Status::already_exists("an upload to that path is already reserved"));
+            }
+            reservations.insert(path_key.as_slice(), ulid.0)
+                .map_err(|_| tonic::Status::internal("could not write to reservations table"))?;
+            let record = UploadRecordValue {
+                length: req.reserve,
+                uid: req.uid,
+                gid: req.gid,
+                flags: req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755),
+                next: req.next as u8,
+                force: req.force as u8,
+                ..Default::default()
+            };
+            put_upload(&w, ulid.0, &record, &path)?;
+        }
+        if !req.data.is_empty() {
+            add_upload_range(&w, ulid.0, 0, req.data.len() as u64)?;
+        }
+        // The records are only committed once the blob has been allocated.
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.upload_blob_path(ulid.0))
+            .await?;
+        f.set_len(req.reserve).await?;
+        f.write_all(&req.data).await?;
+        f.sync_all().await?;
+        drop(f);
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        Ok(tonic::Response::new(UploadResult {
+            continuation: ulid.to_bytes().to_vec(),
+            ..Default::default()
+        }))
+    }
 }
 
 type WatchManyStream = tonic::codec::Streaming<FileSystemEvent>;
@@ -402,7 +2006,7 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
-        let mut fullpath = req.target.unwrap().path;
+        let mut fullpath = validate_path(&req.target.unwrap().path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -412,6 +2016,7 @@ impl Storage for DatabaseStorage {
         let fs = r.open_table(FS_TABLE)
             .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
         let mut parent_id: FileSystemId = ROOT_FSID;
+        let mut parent_rec: Option<FsRecordValue> = None;
         let folder_name = fullpath.pop().unwrap();
         let folder_name = folder_name.trim();
         for pc in fullpath {
@@ -440,6 +2045,7 @@ impl Storage for DatabaseStorage {
             if obj_type != OBJ_TYPE_FOLDER {
                 return Err(tonic::Status::invalid_argument("cannot place under non-folder"));
             }
+            parent_rec = Some(record);
         }
 
         let w = self.db.begin_write()
@@ -458,13 +2064,8 @@ impl Storage for DatabaseStorage {
                     return Err(tonic::Status::invalid_argument("object already exists with that name"));
                 }
             }
-            let seq_writer = w.open_table(SEQ_TABLE)
-                .map_err(|_| tonic::Status::internal("could not read from seq table"))?;
-            let last_id = seq_writer.get(FS_TABLE_NAME)
-                .map_err(|_| tonic::Status::internal("error trying to read seq key"))?
-                .map(|s| s.value())
-                .unwrap_or(0);
-            let next_id = last_id + 1;
+            let next_id = get_next_id(&w, FS_TABLE_NAME)
+                .map_err(|_| tonic::Status::internal("could not read and/or increment sequence for FS table"))?;
             let new_dir_record = FsRecordValue {
                 id: next_id,
                 r#type: OBJ_TYPE_FOLDER,
@@ -480,6 +2081,35 @@ impl Storage for DatabaseStorage {
                 // Nothing needs to be done here. The write wasn't committed yet.
                 return Err(tonic::Status::internal("replaced existing folder"));
             }
+            set_fs_key(&w, next_id, &key)?;
+
+            // Folders are not versioned, but their ownership and permissions
+            // are stored in a version record, just like files.
+            let mut v_writer = w.open_table(VER_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+            let inherited_gid = get_inherited_gid(parent_rec.as_ref(), &v_writer)?;
+            let mut flags = req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755);
+            if inherited_gid.is_some() {
+                // Subfolders of a setgid folder are setgid too.
+                flags |= UNIX_PERM_SETGID;
+            }
+            let new_dir_version = VersionRecordValue {
+                create_time: Time64::now(),
+                access_time: TIME64_UNKNOWN_TIME,
+                length: 0,
+                uid: req.uid,
+                gid: inherited_gid.unwrap_or(req.gid),
+                flags,
+                storage_tier: DEFAULT_STORAGE_TIER,
+                other: 0,
+                hashes: HashesValue::zeroed(),
+            };
+            let version_key = VersionRecordKey {
+                file_id: next_id,
+                version: new_dir_record.latest_version,
+            };
+            v_writer.insert(bytemuck::bytes_of(&version_key), bytemuck::bytes_of(&new_dir_version))
+                .map_err(|_| tonic::Status::internal("could not write to versions table"))?;
         }
         w.commit()
             .map_err(|_| tonic::Status::internal("could not commit changes"))?;
@@ -496,7 +2126,16 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
-        let mut fullpath = req.target.unwrap().path;
+        if req.reserve > 0 {
+            return self.reserve(req).await;
+        }
+        if req.part > 0 {
+            return self.upload_part(req).await;
+        }
+        if !req.parts.is_empty() {
+            return self.assemble_parts(req).await;
+        }
+        let mut fullpath = validate_path(&req.target.unwrap().path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -507,11 +2146,13 @@ impl Storage for DatabaseStorage {
             Ulid::new()
         } else {
             // Otherwise, we use the continuation token as the blob name.
-            let a: [u8; 16] = req.continuation[..]
-                .try_into()
-                .map_err(|_| tonic::Status::invalid_argument("invalid continuation token"))?;
-            Ulid::try_from(a)
-                .map_err(|_| tonic::Status::invalid_argument("invalid continuation token"))?
+            let ulid = decode_continuation(&req.continuation)?;
+            let r = self.db.begin_read()
+                .map_err(|_| tonic::Status::internal("could not read from database"))?;
+            let uploads = r.open_table(UPLOADS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            find_continued_upload(ulid.0, &fullpath, &uploads)?;
+            ulid
         };
 
         let blob_file_name = format!("{}.blob", &ulid);
@@ -525,155 +2166,114 @@ impl Storage for DatabaseStorage {
         // TODO: https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.set_len
         // TODO: https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.sync_all
         // TODO: What if the write is 0-length?
-        f.write(&req.data).await?;
+        f.write_all(&req.data).await?;
         drop(f);
 
         if req.incomplete {
-            let ulid_bytes = ulid.to_bytes();
-            return Ok(tonic::Response::new(UploadResult {
-                continuation: ulid_bytes.to_vec(),
-                ..Default::default()
-            }));
-        }
-
-        let r = self.db.begin_read()
-            .map_err(|_| tonic::Status::internal("could not read from database"))?;
-        let fs = r.open_table(FS_TABLE)
-            .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
-        let mut parent_id: FileSystemId = ROOT_FSID;
-        let file_name = fullpath.pop().unwrap();
-        let file_name = file_name.trim(); // TODO: Cow trim
-        for pc in fullpath {
-            let key = [
-                parent_id.to_be_bytes().to_vec(),
-                pc.into_bytes(),
-            ].concat();
-            let maybe_value = fs.get(key.as_slice())
-                .map_err(|_| tonic::Status::internal("error trying to read fs key"))?;
-            if maybe_value.is_none() {
-                // TODO: Check permissions to create.
-                // TODO: Create if permitted.
-                return Err(tonic::Status::invalid_argument("no such parent path"));
-            }
-            let value = maybe_value.unwrap();
-            let value = value.value();
-            if value.len() < mem::size_of::<FsRecordValue>() {
-                return Err(tonic::Status::internal("corrupted fs record"));
-            }
-            parent_id = u64::from_be_bytes([
-                value[0], value[1], value[2], value[3],
-                value[4], value[5], value[6], value[7],
-            ]);
-            let record: FsRecordValue = bytemuck::pod_read_unaligned(&value[0..mem::size_of::<FsRecordValue>()]);
-            let obj_type: u8 = record.r#type;
-            if obj_type != OBJ_TYPE_FOLDER {
-                return Err(tonic::Status::invalid_argument("cannot place under non-folder"));
-            }
-        }
-
-        let w = self.db.begin_write()
-            .map_err(|_| tonic::Status::internal("could not write to database"))?;
-        {
-            let mut fs_writer = w.open_table(FS_TABLE)
-                .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
-            let key = make_key(parent_id, &file_name);
-            let mut existing_file_id: Option<FileSystemId> = None;
-            let mut create_time: Option<Time64> = None;
-            let mut access_time: Option<Time64> = None;
-            let mut latest_version: Option<FsVersion> = None;
-            {
-                let maybe_existing = fs_writer.get(key.as_slice())
-                    .map_err(|_| tonic::Status::internal("error trying to check if file exists already"))?;
-                if maybe_existing.is_some() {
-                    if !req.next { // If we are not explicitly trying to create a new version...
-                        return Err(tonic::Status::invalid_argument("object already exists with that name"));
-                    }
-                    let existing = maybe_existing.unwrap();
-                    let bytes = existing.value();
-                    let prev_fs_record = fs_record_from_bytes(bytes);
-                    existing_file_id = Some(prev_fs_record.id);
-                    create_time = Some(prev_fs_record.create_time);
-                    access_time = Some(prev_fs_record.access_time);
-                    latest_version = Some(prev_fs_record.latest_version);
+            // The upload is recorded, so that it can be continued with parts
+            // and so that it is known to be in progress.
+            let w = self.db.begin_write()
+                .map_err(|_| tonic::Status::internal("could not write to database"))?;
+            let mut record = if req.continuation.len() == 0 {
+                UploadRecordValue {
+                    uid: req.uid,
+                    gid: req.gid,
+                    flags: req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755),
+                    next: req.next as u8,
+                    force: req.force as u8,
+                    ..Default::default()
                 }
-            }
-
-            let file_id = if let Some(fid) = existing_file_id {
-                fid
             } else {
-                get_next_id(&w, FS_TABLE_NAME)
-                    .map_err(|_| tonic::Status::internal("could not read and/or increment sequence for FS table"))?
+                let uploads = w.open_table(UPLOADS_TABLE)
+                    .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+                find_continued_upload(ulid.0, &fullpath, &uploads)?
             };
-
-            let current_version = latest_version.map(|v| v + 1).unwrap_or(1);
-            let new_file_record = FsRecordValue {
-                id: file_id,
-                r#type: OBJ_TYPE_VERSION_BLOB, // FIXME: Allow creating different file types.
-                create_time: create_time.unwrap_or(Time64::now()),
-                modify_time: Time64::now(),
-                access_time: access_time.unwrap_or(Time64::now()),
-                change_time: TIME64_UNKNOWN_TIME,
-                delete_time: TIME64_UNKNOWN_TIME,
-                latest_version: current_version,
-                blob_ulid: ulid.0,
+            record.modify_time = Time64::now();
+            put_upload(&w, ulid.0, &record, &fullpath)?;
+            w.commit()
+                .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+            let ulid_bytes = ulid.to_bytes();
+            return Ok(tonic::Response::new(UploadResult {
+                continuation: ulid_bytes.to_vec(),
                 ..Default::default()
-            };
-            let new_value = [
-                bytemuck::bytes_of(&new_file_record),
-                file_name.as_bytes(),
-            ].concat();
-            let maybe_existing_value = fs_writer.insert(key.as_slice(), new_value.as_slice())
-                .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
-            if maybe_existing_value.is_some() {
-                // Nothing needs to be done here. The write wasn't committed yet.
-                return Err(tonic::Status::internal("replaced existing folder"));
-            }
-
-            // The entire blob was uploaded in a single message, not fragmented.
-            let single_message_blob: bool = !req.incomplete && req.continuation.len() == 0;
-            let new_file_version = VersionRecordValue {
-                create_time: Time64::now(),
-                access_time: TIME64_UNKNOWN_TIME,
-                uid: req.uid,
-                gid: req.gid,
-                flags: req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755),
-                storage_tier: DEFAULT_STORAGE_TIER,
-                // TODO: Lazy-load this, or increment the blob size in a record
-                length: if single_message_blob { req.data.len() as u64 } else { UNKNOWN_SIZE },
-                other: 0,
-            };
-            let saved_blob_path = if !single_message_blob {
-                // If the blob was fragmented among requests, we have to rename
-                // the blob so the previous ULID cannot be abused to append more
-                // data.
-                let new_ulid = Ulid::new();
-                let blob_file_name = format!("{}.blob", new_ulid);
-                let mut new_blob_path = self.blobs_path.clone();
-                new_blob_path.push(blob_file_name);
-                rename(&blob_path, &new_blob_path).await?;
-                new_blob_path
-            } else {
-                blob_path
-            };
-            let version_key = VersionRecordKey {
-                file_id,
-                version: current_version,
-            };
-            let version_value_bytes = [
-                bytemuck::bytes_of(&new_file_version),
-                saved_blob_path.to_str().unwrap().as_bytes(),
-            ].concat();
-            let mut v_writer = w.open_table(VER_TABLE)
-                .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
-            v_writer.insert(bytemuck::bytes_of(&version_key), version_value_bytes.as_slice())
-                .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+            }));
+        }
+
+        let file_name = fullpath.pop().unwrap();
+        let file_name = file_name.trim(); // TODO: Cow trim
+        // The entire blob was uploaded in a single message, not fragmented.
+        let single_message_blob: bool = !req.incomplete && req.continuation.len() == 0;
+        let (content_hashes, length) = if single_message_blob {
+            let mut hasher = ContentHasher::new();
+            hasher.update(&req.data);
+            (hasher.finish(), req.data.len() as u64)
+        } else {
+            hashes::hash_blob(&blob_path, None).await?
+        };
+        if req.hashes.as_ref().is_some_and(|expected| !hashes::matches(expected, &content_hashes)) {
+            // The upload is discarded, since it cannot be what the client meant.
+            remove_file(&blob_path).await?;
+            if !single_message_blob {
+                let w = self.db.begin_write()
+                    .map_err(|_| tonic::Status::internal("could not write to database"))?;
+                let parts = remove_upload(&w, ulid.0)?;
+                w.commit()
+                    .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+                if let Some(parts) = parts {
+                    self.remove_upload_blobs(ulid.0, &parts).await?;
+                }
+            }
+            return Ok(tonic::Response::new(UploadResult {
+                err: Some(FileSystemError::hash_mismatch()),
+                ..Default::default()
+            }));
         }
+        let (saved_ulid, saved_blob_path) = if !single_message_blob {
+            // If the blob was fragmented among requests, we have to rename
+            // the blob so the previous ULID cannot be abused to append more
+            // data.
+            let new_ulid = Ulid::new();
+            let blob_file_name = format!("{}.blob", new_ulid);
+            let mut new_blob_path = self.blobs_path.clone();
+            new_blob_path.push(blob_file_name);
+            rename(&blob_path, &new_blob_path).await?;
+            (new_ulid, new_blob_path)
+        } else {
+            (ulid, blob_path)
+        };
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let parts = if single_message_blob {
+            None
+        } else {
+            remove_upload(&w, ulid.0)?
+        };
+        let written = write_version(&w, &fullpath, file_name, req.next, NewVersion {
+            ulid: saved_ulid,
+            blob_path: saved_blob_path.clone(),
+            length,
+            uid: req.uid,
+            gid: req.gid,
+            flags: req.perms.as_ref().map(unix_perms_to_u16).unwrap_or(0o755),
+            hashes: content_hashes.clone(),
+            force: req.force,
+        }).await?;
         w.commit()
             .map_err(|_| tonic::Status::internal("could not commit changes"))?;
-        return Ok(tonic::Response::new(UploadResult {
+        if let Some(parts) = parts {
+            // Parts that were uploaded are discarded if the upload is
+            // completed sequentially instead.
+            self.remove_upload_blobs(ulid.0, &parts).await?;
+        }
+        if written.unchanged {
+            remove_file(&saved_blob_path).await?;
+        }
+        Ok(tonic::Response::new(UploadResult {
+            version: Some(written.version),
+            hashes: Some(content_hashes),
+            unchanged: written.unchanged,
             ..Default::default()
-        }));
-        // TODO: Use req.reserve to reserve space for the file.
+        }))
     }
 
     async fn append(
@@ -685,7 +2285,7 @@ impl Storage for DatabaseStorage {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
         let target = req.target.as_ref().unwrap();
-        let mut fullpath = target.path.clone();
+        let mut fullpath = validate_path(&target.path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -704,13 +2304,15 @@ impl Storage for DatabaseStorage {
             .map_err(|_| tonic::Status::internal("could not write to database"))?;
         let fs = w.open_table(FS_TABLE)
             .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
-        let maybe_file_rec = fs.get(&file_key.as_slice())
-            .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
-        if maybe_file_rec.is_none() {
-            return Err(tonic::Status::invalid_argument("no such file"));
-        }
-        let file_rec = maybe_file_rec.unwrap();
-        let file_rec = fs_record_from_bytes(file_rec.value());
+        let file_rec = {
+            let maybe_file_rec = fs.get(&file_key.as_slice())
+                .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+            if maybe_file_rec.is_none() {
+                return Err(tonic::Status::invalid_argument("no such file"));
+            }
+            let file_rec = maybe_file_rec.unwrap();
+            fs_record_from_bytes(file_rec.value())
+        };
         if !is_readable_obj_type(file_rec.r#type) {
             return Err(tonic::Status::invalid_argument("not a readable object"));
         }
@@ -725,6 +2327,7 @@ impl Storage for DatabaseStorage {
                 };
                 let new_version_key;
                 let new_version_rec;
+                let blob_path;
                 let mut v = w.open_table(VER_TABLE)
                     .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
                 { // Scoped so immutable ref to v is dropped before mutable ref is needed.
@@ -740,14 +2343,42 @@ impl Storage for DatabaseStorage {
                     let version_blob = unsafe {
                         std::str::from_utf8_unchecked(&version_rec_bytes[mem::size_of::<VersionRecordValue>()..])
                     };
-                    let mut blob_path = self.blobs_path.clone();
-                    blob_path.push(version_blob);
+                    blob_path = {
+                        let mut blob_path = self.blobs_path.clone();
+                        blob_path.push(version_blob);
+                        blob_path
+                    };
 
+                    // The blob is hashed before appending, so that nothing is
+                    // appended if the client expected other hashes.
+                    let existing_size = if known_size {
+                        version_rec.length
+                    } else {
+                        metadata(&blob_path).await?.len()
+                    };
+                    let content_hashes = if existing_size <= hashes::MAX_APPEND_HASHED_SIZE {
+                        let mut hasher = ContentHasher::new();
+                        hasher.update_from_blob(&blob_path, Some(existing_size)).await?;
+                        hasher.update(&req.data);
+                        Some(hasher.finish())
+                    } else {
+                        None
+                    };
+                    let err = match (req.hashes.as_ref(), content_hashes.as_ref()) {
+                        (Some(expected), Some(actual)) if !hashes::matches(expected, actual) => {
+                            Some(FileSystemError::hash_mismatch())
+                        },
+                        (Some(_), None) => Some(FileSystemError::too_large()),
+                        _ => None,
+                    };
+                    if err.is_some() {
+                        return Ok(tonic::Response::new(AppendResult { err }));
+                    }
                     let mut f = OpenOptions::new()
                         .append(true) // We have to seek to the end to append.
                         .open(&blob_path)
                         .await?;
-                    f.write(&req.data).await?;
+                    f.write_all(&req.data).await?;
                     let new_size: u64 = if known_size {
                         version_rec.length + req.data.len() as u64
                     } else {
@@ -764,6 +2395,7 @@ impl Storage for DatabaseStorage {
                     new_version_rec = VersionRecordValue {
                         create_time: Time64::now(),
                         length: new_size,
+                        hashes: content_hashes.as_ref().map_or(HashesValue::zeroed(), HashesValue::from),
                         ..version_rec
                     };
                 }
@@ -772,6 +2404,16 @@ impl Storage for DatabaseStorage {
                 v.insert(&new_version_key, &new_version_rec)
                     .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
                 // TODO: Write new version
+                drop(v);
+                drop(fs);
+                drop(w);
+                // The blob was appended to in place, so its text is reindexed.
+                let text = read_blob_text(&file_name, &blob_path).await?;
+                let w = self.db.begin_write()
+                    .map_err(|_| tonic::Status::internal("could not write to database"))?;
+                fulltext::index_document(&w, file_rec.id, text.as_deref())?;
+                w.commit()
+                    .map_err(|_| tonic::Status::internal("could not commit changes"))?;
                 Ok(tonic::Response::new(AppendResult {
                     ..Default::default()
                 }))
@@ -780,11 +2422,128 @@ impl Storage for DatabaseStorage {
         }
     }
 
+    // Patches fill in the holes of a reserved upload, which is version "0.1" of
+    // the target, in any order. Each byte may only be written once. Finishing
+    // makes the upload the next version of the file, but only once every byte
+    // has been written.
     async fn patch(
         &self,
         request: tonic::Request<PatchArg>,
     ) -> std::result::Result<tonic::Response<PatchResult>, tonic::Status> {
-        unimplemented!()
+        let req = request.into_inner();
+        let target = match req.target {
+            Some(target) => target,
+            None => return Err(tonic::Status::invalid_argument("target is required")),
+        };
+        if target.path.is_empty() {
+            return Err(tonic::Status::invalid_argument("target may not be empty"));
+        }
+        if target.version.as_ref().is_some_and(|v| v.major != 0) {
+            return Err(tonic::Status::invalid_argument("only reserved uploads may be patched"));
+        }
+        if req.length != 0 && req.length != req.data.len() as u64 {
+            return Err(tonic::Status::invalid_argument("length does not match data"));
+        }
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let ulid = {
+            let reservations = w.open_table(RESERVATIONS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from reservations table"))?;
+            let maybe_ulid = reservations.get(make_upload_path_key(&target.path).as_slice())
+                .map_err(|_| tonic::Status::internal("could not read from reservations table"))?
+                .map(|v| v.value());
+            match maybe_ulid {
+                Some(ulid) => ulid,
+                None => return Err(tonic::Status::invalid_argument("no upload to that path is reserved")),
+            }
+        };
+        if decode_continuation(&req.continuation)?.0 != ulid {
+            return Err(tonic::Status::invalid_argument("continuation is for a different upload"));
+        }
+        let (mut record, path) = {
+            let uploads = w.open_table(UPLOADS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            let maybe_record = uploads.get(ulid)
+                .map_err(|_| tonic::Status::internal("could not read from uploads table"))?
+                .map(|v| upload_record_from_bytes(v.value()))
+                .transpose()?;
+            match maybe_record {
+                Some(found) => found,
+                None => return Err(tonic::Status::internal("database corrupted: missing upload")),
+            }
+        };
+        let end = req.offset.checked_add(req.data.len() as u64)
+            .filter(|end| *end <= record.length)
+            .ok_or_else(|| tonic::Status::out_of_range("patch extends beyond the reserved length"))?;
+        if end > req.offset && !add_upload_range(&w, ulid, req.offset, end)? {
+            return Err(tonic::Status::invalid_argument("patch overlaps data already written"));
+        }
+        if req.finish && !is_upload_filled(&w, ulid, record.length)? {
+            return Err(tonic::Status::failed_precondition("not every byte of the upload has been written"));
+        }
+        let blob_path = self.upload_blob_path(ulid);
+        if !req.data.is_empty() {
+            let mut f = OpenOptions::new()
+                .write(true)
+                .open(&blob_path)
+                .await?;
+            f.seek(std::io::SeekFrom::Start(req.offset)).await?;
+            f.write_all(&req.data).await?;
+            f.sync_all().await?;
+        }
+        if req.finish {
+            let (content_hashes, _) = hashes::hash_blob(&blob_path, Some(record.length)).await?;
+            remove_upload(&w, ulid)?;
+            if req.hashes.as_ref().is_some_and(|expected| !hashes::matches(expected, &content_hashes)) {
+                // The upload is discarded, since it cannot be what the client meant.
+                w.commit()
+                    .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+                remove_file(&blob_path).await?;
+                return Ok(tonic::Response::new(PatchResult {
+                    err: Some(FileSystemError::hash_mismatch()),
+                }));
+            }
+            // Like a fragmented upload, the blob is renamed so that the ULID of
+            // the upload cannot be abused to modify the new version.
+            let new_ulid = Ulid::new();
+            let new_blob_path = self.upload_blob_path(new_ulid.0);
+            rename(&blob_path, &new_blob_path).await?;
+            let (dir_path, file_name) = path.split_at(path.len() - 1);
+            let written = write_version(&w, dir_path, file_name[0].trim(), record.next != 0, NewVersion {
+                ulid: new_ulid,
+                blob_path: new_blob_path.clone(),
+                length: record.length,
+                uid: record.uid,
+                gid: record.gid,
+                flags: record.flags,
+                hashes: content_hashes,
+                force: record.force != 0,
+            }).await;
+            match written {
+                Ok(written) if written.unchanged => {
+                    w.commit()
+                        .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+                    remove_file(&new_blob_path).await?;
+                    return Ok(tonic::Response::new(PatchResult {
+                        ..Default::default()
+                    }));
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    // The upload is left as it was, so it may be finished again.
+                    rename(&new_blob_path, &blob_path).await?;
+                    return Err(e);
+                },
+            };
+        } else {
+            record.modify_time = Time64::now();
+            put_upload(&w, ulid, &record, &path)?;
+        }
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        Ok(tonic::Response::new(PatchResult {
+            ..Default::default()
+        }))
     }
 
     async fn download(
@@ -796,7 +2555,7 @@ impl Storage for DatabaseStorage {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
         let target = req.target.as_ref().unwrap();
-        let mut fullpath = target.path.clone();
+        let mut fullpath = validate_path(&target.path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -844,6 +2603,47 @@ impl Storage for DatabaseStorage {
                 };
                 let mut blob_path = self.blobs_path.clone();
                 blob_path.push(version_blob);
+                let format = DocumentFormat::from_i32(req.format)
+                    .ok_or_else(|| tonic::Status::invalid_argument("unrecognized format"))?;
+                if format != DocumentFormat::Original {
+                    let translated = match translate::source_format(&file_name) {
+                        Some(source_format) => match read_whole_blob(&blob_path, known_size.then_some(version_rec.length), MAX_TRANSLATION_SIZE).await? {
+                            Some(data) => translate::translate(&data, source_format, format)
+                                .map_err(|e| match e {
+                                    TranslationError::Unparseable => FileSystemError::invalid_operand_type(),
+                                    TranslationError::Unrepresentable => FileSystemError::not_representable(),
+                                }),
+                            None => Err(FileSystemError::too_large()),
+                        },
+                        None => Err(FileSystemError::invalid_operand_type()),
+                    };
+                    let translated = match translated {
+                        Ok(translated) => translated,
+                        Err(err) => return Ok(tonic::Response::new(DownloadResult {
+                            err: Some(err),
+                            ..Default::default()
+                        })),
+                    };
+                    if req.offset > translated.len() as u64 {
+                        return Err(tonic::Status::invalid_argument("offset beyond end of file"));
+                    }
+                    let start = req.offset as usize;
+                    let end = min(start + min(req.length as usize, MAX_READ_SIZE), translated.len());
+                    return Ok(tonic::Response::new(DownloadResult {
+                        data: translated[start..end].to_vec(),
+                        version: Some(FileVersion {
+                            major: requested_version,
+                            minor: 0,
+                        }),
+                        more: end < translated.len(),
+                        etag: version_rec.hashes.known()
+                            .map(|h| hashes::etag(&h, format))
+                            .unwrap_or_default(),
+                        // The hashes are of the original, not of the translation.
+                        hashes: None,
+                        ..Default::default()
+                    }));
+                }
                 let mut f = OpenOptions::new()
                     .write(false)
                     .read(true)
@@ -859,17 +2659,25 @@ impl Storage for DatabaseStorage {
                 }
                 let alloc_size: usize = min(req_length as usize, MAX_READ_SIZE);
                 let mut data = Vec::with_capacity(alloc_size);
-                // We have to set the length to the capacity because the read function
-                // uses the length to determine how many bytes to read, not the capacity.
-                unsafe {
-                    data.set_len(alloc_size);
-                }
-                let bytes_read = f.read(&mut data).await?;
-                // Then we truncate it to the actual number of bytes read.
-                data.truncate(bytes_read);
+                // A single read may return fewer bytes than requested, even
+                // before the end of the file, so we read until it is reached.
+                let bytes_read = f.take(alloc_size as u64).read_to_end(&mut data).await?;
+                let more = if known_size {
+                    req.offset + (bytes_read as u64) < version_rec.length
+                } else {
+                    bytes_read == alloc_size
+                };
                 Ok(tonic::Response::new(DownloadResult {
                     data,
-                    more: bytes_read == alloc_size,
+                    version: Some(FileVersion {
+                        major: requested_version,
+                        minor: 0,
+                    }),
+                    more,
+                    etag: version_rec.hashes.known()
+                        .map(|h| hashes::etag(&h, format))
+                        .unwrap_or_default(),
+                    hashes: version_rec.hashes.known(),
                     ..Default::default()
                 }))
             },
@@ -885,8 +2693,11 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
+        if !req.continuation.is_empty() {
+            return self.delete_upload(req).await;
+        }
         let target = req.target.as_ref().unwrap();
-        let mut fullpath = target.path.clone();
+        let mut fullpath = validate_path(&target.path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -911,13 +2722,21 @@ impl Storage for DatabaseStorage {
         let file_rec_guard = maybe_file_rec.unwrap();
         let file_rec_bytes = file_rec_guard.value();
         let file_rec = fs_record_from_bytes(&file_rec_bytes);
+        drop(file_rec_guard);
+        drop(fs);
         if target.version.as_ref().is_some_and(|v| v.major != file_rec.latest_version) {
             return Err(tonic::Status::invalid_argument("not deleting the latest version"));
         }
+        {
+            let mut attrs = w.open_table(ATTRS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to attrs table"))?;
+            delete_attrs_of(file_rec.id, &mut attrs)?;
+        }
+        unindex_attrs_of(&w, file_rec.id)?;
+        fulltext::unindex_document(&w, file_rec.id)?;
+        remove_fs_key(&w, file_rec.id)?;
         if file_rec.r#type != OBJ_TYPE_VERSION_BLOB {
             // TODO: Check if the folder is empty. Recurse if requested.
-            drop(file_rec_guard);
-            drop(fs);
             w.commit()
                 .map_err(|_| tonic::Status::internal("could not delete folder"))?;
             // Only files have versions.
@@ -929,6 +2748,7 @@ impl Storage for DatabaseStorage {
         let mut v = w.open_table(VER_TABLE)
             .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
         let mut latest_version = file_rec.latest_version;
+        let mut blob_paths: Vec<PathBuf> = Vec::new();
 
         // Pay close attention: this loop is constructed to not underflow the latest_version variable.
         loop {
@@ -948,8 +2768,7 @@ impl Storage for DatabaseStorage {
                     };
                     let mut blob_path = self.blobs_path.clone();
                     blob_path.push(blob_name);
-                    remove_file(&blob_path).await?;
-                    // There does not seem to be a good async shredding library for Rust anywhere.
+                    blob_paths.push(blob_path);
                 }
             }
             if latest_version == 0 {
@@ -957,6 +2776,14 @@ impl Storage for DatabaseStorage {
             }
             latest_version -= 1;
         }
+        drop(v);
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not delete file"))?;
+        // The blobs are only removed once the records that refer to them are gone.
+        for blob_path in blob_paths {
+            remove_file(&blob_path).await?;
+            // There does not seem to be a good async shredding library for Rust anywhere.
+        }
 
         Ok(tonic::Response::new(DeleteResult {
             shredded: false, // TODO: Implement shredding.
@@ -973,7 +2800,7 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
-        let fullpath = req.target.unwrap().path;
+        let fullpath = validate_path(&req.target.unwrap().path)?;
         // let fullpath = strs_to_path(&req.target.unwrap().path);
         let r = self.db.begin_read()
             .map_err(|_| tonic::Status::internal("could not read from database"))?;
@@ -1007,6 +2834,12 @@ impl Storage for DatabaseStorage {
                 return Err(tonic::Status::invalid_argument("cannot list under non-folder"));
             }
         }
+        let attrs_table = if req.attrs {
+            Some(r.open_table(ATTRS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from attrs table"))?)
+        } else {
+            None
+        };
         let next_id = parent_id + 1;
         let mut entries: Vec<ListEntry> = Vec::new();
         let fs_iter = fs.range(parent_id.to_be_bytes().as_slice()..next_id.to_be_bytes().as_slice())
@@ -1060,6 +2893,10 @@ impl Storage for DatabaseStorage {
                 // size: if obj_type != OBJ_TYPE_FOLDER { record.known_size() } else { None },
                 // entries: if obj_type == OBJ_TYPE_FOLDER { record.known_size() } else { None },
                 // storage_tier_id: record.storage_tier as u32, // Not supported in this driver.
+                other_metadata: match attrs_table.as_ref() {
+                    Some(attrs_table) => get_version_attrs_of(record.id, record.latest_version, attrs_table)?,
+                    None => HashMap::new(),
+                },
                 ..Default::default()
             };
             entries.push(ListEntry {
@@ -1083,12 +2920,13 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
+        req.destination = validate_path(&req.destination)?;
         let dest_file_name = match req.destination.pop() {
             Some(f) => f,
             None => return Err(tonic::Status::invalid_argument("destination is required")),
         };
         let target = req.target.as_ref().unwrap();
-        let mut fullpath = target.path.clone();
+        let mut fullpath = validate_path(&target.path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -1126,6 +2964,7 @@ impl Storage for DatabaseStorage {
             if write_result.is_some() {
                 return Err(tonic::Status::invalid_argument("destination file already exists"));
             }
+            set_fs_key(&w, file_rec.id, &dest_file_key)?;
         }
         w.commit()
             .map_err(|_| tonic::Status::internal("could not commit changes"))?;
@@ -1146,12 +2985,13 @@ impl Storage for DatabaseStorage {
         if req.target.is_none() {
             return Err(tonic::Status::invalid_argument("target is required"));
         }
+        req.destination = validate_path(&req.destination)?;
         let dest_file_name = match req.destination.pop() {
             Some(f) => f,
             None => return Err(tonic::Status::invalid_argument("destination is required")),
         };
         let target = req.target.as_ref().unwrap();
-        let mut fullpath = target.path.clone();
+        let mut fullpath = validate_path(&target.path)?;
         if fullpath.len() == 0 {
             return Err(tonic::Status::invalid_argument("target may not be empty"));
         }
@@ -1226,6 +3066,14 @@ impl Storage for DatabaseStorage {
             if write_result.is_some() {
                 return Err(tonic::Status::invalid_argument("destination file already exists"));
             }
+            {
+                let mut attrs = w.open_table(ATTRS_TABLE)
+                    .map_err(|_| tonic::Status::internal("could not write to attrs table"))?;
+                copy_attrs_of(file_rec.id, new_rec.id, &mut attrs)?;
+            }
+            set_fs_key(&w, new_rec.id, &dest_file_key)?;
+            index_attrs_of(&w, new_rec.id, new_rec.latest_version)?;
+            fulltext::copy_document(&w, file_rec.id, new_rec.id)?;
         }
         w.commit()
             .map_err(|_| tonic::Status::internal("could not commit changes"))?;
@@ -1238,7 +3086,92 @@ impl Storage for DatabaseStorage {
         &self,
         request: tonic::Request<ListIncompleteUploadsArg>,
     ) -> std::result::Result<tonic::Response<ListIncompleteUploadsResult>, tonic::Status> {
-        unimplemented!()
+        let req = request.into_inner();
+        let limit = match req.limit {
+            0 => DEFAULT_UPLOADS_LIMIT,
+            limit => min(limit as usize, MAX_UPLOADS_LIMIT),
+        };
+        let subtree = validate_path(&req.subtree)?;
+        let r = self.db.begin_read()
+            .map_err(|_| tonic::Status::internal("could not read from database"))?;
+        let uploads = r.open_table(UPLOADS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+        let ranges = r.open_table(UPLOAD_RANGES_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?;
+        let upload_parts = r.open_table(UPLOAD_PARTS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from upload parts table"))?;
+        // Uploads are listed in the order in which they were begun.
+        let mut found: Vec<(u128, UploadRecordValue, Vec<String>, u64, u32)> = Vec::new();
+        let mut skipped: u32 = 0;
+        for entry in uploads.iter().map_err(|_| tonic::Status::internal("could not read from uploads table"))? {
+            let (k, v) = entry.map_err(|_| tonic::Status::internal("could not read from uploads table"))?;
+            let ulid = k.value();
+            let (record, path) = upload_record_from_bytes(v.value())?;
+            let under_subtree = path.len() > subtree.len()
+                && path.iter().zip(subtree.iter()).all(|(pc, spc)| pc.nfkd().eq(spc.chars()));
+            if !under_subtree {
+                continue;
+            }
+            if skipped < req.skip {
+                skipped += 1;
+                continue;
+            }
+            if found.len() >= limit {
+                break;
+            }
+            let mut received: u64 = 0;
+            if record.length != UNKNOWN_SIZE {
+                for range in ranges.range((ulid, 0)..=(ulid, u64::MAX))
+                    .map_err(|_| tonic::Status::internal("could not read from upload ranges table"))? {
+                    let (start, end) = range.map_err(|_| tonic::Status::internal("could not read from upload ranges table"))?;
+                    received += end.value() - start.value().1;
+                }
+            }
+            let mut parts: u32 = 0;
+            for part in upload_parts.range((ulid, 0)..=(ulid, u32::MAX))
+                .map_err(|_| tonic::Status::internal("could not read from upload parts table"))? {
+                let (_, part) = part.map_err(|_| tonic::Status::internal("could not read from upload parts table"))?;
+                let part: UploadPartValue = bytemuck::pod_read_unaligned(part.value());
+                received += part.length;
+                parts += 1;
+            }
+            found.push((ulid, record, path, received, parts));
+        }
+        drop(uploads);
+        drop(ranges);
+        drop(upload_parts);
+        drop(r);
+
+        let mut entries: Vec<IncompleteUpload> = Vec::with_capacity(found.len());
+        for (ulid, record, path, mut received, parts) in found {
+            if record.length == UNKNOWN_SIZE {
+                // Uploads that are not reserved are appended to their blob.
+                received += match metadata(self.upload_blob_path(ulid)).await {
+                    Ok(m) => m.len(),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                    Err(e) => return Err(e.into()),
+                };
+            }
+            entries.push(IncompleteUpload {
+                path,
+                attrs: Some(FsAttributes {
+                    uid: Some(record.uid),
+                    gid: Some(record.gid),
+                    perms: Some(u16_to_unix_perms(record.flags)),
+                    create_time: record.create_time.known().map(|t| t.into()),
+                    modify_time: record.modify_time.known().map(|t| t.into()),
+                    size: Some(received),
+                    ..Default::default()
+                }),
+                continuation: Ulid(ulid).to_bytes().to_vec(),
+                reserved: if record.length == UNKNOWN_SIZE { 0 } else { record.length },
+                parts,
+            });
+        }
+        Ok(tonic::Response::new(ListIncompleteUploadsResult {
+            entries,
+            ..Default::default()
+        }))
     }
 
     // TODO: I have to determine what the syntax of the URL is.
@@ -1268,20 +3201,208 @@ impl Storage for DatabaseStorage {
         &self,
         request: tonic::Request<GetAttributesArg>,
     ) -> std::result::Result<tonic::Response<GetAttributesResult>, tonic::Status> {
-        // https://doc.rust-lang.org/nightly/std/fs/struct.Metadata.html
-        // https://doc.rust-lang.org/nightly/std/fs/fn.symlink_metadata.html
-        unimplemented!()
+        let req = request.into_inner();
+        let target = match req.target {
+            Some(target) => target,
+            None => return Err(tonic::Status::invalid_argument("target is required")),
+        };
+        let r = self.db.begin_read()
+            .map_err(|_| tonic::Status::internal("could not read from database"))?;
+        let fs = r.open_table(FS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+        let v = r.open_table(VER_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+        let attrs = r.open_table(ATTRS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+        if target.path.is_empty() {
+            // The root folder has no record, so only its contents are known.
+            return Ok(tonic::Response::new(GetAttributesResult {
+                attrs: Some(FsAttributes {
+                    r#type: Some(ObjectType::Folder.into()),
+                    inode: Some(ROOT_FSID),
+                    entries: Some(count_entries(ROOT_FSID, &fs)?),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+        let record = match lookup_path(&validate_path(&target.path)?, &fs)? {
+            Some((_, value)) => fs_record_from_bytes(&value),
+            None => return Err(tonic::Status::invalid_argument("no such file")),
+        };
+        let requested_version = target.version.as_ref()
+            .map(|v| v.major)
+            .unwrap_or(record.latest_version);
+        let version_key = VersionRecordKey {
+            file_id: record.id,
+            version: requested_version,
+        };
+        let maybe_version_rec = v.get(bytemuck::bytes_of(&version_key))
+            .map_err(|_| tonic::Status::internal("failed to read requested version"))?;
+        let version = match maybe_version_rec {
+            Some(value) if value.value().len() < mem::size_of::<VersionRecordValue>() => {
+                return Err(tonic::Status::internal("corrupted version record"));
+            },
+            Some(value) => Some(version_value_from_bytes(value.value())),
+            None if requested_version != record.latest_version => {
+                return Err(tonic::Status::invalid_argument("no such version"));
+            },
+            None => None,
+        };
+        let content_hashes = version.and_then(|v| v.hashes.known());
+        Ok(tonic::Response::new(GetAttributesResult {
+            attrs: Some(object_attributes(&record, requested_version, version, &fs, &attrs)?),
+            version: Some(FileVersion {
+                major: requested_version,
+                minor: 0,
+            }),
+            etag: content_hashes.as_ref()
+                .map(|h| hashes::etag(h, DocumentFormat::Original))
+                .unwrap_or_default(),
+            hashes: content_hashes,
+            ..Default::default()
+        }))
     }
 
+    // Like chmod, chown, and utimes, this changes the ownership, permissions,
+    // and times of the latest version. Labels are set on the requested version,
+    // or on the object as a whole if none is requested. All changes update the
+    // change time, and are made atomically.
     async fn set_attributes(
         &self,
         request: tonic::Request<SetAttributesArg>,
     ) -> std::result::Result<tonic::Response<SetAttributesResult>, tonic::Status> {
-        // https://doc.rust-lang.org/nightly/std/fs/fn.set_permissions.html
-        // https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.set_modified
-        // https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.set_times
-        // https://doc.rust-lang.org/nightly/std/fs/struct.File.html#method.set_permissions
-        unimplemented!()
+        let req = request.into_inner();
+        let target = match req.target {
+            Some(target) => target,
+            None => return Err(tonic::Status::invalid_argument("target is required")),
+        };
+        if target.path.is_empty() {
+            return Err(tonic::Status::invalid_argument("cannot set attributes of the root folder"));
+        }
+        let new_attrs = req.attrs.unwrap_or_default();
+        let read_only = new_attrs.r#type.is_some()
+            || new_attrs.create_time.is_some()
+            || new_attrs.change_time.is_some()
+            || new_attrs.delete_time.is_some()
+            || new_attrs.size.is_some()
+            || new_attrs.dev.is_some()
+            || new_attrs.hardlinks.is_some()
+            || new_attrs.inode.is_some()
+            || new_attrs.block_size.is_some()
+            || new_attrs.block_count.is_some()
+            || new_attrs.source.is_some()
+            || new_attrs.entries.is_some()
+            || new_attrs.storage_tier_id != 0;
+        if read_only {
+            return Err(tonic::Status::invalid_argument("only ownership, permissions, times, and labels may be set"));
+        }
+        if new_attrs.other_metadata.keys().any(|name| name.is_empty()) {
+            return Err(tonic::Status::invalid_argument("label names may not be empty"));
+        }
+        let changes_inode = new_attrs.uid.is_some()
+            || new_attrs.gid.is_some()
+            || new_attrs.perms.is_some()
+            || new_attrs.access_time.is_some()
+            || new_attrs.modify_time.is_some();
+        let access_time = new_attrs.access_time.as_ref().map(to_time64).transpose()?;
+        let modify_time = new_attrs.modify_time.as_ref().map(to_time64).transpose()?;
+        let w = self.db.begin_write()
+            .map_err(|_| tonic::Status::internal("could not write to database"))?;
+        let ret = {
+            let mut fs = w.open_table(FS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+            let mut v = w.open_table(VER_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to versions table"))?;
+            let mut attrs = w.open_table(ATTRS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not write to attrs table"))?;
+            let (key, mut value) = match lookup_path(&validate_path(&target.path)?, &fs)? {
+                Some(found) => found,
+                None => return Err(tonic::Status::invalid_argument("no such file")),
+            };
+            let mut record = fs_record_from_bytes(&value);
+            let requested_version = target.version.as_ref().map(|v| v.major);
+            if changes_inode && requested_version.is_some_and(|v| v != record.latest_version) {
+                return Err(tonic::Status::invalid_argument("not changing the latest version"));
+            }
+            let version_number = requested_version.unwrap_or(record.latest_version);
+            let version_key = VersionRecordKey {
+                file_id: record.id,
+                version: version_number,
+            };
+            let maybe_version_value = v.get(bytemuck::bytes_of(&version_key))
+                .map_err(|_| tonic::Status::internal("could not read from versions table"))?
+                .map(|existing| existing.value().to_vec());
+            let mut version_value = match maybe_version_value {
+                Some(existing) => existing,
+                None if requested_version.is_some() => {
+                    return Err(tonic::Status::invalid_argument("no such version"));
+                },
+                None => bytemuck::bytes_of(&VersionRecordValue {
+                    create_time: Time64::now(),
+                    access_time: TIME64_UNKNOWN_TIME,
+                    length: 0,
+                    uid: 0,
+                    gid: 0,
+                    flags: 0o755,
+                    storage_tier: DEFAULT_STORAGE_TIER,
+                    other: 0,
+                    hashes: HashesValue::zeroed(),
+                }).to_vec(),
+            };
+            if version_value.len() < mem::size_of::<VersionRecordValue>() {
+                return Err(tonic::Status::internal("corrupted version record"));
+            }
+            let mut version = version_value_from_bytes(&version_value);
+            if let Some(uid) = new_attrs.uid {
+                version.uid = uid;
+            }
+            if let Some(gid) = new_attrs.gid {
+                version.gid = gid;
+            }
+            if let Some(perms) = new_attrs.perms.as_ref() {
+                version.flags = unix_perms_to_u16(perms);
+            }
+            if let Some(access_time) = access_time {
+                record.access_time = access_time;
+            }
+            if let Some(modify_time) = modify_time {
+                record.modify_time = modify_time;
+            }
+            record.change_time = Time64::now();
+            for (name, label) in new_attrs.other_metadata.iter() {
+                let attr_key = match requested_version {
+                    Some(version_number) => make_version_attr_key(record.id, version_number, name),
+                    None => make_attr_key(record.id, name),
+                };
+                if label.type_url.is_empty() {
+                    attrs.remove(attr_key.as_str())
+                        .map_err(|_| tonic::Status::internal("could not delete attribute"))?;
+                } else {
+                    attrs.insert(attr_key.as_str(), label.encode_to_vec().as_slice())
+                        .map_err(|_| tonic::Status::internal("could not write to attrs table"))?;
+                }
+            }
+            if changes_inode {
+                // The variable-length part of the record is left as it was.
+                version_value[0..mem::size_of::<VersionRecordValue>()].copy_from_slice(bytemuck::bytes_of(&version));
+                v.insert(bytemuck::bytes_of(&version_key), version_value.as_slice())
+                    .map_err(|_| tonic::Status::internal("could not write to versions table"))?;
+            }
+            value[0..mem::size_of::<FsRecordValue>()].copy_from_slice(bytemuck::bytes_of(&record));
+            fs.insert(key.as_slice(), value.as_slice())
+                .map_err(|_| tonic::Status::internal("could not write to fs table"))?;
+            let ret = object_attributes(&record, version_number, Some(version), &fs, &attrs)?;
+            drop(attrs);
+            index_attrs_of(&w, record.id, record.latest_version)?;
+            ret
+        };
+        w.commit()
+            .map_err(|_| tonic::Status::internal("could not commit changes"))?;
+        Ok(tonic::Response::new(SetAttributesResult {
+            attrs: Some(ret),
+            ..Default::default()
+        }))
     }
 
     async fn delete_many(
@@ -1341,4 +3462,399 @@ impl Storage for DatabaseStorage {
     ) -> std::result::Result<tonic::Response<UnlinkResult>, tonic::Status> {
         unimplemented!()
     }
+
+    // Label searches scan the index for the first predicate, full-text searches
+    // rank every file containing any of the words of the query, and name
+    // searches test every object. The other predicates, the subtree, and the
+    // name filter and query are then tested against each match.
+    async fn search(
+        &self,
+        request: tonic::Request<SearchArg>,
+    ) -> std::result::Result<tonic::Response<SearchResult>, tonic::Status> {
+        let req = request.into_inner();
+        let terms = fulltext::query_terms(&req.text);
+        if !req.text.is_empty() && terms.is_empty() {
+            return Err(tonic::Status::invalid_argument("text contains no searchable words"));
+        }
+        let predicates = req.predicates.iter()
+            .map(IndexPredicate::compile)
+            .collect::<std::result::Result<Vec<IndexPredicate>, tonic::Status>>()?;
+        let subtree = req.subtree.unwrap_or_default();
+        let base = subtree.base.iter()
+            .map(|pc| String::from_utf8(pc.clone()))
+            .collect::<std::result::Result<Vec<String>, _>>()
+            .map_err(|_| tonic::Status::invalid_argument("subtree base must be UTF-8"))?;
+        let base = validate_path(&base)?;
+        let name_query = if req.name.is_empty() {
+            None
+        } else {
+            Some(fuzzy::NameQuery::new(&req.name, req.max_edits)?)
+        };
+        if predicates.is_empty() && terms.is_empty() && name_query.is_none() && subtree.name_filter.is_none() {
+            return Err(tonic::Status::invalid_argument("text, a name, or at least one predicate is required"));
+        }
+        let limit = match req.limit {
+            0 => DEFAULT_SEARCH_LIMIT,
+            limit => min(limit as usize, MAX_SEARCH_LIMIT),
+        };
+        let r = self.db.begin_read()
+            .map_err(|_| tonic::Status::internal("could not read from database"))?;
+        let fs = r.open_table(FS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+        let v = r.open_table(VER_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+        let attrs = r.open_table(ATTRS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from attrs table"))?;
+        let fs_keys = r.open_table(FS_KEYS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from fs keys table"))?;
+        let index = r.open_table(ATTR_INDEX_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+        let by_id = r.open_table(ATTR_INDEX_BY_ID_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+        let base_id = if base.is_empty() {
+            ROOT_FSID
+        } else {
+            match lookup_path(&base, &fs)? {
+                Some((_, value)) => fs_record_from_bytes(&value).id,
+                None => return Err(tonic::Status::invalid_argument("no such file")),
+            }
+        };
+
+        // Tests the given predicates, the subtree, and the name filter and query
+        // against a candidate, returning its record, its path, and how well its
+        // name matches the query if it matches.
+        let admit = |id: FileSystemId, predicates: &[IndexPredicate]| -> std::result::Result<Option<(FsRecordValue, Vec<String>, f64)>, tonic::Status> {
+            for predicate in predicates.iter() {
+                let value = by_id.get((id, predicate.name.as_str()))
+                    .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+                if !value.is_some_and(|value| predicate.matches(value.value())) {
+                    return Ok(None);
+                }
+            }
+            let (record, ancestry) = match resolve_ancestry(id, &fs_keys, &fs)? {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+            let depth = if base_id == ROOT_FSID {
+                ancestry.len()
+            } else {
+                match ancestry.iter().position(|(ancestor_id, _)| *ancestor_id == base_id) {
+                    Some(i) => ancestry.len() - 1 - i,
+                    None => return Ok(None),
+                }
+            };
+            if depth < subtree.minimum as usize || (subtree.maximum > 0 && depth > subtree.maximum as usize) {
+                return Ok(None);
+            }
+            let base_name = ancestry.last().map(|(_, name)| name.as_str()).unwrap_or_default();
+            if subtree.name_filter.as_ref().is_some_and(|filter| !matches_name_filter(filter, base_name.as_bytes())) {
+                return Ok(None);
+            }
+            let name_score = match &name_query {
+                Some(query) => match query.score(base_name) {
+                    Some(score) => score,
+                    None => return Ok(None),
+                },
+                None => 0.0,
+            };
+            Ok(Some((record, ancestry.into_iter().map(|(_, name)| name).collect(), name_score)))
+        };
+
+        let mut hits: Vec<(FsRecordValue, Vec<String>, f64)> = Vec::new();
+        let mut continuation: Vec<u8> = Vec::new();
+        if terms.is_empty() && !predicates.is_empty() {
+            let driver = &predicates[0];
+            // The continuation is the ID of the last result, from which its
+            // value is looked up again, so that the token does not reveal the
+            // value of a label of an object that the user may not see.
+            let (start_value, start_id) = if req.continuation.is_empty() {
+                (driver.start().to_vec(), 0)
+            } else {
+                let last_id: [u8; 8] = req.continuation.as_slice().try_into()
+                    .map_err(|_| tonic::Status::invalid_argument("invalid continuation token"))?;
+                let last_id = u64::from_be_bytes(last_id);
+                let last_value = by_id.get((last_id, driver.name.as_str()))
+                    .map_err(|_| tonic::Status::internal("could not read from attr index table"))?
+                    .map(|v| v.value().to_vec())
+                    .ok_or_else(|| tonic::Status::failed_precondition("the last result no longer has the label"))?;
+                let start_id = last_id.checked_add(1)
+                    .ok_or_else(|| tonic::Status::invalid_argument("invalid continuation token"))?;
+                (last_value, start_id)
+            };
+            let iter = index.range((driver.name.as_str(), start_value.as_slice(), start_id)..)
+                .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+            for entry in iter {
+                let (key, _) = entry
+                    .map_err(|_| tonic::Status::internal("could not read from attr index table"))?;
+                let (name, value, id) = key.value();
+                if name != driver.name || driver.is_above(value) {
+                    break;
+                }
+                if !driver.matches(value) {
+                    continue;
+                }
+                let (record, path, name_score) = match admit(id, &predicates[1..])? {
+                    Some(found) => found,
+                    None => continue,
+                };
+                hits.push((record, path, name_score));
+                if hits.len() >= limit {
+                    continuation = id.to_be_bytes().to_vec();
+                    break;
+                }
+            }
+        } else if !terms.is_empty() {
+            // Scores change as files are indexed, so the continuation is just
+            // the number of matches already returned.
+            let skip = decode_skip_continuation(&req.continuation)?;
+            let mut admitted: u64 = 0;
+            for (id, score) in fulltext::rank(&r, &terms)? {
+                let (record, path, _) = match admit(id, &predicates)? {
+                    Some(found) => found,
+                    None => continue,
+                };
+                admitted += 1;
+                if admitted <= skip {
+                    continue;
+                }
+                hits.push((record, path, score));
+                if hits.len() >= limit {
+                    continuation = admitted.to_be_bytes().to_vec();
+                    break;
+                }
+            }
+        } else {
+            let skip = decode_skip_continuation(&req.continuation)?;
+            let mut matches: Vec<(FsRecordValue, Vec<String>, f64)> = Vec::new();
+            let iter = fs_keys.iter()
+                .map_err(|_| tonic::Status::internal("could not read from fs keys table"))?;
+            for entry in iter {
+                let (id, key) = entry
+                    .map_err(|_| tonic::Status::internal("could not read from fs keys table"))?;
+                // Most names will not match, so they are tested before the
+                // path of the object is resolved.
+                let name = key.value().get(mem::size_of::<FileSystemId>()..).unwrap_or_default();
+                if subtree.name_filter.as_ref().is_some_and(|filter| !matches_name_filter(filter, name)) {
+                    continue;
+                }
+                if name_query.as_ref().is_some_and(|query| query.score(&String::from_utf8_lossy(name)).is_none()) {
+                    continue;
+                }
+                if let Some(found) = admit(id.value(), &[])? {
+                    matches.push(found);
+                }
+            }
+            matches.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+            let total = matches.len();
+            let skip = min(skip, total as u64) as usize;
+            hits = matches.into_iter().skip(skip).take(limit).collect();
+            if skip + hits.len() < total {
+                continuation = ((skip + hits.len()) as u64).to_be_bytes().to_vec();
+            }
+        }
+
+        let mut entries: Vec<SearchEntry> = Vec::with_capacity(hits.len());
+        for (record, path, score) in hits {
+            let entry_attrs = if req.attrs {
+                let version = get_version_record(&record, &v)?;
+                Some(object_attributes(&record, record.latest_version, version, &fs, &attrs)?)
+            } else {
+                None
+            };
+            let mut snippets: Vec<String> = Vec::new();
+            if !terms.is_empty() {
+                if let Some(blob_name) = get_version_blob_name(&record, &v)? {
+                    let mut blob_path = self.blobs_path.clone();
+                    blob_path.push(blob_name);
+                    let file_name = path.last().map(|name| name.as_str()).unwrap_or_default();
+                    // A blob that cannot be read just has no snippets.
+                    if let Ok(Some(text)) = read_blob_text(file_name, &blob_path).await {
+                        snippets = fulltext::make_snippets(&text, &terms);
+                    }
+                }
+            }
+            entries.push(SearchEntry {
+                path,
+                attrs: entry_attrs,
+                score,
+                snippets,
+            });
+        }
+        Ok(tonic::Response::new(SearchResult {
+            entries,
+            continuation,
+            ..Default::default()
+        }))
+    }
+
+    async fn query(
+        &self,
+        request: tonic::Request<QueryArg>,
+    ) -> std::result::Result<tonic::Response<QueryResult>, tonic::Status> {
+        let req = request.into_inner();
+        let target = req.target
+            .ok_or_else(|| tonic::Status::invalid_argument("target is required"))?;
+        let query = req.query
+            .ok_or_else(|| tonic::Status::invalid_argument("query is required"))?;
+        let limit = match req.limit {
+            0 => DEFAULT_QUERY_LIMIT,
+            limit => min(limit as usize, MAX_QUERY_LIMIT),
+        };
+        let (blob_path, length) = {
+            let r = self.db.begin_read()
+                .map_err(|_| tonic::Status::internal("could not read from database"))?;
+            let fs = r.open_table(FS_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from fs table"))?;
+            let v = r.open_table(VER_TABLE)
+                .map_err(|_| tonic::Status::internal("could not read from versions table"))?;
+            let file_rec = match lookup_path(&validate_path(&target.path)?, &fs)? {
+                Some((_, value)) => fs_record_from_bytes(&value),
+                None => return Err(tonic::Status::invalid_argument("no such file")),
+            };
+            let is_blob = matches!(
+                file_rec.r#type,
+                OBJ_TYPE_NORMAL_BLOB | OBJ_TYPE_VERSION_BLOB | OBJ_TYPE_APPEND_BLOB | OBJ_TYPE_BLOCK_BLOB
+            );
+            if !is_blob {
+                return Ok(tonic::Response::new(QueryResult {
+                    err: Some(FileSystemError::invalid_operand_type()),
+                    ..Default::default()
+                }));
+            }
+            let version_key = VersionRecordKey {
+                file_id: file_rec.id,
+                version: target.version.as_ref().map(|v| v.major).unwrap_or(file_rec.latest_version),
+            };
+            let version_rec = v.get(bytemuck::bytes_of(&version_key))
+                .map_err(|_| tonic::Status::internal("could not read from versions table"))?
+                .ok_or_else(|| tonic::Status::invalid_argument("no such version"))?;
+            let version_blob = version_rec.value()
+                .get(mem::size_of::<VersionRecordValue>()..)
+                .and_then(|blob| std::str::from_utf8(blob).ok())
+                .ok_or_else(|| tonic::Status::internal("corrupted version record"))?;
+            let mut blob_path = self.blobs_path.clone();
+            blob_path.push(version_blob);
+            let length = version_value_from_bytes(version_rec.value()).length;
+            (blob_path, Some(length).filter(|length| *length != UNKNOWN_SIZE))
+        };
+        let found = match query {
+            query_arg::Query::JsonPath(expr) => match read_whole_blob(&blob_path, length, MAX_QUERY_SIZE).await? {
+                Some(data) => query::query_json_path(&data, &expr, limit)?,
+                None => return Ok(tonic::Response::new(QueryResult {
+                    err: Some(FileSystemError::too_large()),
+                    ..Default::default()
+                })),
+            },
+            query_arg::Query::XPath(expr) => match read_whole_blob(&blob_path, length, MAX_QUERY_SIZE).await? {
+                Some(data) => query::query_xpath(&data, &expr, limit)?,
+                None => return Ok(tonic::Response::new(QueryResult {
+                    err: Some(FileSystemError::too_large()),
+                    ..Default::default()
+                })),
+            },
+            query_arg::Query::Csv(csv_query) => {
+                let delimiter = match csv_query.delimiter.as_bytes() {
+                    [] => b',',
+                    [d] if d.is_ascii() => *d,
+                    _ => return Err(tonic::Status::invalid_argument("delimiter must be a single ASCII character")),
+                };
+                let has_header = !csv_query.no_header;
+                // The CSV parser is synchronous, and the file may be large.
+                let shape = tokio::task::spawn_blocking(move || {
+                    let f = std::fs::File::open(&blob_path)?;
+                    query::csv_shape(std::io::Read::take(f, length.unwrap_or(u64::MAX)), delimiter, has_header)
+                })
+                    .await
+                    .map_err(|_| tonic::Status::internal("could not read file"))??;
+                return Ok(tonic::Response::new(match shape {
+                    Some(shape) => QueryResult {
+                        csv: Some(shape),
+                        ..Default::default()
+                    },
+                    None => QueryResult {
+                        err: Some(FileSystemError::invalid_operand_type()),
+                        ..Default::default()
+                    },
+                }));
+            },
+        };
+        Ok(tonic::Response::new(match found {
+            Some((matches, more)) => QueryResult {
+                matches,
+                more,
+                ..Default::default()
+            },
+            None => QueryResult {
+                err: Some(FileSystemError::invalid_operand_type()),
+                ..Default::default()
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redb::backends::InMemoryBackend;
+
+    const ULID: u128 = 1;
+
+    fn in_memory_db () -> Database {
+        Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .expect("failed to create in-memory database")
+    }
+
+    fn ranges_of (w: &WriteTransaction<'_>) -> Vec<(u64, u64)> {
+        let ranges = w.open_table(UPLOAD_RANGES_TABLE).unwrap();
+        let ret = ranges.range((ULID, 0)..=(ULID, u64::MAX)).unwrap()
+            .map(|entry| {
+                let (k, v) = entry.unwrap();
+                (k.value().1, v.value())
+            })
+            .collect();
+        ret
+    }
+
+    #[test]
+    fn adjacent_ranges_merge_on_both_sides () {
+        let db = in_memory_db();
+        let w = db.begin_write().unwrap();
+        assert!(add_upload_range(&w, ULID, 0, 10).unwrap());
+        assert!(add_upload_range(&w, ULID, 20, 30).unwrap());
+        assert_eq!(ranges_of(&w), vec![(0, 10), (20, 30)]);
+        assert!(add_upload_range(&w, ULID, 10, 20).unwrap());
+        assert_eq!(ranges_of(&w), vec![(0, 30)]);
+    }
+
+    #[test]
+    fn overlap_with_earlier_range_is_rejected () {
+        let db = in_memory_db();
+        let w = db.begin_write().unwrap();
+        assert!(add_upload_range(&w, ULID, 0, 10).unwrap());
+        assert!(!add_upload_range(&w, ULID, 5, 15).unwrap());
+        assert_eq!(ranges_of(&w), vec![(0, 10)]);
+    }
+
+    #[test]
+    fn exact_refill_is_rejected () {
+        let db = in_memory_db();
+        let w = db.begin_write().unwrap();
+        assert!(add_upload_range(&w, ULID, 10, 20).unwrap());
+        assert!(!add_upload_range(&w, ULID, 10, 20).unwrap());
+        assert_eq!(ranges_of(&w), vec![(10, 20)]);
+    }
+
+    #[test]
+    fn fully_patched_reservation_is_filled () {
+        let db = in_memory_db();
+        let w = db.begin_write().unwrap();
+        assert!(add_upload_range(&w, ULID, 20, 30).unwrap());
+        assert!(!is_upload_filled(&w, ULID, 30).unwrap());
+        assert!(add_upload_range(&w, ULID, 0, 10).unwrap());
+        assert!(!is_upload_filled(&w, ULID, 30).unwrap());
+        assert!(add_upload_range(&w, ULID, 10, 20).unwrap());
+        assert!(is_upload_filled(&w, ULID, 30).unwrap());
+        assert!(!is_upload_filled(&w, ULID, 31).unwrap());
+    }
 }
@@ -0,0 +1,369 @@
+use std::cmp::min;
+use std::collections::HashMap;
+use redb::{ReadTransaction, ReadableTable, TableDefinition, WriteTransaction};
+use unicode_normalization::UnicodeNormalization;
+
+// This is where postings are stored, keyed by term, then the ID of the object
+// in which it occurs, with the number of occurrences as the value.
+const POSTINGS_TABLE: TableDefinition<(&str, u64), u32> = TableDefinition::new("text_postings");
+
+// This is the same as the postings table, but keyed by the ID of the object
+// first, so that the postings of an object can be found and removed.
+const TERMS_TABLE: TableDefinition<(u64, &str), u32> = TableDefinition::new("text_terms");
+
+// This is where the number of terms in each indexed object is stored.
+const DOCS_TABLE: TableDefinition<u64, u64> = TableDefinition::new("text_docs");
+
+// This is where the totals used for ranking are stored, keyed by `DOCS_STAT`
+// and `TERMS_STAT`.
+const STATS_TABLE: TableDefinition<&str, u64> = TableDefinition::new("text_stats");
+
+const DOCS_STAT: &str = "docs";
+const TERMS_STAT: &str = "terms";
+
+/// Only this much of the start of a file is indexed.
+pub const MAX_INDEXED_SIZE: usize = 8 * 1024 * 1024;
+
+/// Longer terms, which are probably not words, are not indexed.
+const MAX_TERM_LENGTH: usize = 64;
+
+/// The number of snippets returned for each result.
+const MAX_SNIPPETS: usize = 3;
+
+/// The number of bytes of context on either side of a term in a snippet.
+const SNIPPET_CONTEXT: usize = 60;
+
+// Parameters of the Okapi BM25 ranking function.
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+pub fn create_tables (w: &WriteTransaction<'_>) -> std::result::Result<(), redb::Error> {
+    w.open_table(POSTINGS_TABLE)?;
+    w.open_table(TERMS_TABLE)?;
+    w.open_table(DOCS_TABLE)?;
+    w.open_table(STATS_TABLE)?;
+    Ok(())
+}
+
+/// Interprets the start of a file as UTF-8 text, allowing a character to be
+/// cut off at the end, since only the start of large files is read. Files with
+/// null characters are assumed to be binary.
+fn utf8_prefix (data: &[u8]) -> Option<&str> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&data[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    if text.contains('\0') {
+        return None;
+    }
+    Some(text)
+}
+
+fn json_text (value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, v) in map {
+                out.push_str(k);
+                out.push('\n');
+                json_text(v, out);
+            }
+        },
+        serde_json::Value::Array(values) => values.iter().for_each(|v| json_text(v, out)),
+        serde_json::Value::String(s) => {
+            out.push_str(s);
+            out.push('\n');
+        },
+        serde_json::Value::Number(n) => {
+            out.push_str(&n.to_string());
+            out.push('\n');
+        },
+        serde_json::Value::Bool(_) | serde_json::Value::Null => {},
+    }
+}
+
+fn xml_text (doc: &roxmltree::Document) -> String {
+    let mut out = String::new();
+    for node in doc.descendants() {
+        if node.is_text() {
+            out.push_str(node.text().unwrap_or_default().trim());
+            out.push('\n');
+        }
+        for attr in node.attributes() {
+            out.push_str(attr.value());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Removes the destinations of links and images, which are not prose, and
+/// blanks out the punctuation of the other Markdown syntax, so that it does
+/// not clutter snippets.
+fn markdown_text (text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("](") {
+        out.push_str(&rest[..i]);
+        out.push(' ');
+        rest = match rest[i..].find(')') {
+            Some(j) => &rest[i + j + 1..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out.chars()
+        .map(|c| match c {
+            '#' | '*' | '`' | '>' | '[' | ']' | '!' | '~' | '|' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+/// Extracts the text of a file from its contents, based on the extension of
+/// its name. JSON and XML are reduced to their keys, values, text, and
+/// attributes, and Markdown to its prose. CSV and any other UTF-8 text are
+/// used as they are. This returns `None` for files that are not text.
+pub fn extract_text (file_name: &str, data: &[u8]) -> Option<String> {
+    let text = utf8_prefix(data)?;
+    let extension = file_name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    // Files that do not parse, such as those cut off at MAX_INDEXED_SIZE, are
+    // indexed as plain text.
+    let ret = match extension.as_str() {
+        "json" => serde_json::from_str::<serde_json::Value>(text).ok()
+            .map(|value| {
+                let mut out = String::new();
+                json_text(&value, &mut out);
+                out
+            }),
+        "xml" => roxmltree::Document::parse(text).ok().map(|doc| xml_text(&doc)),
+        "md" | "markdown" => Some(markdown_text(text)),
+        _ => None,
+    };
+    Some(ret.unwrap_or_else(|| text.to_owned()))
+}
+
+fn normalize_term (word: &str) -> Option<String> {
+    let term = word.nfkc().flat_map(char::to_lowercase).collect::<String>();
+    if term.len() > MAX_TERM_LENGTH {
+        return None;
+    }
+    Some(term)
+}
+
+/// Splits text into terms, which are runs of alphanumeric characters, in
+/// lowercase, along with their byte offsets and lengths in the text.
+fn tokenize (text: &str) -> Vec<(usize, usize, String)> {
+    let mut ret = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if c.is_alphanumeric() {
+            start.get_or_insert(i);
+        } else if let Some(s) = start.take() {
+            if let Some(term) = normalize_term(&text[s..i]) {
+                ret.push((s, i - s, term));
+            }
+        }
+    }
+    ret
+}
+
+/// The distinct terms of a query.
+pub fn query_terms (query: &str) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    for (_, _, term) in tokenize(query) {
+        if !ret.contains(&term) {
+            ret.push(term);
+        }
+    }
+    ret
+}
+
+fn add_stat (w: &WriteTransaction<'_>, name: &str, delta: i64) -> std::result::Result<(), tonic::Status> {
+    let mut stats = w.open_table(STATS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not write to text stats table"))?;
+    let current = stats.get(name)
+        .map_err(|_| tonic::Status::internal("could not read from text stats table"))?
+        .map(|v| v.value())
+        .unwrap_or(0);
+    stats.insert(name, current.saturating_add_signed(delta))
+        .map_err(|_| tonic::Status::internal("could not write to text stats table"))?;
+    Ok(())
+}
+
+fn insert_document (w: &WriteTransaction<'_>, id: u64, terms: &HashMap<String, u32>, length: u64) -> std::result::Result<(), tonic::Status> {
+    {
+        let mut postings = w.open_table(POSTINGS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text postings table"))?;
+        let mut by_id = w.open_table(TERMS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text terms table"))?;
+        let mut docs = w.open_table(DOCS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text docs table"))?;
+        for (term, count) in terms {
+            postings.insert((term.as_str(), id), count)
+                .map_err(|_| tonic::Status::internal("could not write to text postings table"))?;
+            by_id.insert((id, term.as_str()), count)
+                .map_err(|_| tonic::Status::internal("could not write to text terms table"))?;
+        }
+        docs.insert(id, length)
+            .map_err(|_| tonic::Status::internal("could not write to text docs table"))?;
+    }
+    add_stat(w, DOCS_STAT, 1)?;
+    add_stat(w, TERMS_STAT, length as i64)
+}
+
+/// Removes the text of an object from the index, if it was indexed.
+pub fn unindex_document (w: &WriteTransaction<'_>, id: u64) -> std::result::Result<(), tonic::Status> {
+    let length = {
+        let mut postings = w.open_table(POSTINGS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text postings table"))?;
+        let mut by_id = w.open_table(TERMS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text terms table"))?;
+        let mut docs = w.open_table(DOCS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not write to text docs table"))?;
+        let terms = by_id.range((id, "")..(id + 1, ""))
+            .map_err(|_| tonic::Status::internal("could not read from text terms table"))?
+            .map(|entry| entry.map(|(k, _)| k.value().1.to_string()))
+            .collect::<std::result::Result<Vec<String>, _>>()
+            .map_err(|_| tonic::Status::internal("could not read from text terms table"))?;
+        for term in terms {
+            postings.remove((term.as_str(), id))
+                .map_err(|_| tonic::Status::internal("could not write to text postings table"))?;
+            by_id.remove((id, term.as_str()))
+                .map_err(|_| tonic::Status::internal("could not write to text terms table"))?;
+        }
+        let length = docs.remove(id)
+            .map_err(|_| tonic::Status::internal("could not write to text docs table"))?
+            .map(|v| v.value());
+        length
+    };
+    if let Some(length) = length {
+        add_stat(w, DOCS_STAT, -1)?;
+        add_stat(w, TERMS_STAT, -(length as i64))?;
+    }
+    Ok(())
+}
+
+/// Replaces the indexed text of an object. If `text` is `None`, because the
+/// object is not text, it is only removed from the index.
+pub fn index_document (w: &WriteTransaction<'_>, id: u64, text: Option<&str>) -> std::result::Result<(), tonic::Status> {
+    unindex_document(w, id)?;
+    let text = match text {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let mut terms: HashMap<String, u32> = HashMap::new();
+    let mut length: u64 = 0;
+    for (_, _, term) in tokenize(text) {
+        *terms.entry(term).or_default() += 1;
+        length += 1;
+    }
+    insert_document(w, id, &terms, length)
+}
+
+/// Indexes a copy of an object with the same text as the original.
+pub fn copy_document (w: &WriteTransaction<'_>, src_id: u64, dest_id: u64) -> std::result::Result<(), tonic::Status> {
+    unindex_document(w, dest_id)?;
+    let (terms, length) = {
+        let by_id = w.open_table(TERMS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from text terms table"))?;
+        let docs = w.open_table(DOCS_TABLE)
+            .map_err(|_| tonic::Status::internal("could not read from text docs table"))?;
+        let length = match docs.get(src_id)
+            .map_err(|_| tonic::Status::internal("could not read from text docs table"))? {
+            Some(length) => length.value(),
+            None => return Ok(()),
+        };
+        let terms = by_id.range((src_id, "")..(src_id + 1, ""))
+            .map_err(|_| tonic::Status::internal("could not read from text terms table"))?
+            .map(|entry| entry.map(|(k, v)| (k.value().1.to_string(), v.value())))
+            .collect::<std::result::Result<HashMap<String, u32>, _>>()
+            .map_err(|_| tonic::Status::internal("could not read from text terms table"))?;
+        (terms, length)
+    };
+    insert_document(w, dest_id, &terms, length)
+}
+
+/// Ranks the objects containing any of the terms using Okapi BM25, from the
+/// most to the least relevant.
+pub fn rank (r: &ReadTransaction<'_>, terms: &[String]) -> std::result::Result<Vec<(u64, f64)>, tonic::Status> {
+    let postings = r.open_table(POSTINGS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from text postings table"))?;
+    let docs = r.open_table(DOCS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from text docs table"))?;
+    let stats = r.open_table(STATS_TABLE)
+        .map_err(|_| tonic::Status::internal("could not read from text stats table"))?;
+    let get_stat = |name: &str| stats.get(name)
+        .map_err(|_| tonic::Status::internal("could not read from text stats table"))
+        .map(|v| v.map(|v| v.value()).unwrap_or(0));
+    let total_docs = get_stat(DOCS_STAT)?;
+    if total_docs == 0 {
+        return Ok(vec![]);
+    }
+    let average_length = (get_stat(TERMS_STAT)? as f64 / total_docs as f64).max(1.0);
+    let mut scores: HashMap<u64, f64> = HashMap::new();
+    for term in terms {
+        let matches = postings.range((term.as_str(), 0)..=(term.as_str(), u64::MAX))
+            .map_err(|_| tonic::Status::internal("could not read from text postings table"))?
+            .map(|entry| entry.map(|(k, v)| (k.value().1, v.value())))
+            .collect::<std::result::Result<Vec<(u64, u32)>, _>>()
+            .map_err(|_| tonic::Status::internal("could not read from text postings table"))?;
+        let df = matches.len() as f64;
+        let idf = (1.0 + (total_docs as f64 - df + 0.5) / (df + 0.5)).ln();
+        for (id, count) in matches {
+            let length = docs.get(id)
+                .map_err(|_| tonic::Status::internal("could not read from text docs table"))?
+                .map(|v| v.value())
+                .unwrap_or(0) as f64;
+            let tf = count as f64;
+            let score = idf * (tf * (BM25_K1 + 1.0))
+                / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length));
+            *scores.entry(id).or_default() += score;
+        }
+    }
+    let mut ret: Vec<(u64, f64)> = scores.into_iter().collect();
+    ret.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok(ret)
+}
+
+fn floor_char_boundary (text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+fn ceil_char_boundary (text: &str, mut i: usize) -> usize {
+    while !text.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+/// Quotes the text around the first few occurrences of the terms, with
+/// ellipses where the text was cut off.
+pub fn make_snippets (text: &str, terms: &[String]) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::new();
+    let mut covered_until: usize = 0;
+    for (offset, length, term) in tokenize(text) {
+        if ret.len() >= MAX_SNIPPETS {
+            break;
+        }
+        if offset < covered_until || !terms.contains(&term) {
+            continue;
+        }
+        let start = floor_char_boundary(text, offset.saturating_sub(SNIPPET_CONTEXT));
+        let end = ceil_char_boundary(text, min(text.len(), offset + length + SNIPPET_CONTEXT));
+        let quote = text[start..end].split_whitespace().collect::<Vec<&str>>().join(" ");
+        ret.push(format!(
+            "{}{}{}",
+            if start > 0 { "…" } else { "" },
+            quote,
+            if end < text.len() { "…" } else { "" },
+        ));
+        covered_until = end;
+    }
+    ret
+}
@@ -0,0 +1,105 @@
+use std::cmp::min;
+use std::collections::HashSet;
+use unicode_normalization::UnicodeNormalization;
+
+/// Names with at least this trigram similarity to a query match it, even if
+/// they are more edits away from it than allowed. This is the default
+/// threshold of PostgreSQL's `pg_trgm`.
+const MIN_TRIGRAM_SIMILARITY: f64 = 0.3;
+
+/// By default, a query may be one edit away from a name for every this many
+/// characters in the query.
+const CHARS_PER_EDIT: usize = 4;
+
+/// Longer queries are refused, because matching takes time proportional to
+/// the length of the query times the length of each name.
+pub const MAX_QUERY_LENGTH: usize = 255;
+
+fn normalize (s: &str) -> Vec<char> {
+    s.nfkc().flat_map(char::to_lowercase).collect()
+}
+
+/// Returns the fewest characters that must be inserted, deleted, or
+/// substituted to make `query` appear somewhere within `name`. This is the
+/// Levenshtein distance, except that the characters of the name before and
+/// after the match are free.
+fn substring_distance (query: &[char], name: &[char]) -> usize {
+    // Each row is the distance between a prefix of the query and the best
+    // match ending at each character of the name. A match may begin anywhere,
+    // so the first row is all zeroes.
+    let mut row: Vec<usize> = vec![0; name.len() + 1];
+    for (i, qc) in query.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, nc) in name.iter().enumerate() {
+            let substitution = diagonal + usize::from(qc != nc);
+            diagonal = row[j + 1];
+            row[j + 1] = min(substitution, min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row.into_iter().min().unwrap_or(query.len())
+}
+
+/// Returns the trigrams of a string, padded as `pg_trgm` does, so that short
+/// strings and the starts and ends of strings count too.
+fn trigrams (s: &[char]) -> HashSet<[char; 3]> {
+    let padded: Vec<char> = [' ', ' '].into_iter()
+        .chain(s.iter().copied())
+        .chain([' '])
+        .collect();
+    padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// Returns the number of trigrams shared by two strings, as a fraction of the
+/// number of distinct trigrams of both.
+fn trigram_similarity (a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f64 {
+    let shared = a.intersection(b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+/// A fuzzy, case-insensitive query for the names of objects.
+pub struct NameQuery {
+    chars: Vec<char>,
+    trigrams: HashSet<[char; 3]>,
+    max_edits: usize,
+}
+
+impl NameQuery {
+
+    /// If `max_edits` is `None`, a default that grows with the length of the
+    /// query is used.
+    pub fn new (query: &str, max_edits: Option<u32>) -> std::result::Result<Self, tonic::Status> {
+        let chars = normalize(query);
+        if chars.is_empty() {
+            return Err(tonic::Status::invalid_argument("name query is empty"));
+        }
+        if chars.len() > MAX_QUERY_LENGTH {
+            return Err(tonic::Status::invalid_argument("name query is too long"));
+        }
+        let max_edits = max_edits
+            .map(|m| m as usize)
+            .unwrap_or(chars.len() / CHARS_PER_EDIT);
+        let trigrams = trigrams(&chars);
+        Ok(NameQuery { chars, trigrams, max_edits })
+    }
+
+    /// Returns how well a name matches the query, from 0 to 2, or `None` if it
+    /// does not match at all. The score is the fraction of the query that did
+    /// not have to be edited to appear in the name plus the trigram similarity
+    /// of the query and the name, so exact matches of the whole name score 2.
+    pub fn score (&self, name: &str) -> Option<f64> {
+        let name = normalize(name);
+        let distance = substring_distance(&self.chars, &name);
+        let similarity = trigram_similarity(&self.trigrams, &trigrams(&name));
+        if distance > self.max_edits && similarity < MIN_TRIGRAM_SIMILARITY {
+            return None;
+        }
+        let closeness = 1.0 - min(distance, self.chars.len()) as f64 / self.chars.len() as f64;
+        Some(closeness + similarity)
+    }
+
+}
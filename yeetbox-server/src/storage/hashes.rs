@@ -0,0 +1,97 @@
+use crate::grpc::remotefs::{DocumentFormat, Hashes};
+use sha2::{Digest, Sha512};
+use sha3::Sha3_512;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use xxhash_rust::xxh64::Xxh64;
+
+/// Blobs are hashed in pieces of this many bytes, so they need not fit in memory.
+pub const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The state of the hashers is not kept, so appending to a file hashes all of
+/// it again. The hashes of versions appended to files larger than this are
+/// left unknown instead, so that appending does not take ever longer.
+pub const MAX_APPEND_HASHED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Computes every hash of `Hashes` at once, over data that arrives in pieces.
+pub struct ContentHasher {
+    sha2_512: Sha512,
+    sha3_512: Sha3_512,
+    xxh64: Xxh64,
+}
+
+impl ContentHasher {
+
+    pub fn new () -> Self {
+        ContentHasher {
+            sha2_512: Sha512::new(),
+            sha3_512: Sha3_512::new(),
+            xxh64: Xxh64::new(0),
+        }
+    }
+
+    pub fn update (&mut self, data: &[u8]) {
+        self.sha2_512.update(data);
+        self.sha3_512.update(data);
+        self.xxh64.update(data);
+    }
+
+    /// Hashes the first `length` bytes of the blob at `path`, or all of it if
+    /// no length is given, returning how many bytes were hashed.
+    pub async fn update_from_blob (&mut self, path: &Path, length: Option<u64>) -> std::io::Result<u64> {
+        let f = File::open(path).await?;
+        let mut f = f.take(length.unwrap_or(u64::MAX));
+        let mut buf = vec![0; HASH_BUFFER_SIZE];
+        let mut hashed: u64 = 0;
+        loop {
+            let bytes_read = f.read(&mut buf).await?;
+            if bytes_read == 0 {
+                break;
+            }
+            self.update(&buf[..bytes_read]);
+            hashed += bytes_read as u64;
+        }
+        Ok(hashed)
+    }
+
+    /// The XXH64 hash is given in big-endian byte order, which is how it is
+    /// conventionally printed.
+    pub fn finish (self) -> Hashes {
+        Hashes {
+            sha2_512: self.sha2_512.finalize().to_vec(),
+            sha3_512: self.sha3_512.finalize().to_vec(),
+            xxh64: self.xxh64.digest().to_be_bytes().to_vec(),
+        }
+    }
+
+}
+
+/// Hashes the first `length` bytes of the blob at `path`, or all of it if no
+/// length is given, returning the hashes and how many bytes were hashed.
+pub async fn hash_blob (path: &Path, length: Option<u64>) -> std::io::Result<(Hashes, u64)> {
+    let mut hasher = ContentHasher::new();
+    let hashed = hasher.update_from_blob(path, length).await?;
+    Ok((hasher.finish(), hashed))
+}
+
+/// Returns whether `actual` has every hash that is given in `expected`. The
+/// hashes that are not given are not checked.
+pub fn matches (expected: &Hashes, actual: &Hashes) -> bool {
+    [
+        (&expected.sha2_512, &actual.sha2_512),
+        (&expected.sha3_512, &actual.sha3_512),
+        (&expected.xxh64, &actual.xxh64),
+    ].into_iter().all(|(e, a)| e.is_empty() || e == a)
+}
+
+/// The ETag of contents with these hashes, which is the first 128 bits of
+/// their SHA-512 hash in hexadecimal, quoted as in IETF RFC 9110. Contents that
+/// were translated to another format get a different ETag for each format.
+pub fn etag (hashes: &Hashes, format: DocumentFormat) -> String {
+    let prefix = hex::encode(&hashes.sha2_512[..hashes.sha2_512.len().min(16)]);
+    match format {
+        DocumentFormat::Original => format!("\"{}\"", prefix),
+        _ => format!("\"{}-{}\"", prefix, format.as_str_name().to_lowercase()),
+    }
+}
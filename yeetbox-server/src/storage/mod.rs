@@ -1,4 +1,9 @@
 pub mod database;
+pub mod fulltext;
+pub mod fuzzy;
+pub mod hashes;
+pub mod query;
+pub mod translate;
 // pub mod file;
 use crate::grpc::remotefs::{
     AbortTransactionArg, AbortTransactionResult, AppendArg, AppendResult,
@@ -12,6 +17,7 @@ use crate::grpc::remotefs::{
     SetAttributesResult, StartTransactionArg, StartTransactionResult, UnlinkArg, UnlinkResult,
     UploadArg, UploadResult, WatchManyArg, WatchOnceArg, WatchOnceResult,
     GetAvailableSaslMechanismsResult, AuthenticateArg, AuthenticateResult,
+    SearchArg, SearchResult, QueryArg, QueryResult,
 };
 
 type WatchManyStream = tonic::codec::Streaming<FileSystemEvent>;
@@ -138,4 +144,14 @@ pub trait Storage {
         &self,
         request: tonic::Request<UnlinkArg>,
     ) -> std::result::Result<tonic::Response<UnlinkResult>, tonic::Status>;
+
+    async fn search(
+        &self,
+        request: tonic::Request<SearchArg>,
+    ) -> std::result::Result<tonic::Response<SearchResult>, tonic::Status>;
+
+    async fn query(
+        &self,
+        request: tonic::Request<QueryArg>,
+    ) -> std::result::Result<tonic::Response<QueryResult>, tonic::Status>;
 }
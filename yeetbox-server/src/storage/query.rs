@@ -0,0 +1,178 @@
+use crate::grpc::remotefs::CsvShape;
+use serde_json_path::JsonPath;
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+use sxd_xpath::nodeset::Node;
+use sxd_xpath::{Context, Factory, Value};
+
+/// Files larger than this are not parsed for a JSONPath or XPath, because the
+/// whole file must be held in memory.
+pub const MAX_QUERY_SIZE: u64 = 64 * 1024 * 1024;
+
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 10000;
+
+/// Returns each value in a JSON document that matches a JSONPath, as JSON, or
+/// `None` if the document is not JSON. The `bool` is whether there were more
+/// than `limit` matches.
+pub fn query_json_path (data: &[u8], expr: &str, limit: usize) -> std::result::Result<Option<(Vec<String>, bool)>, tonic::Status> {
+    let path = JsonPath::parse(expr)
+        .map_err(|e| tonic::Status::invalid_argument(format!("invalid JSONPath: {}", e)))?;
+    let value: serde_json::Value = match serde_json::from_slice(data) {
+        Ok(value) => value,
+        Err(_) => return Ok(None),
+    };
+    let nodes = path.query(&value).all();
+    let more = nodes.len() > limit;
+    let matches = nodes.into_iter()
+        .take(limit)
+        .map(|node| node.to_string())
+        .collect();
+    Ok(Some((matches, more)))
+}
+
+pub fn escape_xml (s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn write_element (element: Element<'_>, out: &mut String) {
+    let name = match element.preferred_prefix() {
+        Some(prefix) => format!("{}:{}", prefix, element.name().local_part()),
+        None => element.name().local_part().to_owned(),
+    };
+    out.push('<');
+    out.push_str(&name);
+    for attr in element.attributes() {
+        out.push(' ');
+        if let Some(prefix) = attr.preferred_prefix() {
+            out.push_str(prefix);
+            out.push(':');
+        }
+        out.push_str(attr.name().local_part());
+        out.push_str("=\"");
+        escape_xml(attr.value(), out);
+        out.push('"');
+    }
+    let children = element.children();
+    if children.is_empty() {
+        out.push_str("/>");
+        return;
+    }
+    out.push('>');
+    for child in children {
+        match child {
+            ChildOfElement::Element(e) => write_element(e, out),
+            ChildOfElement::Text(t) => escape_xml(t.text(), out),
+            ChildOfElement::Comment(c) => {
+                out.push_str("<!--");
+                out.push_str(c.text());
+                out.push_str("-->");
+            },
+            ChildOfElement::ProcessingInstruction(pi) => {
+                out.push_str("<?");
+                out.push_str(pi.target());
+                if let Some(value) = pi.value() {
+                    out.push(' ');
+                    out.push_str(value);
+                }
+                out.push_str("?>");
+            },
+        }
+    }
+    out.push_str("</");
+    out.push_str(&name);
+    out.push('>');
+}
+
+fn node_to_string (node: Node<'_>) -> String {
+    match node {
+        Node::Element(element) => {
+            let mut out = String::new();
+            write_element(element, &mut out);
+            out
+        },
+        Node::Root(root) => {
+            let mut out = String::new();
+            for child in root.children() {
+                if let ChildOfRoot::Element(element) = child {
+                    write_element(element, &mut out);
+                }
+            }
+            out
+        },
+        node => node.string_value(),
+    }
+}
+
+/// Returns each node in an XML document that matches an XPath, as XML for
+/// elements and as text otherwise, or `None` if the document is not XML. The
+/// `bool` is whether there were more than `limit` matches.
+pub fn query_xpath (data: &[u8], expr: &str, limit: usize) -> std::result::Result<Option<(Vec<String>, bool)>, tonic::Status> {
+    let xpath = Factory::new().build(expr)
+        .map_err(|e| tonic::Status::invalid_argument(format!("invalid XPath: {}", e)))?
+        .ok_or_else(|| tonic::Status::invalid_argument("invalid XPath: empty"))?;
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return Ok(None),
+    };
+    let package = match sxd_document::parser::parse(text) {
+        Ok(package) => package,
+        Err(_) => return Ok(None),
+    };
+    let document = package.as_document();
+    let value = xpath.evaluate(&Context::new(), document.root())
+        .map_err(|e| tonic::Status::invalid_argument(format!("could not evaluate XPath: {}", e)))?;
+    let ret = match value {
+        Value::Nodeset(nodes) => {
+            let nodes = nodes.document_order();
+            let more = nodes.len() > limit;
+            let matches = nodes.into_iter()
+                .take(limit)
+                .map(node_to_string)
+                .collect();
+            (matches, more)
+        },
+        Value::Boolean(b) => (vec![ b.to_string() ], false),
+        Value::Number(n) => (vec![ n.to_string() ], false),
+        Value::String(s) => (vec![ s ], false),
+    };
+    Ok(Some(ret))
+}
+
+/// Returns the shape of a CSV file, or `None` if it is not UTF-8 text. Unlike
+/// the other queries, this reads the file as it goes, so it may be of any size.
+pub fn csv_shape <R: std::io::Read> (reader: R, delimiter: u8, has_header: bool) -> std::io::Result<Option<CsvShape>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut shape = CsvShape::default();
+    let mut record = csv::StringRecord::new();
+    let mut first = true;
+    loop {
+        match reader.read_record(&mut record) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => match e.into_kind() {
+                csv::ErrorKind::Io(e) => return Err(e),
+                _ => return Ok(None),
+            },
+        };
+        shape.columns = shape.columns.max(record.len() as u32);
+        if first && has_header {
+            shape.header = record.iter().map(|field| field.to_owned()).collect();
+        } else {
+            shape.rows += 1;
+        }
+        first = false;
+    }
+    Ok(Some(shape))
+}
@@ -0,0 +1,221 @@
+use crate::grpc::remotefs::DocumentFormat;
+use crate::storage::query::escape_xml;
+use serde_json::{Map, Number, Value};
+
+/// Files larger than this are not translated, because the whole file and its
+/// translation must be held in memory.
+pub const MAX_TRANSLATION_SIZE: u64 = 64 * 1024 * 1024;
+
+pub enum TranslationError {
+    /// The file could not be parsed in the format indicated by its name.
+    Unparseable,
+    /// The file cannot be represented in the requested format.
+    Unrepresentable,
+}
+
+/// Returns the format of a file, based on the extension of its name, if it
+/// is one that can be translated.
+pub fn source_format (file_name: &str) -> Option<DocumentFormat> {
+    let extension = file_name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => Some(DocumentFormat::Json),
+        "yaml" | "yml" => Some(DocumentFormat::Yaml),
+        "toml" => Some(DocumentFormat::Toml),
+        "xml" => Some(DocumentFormat::Xml),
+        _ => None,
+    }
+}
+
+fn toml_to_json (value: toml::Value) -> Result<Value, TranslationError> {
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i.into()),
+        toml::Value::Float(f) => Value::Number(Number::from_f64(f).ok_or(TranslationError::Unrepresentable)?),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter()
+            .map(toml_to_json)
+            .collect::<Result<Vec<Value>, TranslationError>>()?),
+        toml::Value::Table(table) => Value::Object(table.into_iter()
+            .map(|(k, v)| Ok((k, toml_to_json(v)?)))
+            .collect::<Result<Map<String, Value>, TranslationError>>()?),
+    })
+}
+
+/// Converts an element to JSON. An element with only text is a string, and an
+/// empty element is null. Otherwise, it is an object of its attributes, its
+/// text, if any, and its child elements, where those of the same name are
+/// collected into an array.
+fn xml_to_json (element: roxmltree::Node<'_, '_>) -> Value {
+    let mut map = Map::new();
+    for attr in element.attributes() {
+        map.insert(format!("@{}", attr.name()), Value::String(attr.value().to_owned()));
+    }
+    let mut text = String::new();
+    for child in element.children() {
+        if child.is_element() {
+            let name = child.tag_name().name().to_owned();
+            let value = xml_to_json(child);
+            match map.get_mut(&name) {
+                Some(Value::Array(values)) => values.push(value),
+                Some(existing) => {
+                    let first = existing.take();
+                    *existing = Value::Array(vec![ first, value ]);
+                },
+                None => {
+                    map.insert(name, value);
+                },
+            }
+        } else if child.is_text() {
+            text.push_str(child.text().unwrap_or_default());
+        }
+    }
+    let text = text.trim();
+    if map.is_empty() {
+        if text.is_empty() {
+            return Value::Null;
+        }
+        return Value::String(text.to_owned());
+    }
+    if !text.is_empty() {
+        map.insert("#text".to_owned(), Value::String(text.to_owned()));
+    }
+    Value::Object(map)
+}
+
+fn is_xml_name (name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
+
+fn scalar_text (value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Writes a value as an element, or, if it is an array, as one element for
+/// each of its items.
+fn write_xml_element (name: &str, value: &Value, out: &mut String) -> Result<(), TranslationError> {
+    if !is_xml_name(name) {
+        return Err(TranslationError::Unrepresentable);
+    }
+    if let Value::Array(values) = value {
+        for value in values {
+            if value.is_array() {
+                return Err(TranslationError::Unrepresentable);
+            }
+            write_xml_element(name, value, out)?;
+        }
+        return Ok(());
+    }
+    out.push('<');
+    out.push_str(name);
+    let mut content = String::new();
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter() {
+                if let Some(attr_name) = key.strip_prefix('@') {
+                    let text = scalar_text(value).ok_or(TranslationError::Unrepresentable)?;
+                    if !is_xml_name(attr_name) {
+                        return Err(TranslationError::Unrepresentable);
+                    }
+                    out.push(' ');
+                    out.push_str(attr_name);
+                    out.push_str("=\"");
+                    escape_xml(&text, out);
+                    out.push('"');
+                } else if key == "#text" {
+                    let text = scalar_text(value).ok_or(TranslationError::Unrepresentable)?;
+                    escape_xml(&text, &mut content);
+                } else {
+                    write_xml_element(key, value, &mut content)?;
+                }
+            }
+        },
+        value => escape_xml(&scalar_text(value).unwrap_or_default(), &mut content),
+    };
+    if content.is_empty() {
+        out.push_str("/>");
+        return Ok(());
+    }
+    out.push('>');
+    out.push_str(&content);
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+    Ok(())
+}
+
+fn parse (data: &[u8], format: DocumentFormat) -> Result<Value, TranslationError> {
+    let text = std::str::from_utf8(data).map_err(|_| TranslationError::Unparseable)?;
+    match format {
+        DocumentFormat::Json => serde_json::from_str(text)
+            .map_err(|_| TranslationError::Unparseable),
+        DocumentFormat::Yaml => {
+            let value: serde_yaml::Value = serde_yaml::from_str(text)
+                .map_err(|_| TranslationError::Unparseable)?;
+            // This fails for keys that are not strings, for instance.
+            serde_yaml::from_value(value).map_err(|_| TranslationError::Unrepresentable)
+        },
+        DocumentFormat::Toml => {
+            let table: toml::Table = toml::from_str(text)
+                .map_err(|_| TranslationError::Unparseable)?;
+            toml_to_json(toml::Value::Table(table))
+        },
+        DocumentFormat::Xml => {
+            let doc = roxmltree::Document::parse(text)
+                .map_err(|_| TranslationError::Unparseable)?;
+            let root = doc.root_element();
+            let mut map = Map::new();
+            map.insert(root.tag_name().name().to_owned(), xml_to_json(root));
+            Ok(Value::Object(map))
+        },
+        DocumentFormat::Original => Err(TranslationError::Unparseable),
+    }
+}
+
+fn serialize (value: &Value, format: DocumentFormat) -> Result<Vec<u8>, TranslationError> {
+    match format {
+        DocumentFormat::Json => serde_json::to_vec_pretty(value)
+            .map_err(|_| TranslationError::Unrepresentable),
+        DocumentFormat::Yaml => serde_yaml::to_string(value)
+            .map(String::into_bytes)
+            .map_err(|_| TranslationError::Unrepresentable),
+        // TOML has no null, and the document must be a table.
+        DocumentFormat::Toml => toml::to_string_pretty(value)
+            .map(String::into_bytes)
+            .map_err(|_| TranslationError::Unrepresentable),
+        // An XML document has exactly one root element.
+        DocumentFormat::Xml => {
+            let (name, root) = match value {
+                Value::Object(map) if map.len() == 1 => map.iter().next().unwrap(),
+                _ => return Err(TranslationError::Unrepresentable),
+            };
+            if root.is_array() || name.starts_with('@') || name == "#text" {
+                return Err(TranslationError::Unrepresentable);
+            }
+            let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            write_xml_element(name, root, &mut out)?;
+            out.push('\n');
+            Ok(out.into_bytes())
+        },
+        DocumentFormat::Original => Err(TranslationError::Unrepresentable),
+    }
+}
+
+/// Translates a document from one format to another.
+pub fn translate (data: &[u8], from: DocumentFormat, to: DocumentFormat) -> Result<Vec<u8>, TranslationError> {
+    if from == to {
+        return Ok(data.to_vec());
+    }
+    serialize(&parse(data, from)?, to)
+}
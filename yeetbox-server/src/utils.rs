@@ -9,6 +9,13 @@ pub fn grpc_timestamp_to_chrono (grpc_time: &prost_types::Timestamp) -> Option<D
     }
 }
 
+pub fn chrono_to_grpc_timestamp (dt: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    }
+}
+
 pub fn system_time_to_grpc_timestamp (systime: SystemTime) -> prost_types::Timestamp {
     prost_types::Timestamp {
         seconds: systime.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64,
@@ -42,3 +49,20 @@ pub fn unix_perms_to_u16 (perms: &UnixPermissions) -> u16 {
     | setgid
     | setuid
 }
+
+pub fn u16_to_unix_perms (flags: u16) -> UnixPermissions {
+    UnixPermissions {
+        u_r:    flags & 0o0400 > 0,
+        u_w:    flags & 0o0200 > 0,
+        u_x:    flags & 0o0100 > 0,
+        g_r:    flags & 0o0040 > 0,
+        g_w:    flags & 0o0020 > 0,
+        g_x:    flags & 0o0010 > 0,
+        o_r:    flags & 0o0004 > 0,
+        o_w:    flags & 0o0002 > 0,
+        o_x:    flags & 0o0001 > 0,
+        sticky: flags & 0o1000 > 0,
+        setgid: flags & 0o2000 > 0,
+        setuid: flags & 0o4000 > 0,
+    }
+}
@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use anyhow::bail;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_parser::der_parser::ber::{BerObject, BerObjectContent};
+use x509_parser::der_parser::oid::Oid;
+use x509_parser::der_parser::parse_der;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use crate::grpc::remotefs::DistinguishedNameRestrictions;
+
+/// The attribute types that may be restricted by
+/// `DistinguishedNameRestrictions`, by OID and short name.
+pub const DN_ATTRIBUTE_TYPES: [(&str, &str); 13] = [
+    ("2.5.4.6", "c"),
+    ("2.5.4.8", "st"),
+    ("2.5.4.7", "l"),
+    ("2.5.4.10", "o"),
+    ("2.5.4.11", "ou"),
+    ("2.5.4.3", "cn"),
+    ("2.5.4.42", "g"),
+    ("2.5.4.4", "sn"),
+    ("2.5.4.43", "i"),
+    ("2.5.4.44", "q"),
+    ("2.5.4.9", "street"),
+    ("2.5.4.46", "dnQualifier"),
+    ("2.5.4.5", "serialNumber"),
+];
+
+pub const SUBJECT_DIRECTORY_ATTRIBUTES_OID: &str = "2.5.29.9";
+pub const CLEARANCE_OID: &str = "2.5.4.55";
+
+/// The classification assumed by a clearance that does not list any.
+pub const CLASSIFICATION_UNCLASSIFIED: u32 = 1;
+
+/// The names of the classifications of the `ClassList` of IETF RFC 5755.
+pub const CLASSIFICATION_NAMES: [(&str, u32); 6] = [
+    ("unmarked", 0),
+    ("unclassified", 1),
+    ("restricted", 2),
+    ("confidential", 3),
+    ("secret", 4),
+    ("top-secret", 5),
+];
+
+/// A security clearance, as in the `Clearance` attribute of IETF RFC 5755.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Clearance {
+    /// The OID of the security policy, in dotted-decimal notation.
+    pub policy_id: String,
+    /// The classifications for which the holder is cleared.
+    pub class_list: Vec<u32>,
+    /// Security categories, each of the form `<type OID>:<value>`.
+    pub categories: Vec<String>,
+}
+
+/// Parses a classification by name or number.
+pub fn parse_classification (s: &str) -> Option<u32> {
+    CLASSIFICATION_NAMES.iter()
+        .find(|(name, _)| *name == s)
+        .map(|(_, c)| *c)
+        .or_else(|| s.parse().ok())
+}
+
+/// Parses configured clearances, one per line, in the form
+/// `<user ID> <policy OID> <classifications> [<categories>]`, where the
+/// classifications and categories are comma-separated. Users may have one
+/// clearance per policy.
+pub fn parse_clearances (text: &str) -> anyhow::Result<HashMap<String, Vec<Clearance>>> {
+    let mut clearances: HashMap<String, Vec<Clearance>> = HashMap::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (user_id, policy_id, class_list, categories) = match fields.as_slice() {
+            [u, p, c] => (*u, *p, *c, ""),
+            [u, p, c, cats] => (*u, *p, *c, *cats),
+            _ => bail!("malformed clearance: {}", line),
+        };
+        let class_list = class_list.split(',')
+            .map(|c| parse_classification(c).ok_or_else(|| anyhow::anyhow!("unknown classification {}", c)))
+            .collect::<anyhow::Result<Vec<u32>>>()?;
+        clearances.entry(user_id.to_owned()).or_default().push(Clearance {
+            policy_id: policy_id.to_owned(),
+            class_list,
+            categories: categories.split_terminator(',').map(|c| c.to_owned()).collect(),
+        });
+    }
+    Ok(clearances)
+}
+
+/// The attributes of a distinguished name, keyed by short name.
+pub type DnAttributes = HashMap<&'static str, String>;
+
+/// Returns the attributes of the subject DN of a DER-encoded certificate.
+///
+/// If an attribute type appears more than once, the value from the highest
+/// (most superior) RDN wins, so that inferior CAs cannot create a subject
+/// like `C=SE,O=Bjog,C=US` to bypass a `C=US` restriction.
+pub fn subject_dn_attributes (cert_der: &[u8]) -> anyhow::Result<DnAttributes> {
+    let (_, cert) = X509Certificate::from_der(cert_der)?;
+    let mut attrs = DnAttributes::new();
+    // An RDNSequence is ordered from the most superior RDN to the subject.
+    for atav in cert.subject().iter_attributes() {
+        let oid = atav.attr_type().to_id_string();
+        let maybe_name = DN_ATTRIBUTE_TYPES.iter().find(|(o, _)| *o == oid.as_str());
+        let name = match maybe_name {
+            Some((_, name)) => *name,
+            None => continue,
+        };
+        if attrs.contains_key(name) {
+            continue;
+        }
+        // Values that are not strings cannot be matched anyway.
+        if let Ok(value) = atav.as_str() {
+            attrs.insert(name, value.to_owned());
+        }
+    }
+    Ok(attrs)
+}
+
+/// Renders a `SecurityCategory` as `<type OID>:<value>`, where the value is
+/// the string, if it is one, and otherwise the hex of its DER encoding.
+fn parse_security_category (obj: &BerObject) -> anyhow::Result<String> {
+    let (category_type, value) = match obj.as_sequence()?.as_slice() {
+        [category_type, value] => (category_type, value),
+        _ => bail!("malformed security category"),
+    };
+    // The type is [0] IMPLICIT and the value is [1] EXPLICIT.
+    let category_type = Oid::new(Cow::Borrowed(category_type.as_slice()?)).to_id_string();
+    let value = value.as_slice()?;
+    let rendered = match parse_der(value) {
+        Ok((_, inner)) => match inner.as_str() {
+            Ok(s) => s.to_owned(),
+            Err(_) => hex::encode(value),
+        },
+        Err(_) => hex::encode(value),
+    };
+    Ok(format!("{}:{}", category_type, rendered))
+}
+
+fn parse_clearance (obj: &BerObject) -> anyhow::Result<Clearance> {
+    let mut fields = obj.as_sequence()?.iter();
+    let policy_id = match fields.next() {
+        Some(policy_id) => policy_id.as_oid()?.to_id_string(),
+        None => bail!("clearance is missing its policy"),
+    };
+    let mut clearance = Clearance {
+        policy_id,
+        class_list: vec![CLASSIFICATION_UNCLASSIFIED],
+        categories: vec![],
+    };
+    for field in fields {
+        match &field.content {
+            BerObjectContent::BitString(_, bits) => {
+                clearance.class_list = (0..bits.data.len() * 8)
+                    .filter(|i| bits.is_set(*i))
+                    .map(|i| i as u32)
+                    .collect();
+            },
+            BerObjectContent::Set(categories) => {
+                for category in categories {
+                    clearance.categories.push(parse_security_category(category)?);
+                }
+            },
+            _ => bail!("malformed clearance"),
+        };
+    }
+    Ok(clearance)
+}
+
+/// Returns the clearances in the subject directory attributes extension of a
+/// DER-encoded certificate.
+pub fn subject_clearances (cert_der: &[u8]) -> anyhow::Result<Vec<Clearance>> {
+    let (_, cert) = X509Certificate::from_der(cert_der)?;
+    let mut clearances = Vec::new();
+    for ext in cert.extensions() {
+        if ext.oid.to_id_string() != SUBJECT_DIRECTORY_ATTRIBUTES_OID {
+            continue;
+        }
+        let (_, attrs) = parse_der(ext.value)?;
+        for attr in attrs.as_sequence()? {
+            let (attr_type, values) = match attr.as_sequence()?.as_slice() {
+                [attr_type, values] => (attr_type, values),
+                _ => bail!("malformed attribute"),
+            };
+            if attr_type.as_oid()?.to_id_string() != CLEARANCE_OID {
+                continue;
+            }
+            for value in values.as_set()? {
+                clearances.push(parse_clearance(value)?);
+            }
+        }
+    }
+    Ok(clearances)
+}
+
+/// Returns true if every non-empty field of the restrictions equals the
+/// corresponding attribute of the DN.
+pub fn dn_satisfies (attrs: &DnAttributes, restrictions: &DistinguishedNameRestrictions) -> bool {
+    let required = [
+        ("c", &restrictions.c),
+        ("st", &restrictions.st),
+        ("l", &restrictions.l),
+        ("o", &restrictions.o),
+        ("ou", &restrictions.ou),
+        ("cn", &restrictions.cn),
+        ("g", &restrictions.g),
+        ("sn", &restrictions.sn),
+        ("i", &restrictions.i),
+        ("q", &restrictions.q),
+        ("street", &restrictions.street),
+        ("dnQualifier", &restrictions.dn_qualifier),
+        ("serialNumber", &restrictions.serial_number),
+    ];
+    required.iter()
+        .filter(|(_, v)| v.len() > 0)
+        .all(|(k, v)| attrs.get(k).is_some_and(|a| a == *v))
+}
+
+/// Computes the `tls-server-end-point` channel binding data defined in IETF
+/// RFC 5929 from the server's PEM-encoded certificate.
+pub fn tls_server_end_point (cert_pem: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(cert_pem)?;
+    let (_, cert) = X509Certificate::from_der(&pem.contents)?;
+    // MD5 and SHA-1 are upgraded to SHA-256, per the RFC.
+    let hash = match cert.signature_algorithm.algorithm.to_id_string().as_str() {
+        "1.2.840.113549.1.1.12" | "1.2.840.10045.4.3.3" => Sha384::digest(&pem.contents).to_vec(),
+        "1.2.840.113549.1.1.13" | "1.2.840.10045.4.3.4" => Sha512::digest(&pem.contents).to_vec(),
+        _ => Sha256::digest(&pem.contents).to_vec(),
+    };
+    Ok(hash)
+}